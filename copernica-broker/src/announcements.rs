@@ -0,0 +1,104 @@
+use {
+    copernica_packets::{Nonce, HBFI, BFI, LinkId},
+    copernica_common::constants::ANNOUNCE_LIFETIME,
+    std::{
+        collections::HashMap,
+        time::{Duration, Instant},
+    },
+};
+#[derive(Clone, Debug)]
+struct Route {
+    distance: u8,
+    expiry: Instant,
+}
+#[derive(Clone, Debug)]
+struct Namespace {
+    routes: HashMap<LinkId, Route>,
+    // the nonce of the last Announce flooded, a copy arriving over another path isn't flooded again
+    nonce: Option<Nonce>,
+}
+// The producers which have announced an app and the links, and how many
+// brokers away, they were heard on. Routes are forgotten unless the producer
+// announces again within the lifetime.
+#[derive(Clone)]
+pub struct Announcements {
+    lifetime: Duration,
+    namespaces: HashMap<(BFI, BFI), Namespace>,
+}
+impl Announcements {
+    pub fn new() -> Self {
+        Self {
+            lifetime: Duration::from_millis(ANNOUNCE_LIFETIME),
+            namespaces: HashMap::new(),
+        }
+    }
+    pub fn set_lifetime(&mut self, lifetime: Duration) {
+        self.lifetime = lifetime;
+    }
+    pub fn len(&self) -> usize {
+        self.namespaces.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.namespaces.is_empty()
+    }
+    // Returns true when the Announce hasn't been seen before and should be flooded on.
+    pub fn learn(&mut self, hbfi: &HBFI, nonce: &Nonce, link_id: &LinkId, distance: u8, now: Instant) -> bool {
+        let namespace = self.namespaces
+            .entry((hbfi.res.clone(), hbfi.app.clone()))
+            .or_insert_with(|| Namespace { routes: HashMap::new(), nonce: None });
+        let expiry = now + self.lifetime;
+        let route = namespace.routes
+            .entry(link_id.clone())
+            .or_insert(Route { distance, expiry });
+        if route.expiry <= now || distance <= route.distance {
+            route.distance = distance;
+        }
+        route.expiry = expiry;
+        if namespace.nonce.as_ref() == Some(nonce) {
+            return false
+        }
+        namespace.nonce = Some(nonce.clone());
+        true
+    }
+    pub fn routes(&self, hbfi: &HBFI, now: Instant) -> Vec<(LinkId, u8)> {
+        match self.namespaces.get(&(hbfi.res.clone(), hbfi.app.clone())) {
+            Some(namespace) => namespace.routes
+                .iter()
+                .filter(|(_, route)| route.expiry > now)
+                .map(|(link_id, route)| (link_id.clone(), route.distance))
+                .collect(),
+            None => vec![],
+        }
+    }
+    pub fn expire(&mut self, now: Instant) {
+        self.namespaces.retain(|_, namespace| {
+            namespace.routes.retain(|_, route| route.expiry > now);
+            !namespace.routes.is_empty()
+        });
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use copernica_packets::{PrivateIdentityInterface, PublicIdentityInterface, ReplyTo};
+
+    #[test]
+    fn test_routes_prefer_shortest_and_expire() {
+        let response_sid = PrivateIdentityInterface::new_key();
+        let announced = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "app", "", "", "").unwrap();
+        let requested = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "app", "m0d", "fun", "arg").unwrap();
+        let link0 = LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+        let link1 = LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+        let nonce = Nonce::new();
+        let now = Instant::now();
+        let mut announcements = Announcements::new();
+        assert!(announcements.learn(&announced, &nonce, &link0, 3, now));
+        assert!(!announcements.learn(&announced, &nonce, &link1, 2, now));
+        assert!(announcements.learn(&announced, &Nonce::new(), &link0, 1, now));
+        let mut routes = announcements.routes(&requested, now);
+        routes.sort_by_key(|(_, distance)| *distance);
+        assert_eq!(routes, vec![(link0, 1), (link1, 2)]);
+        announcements.expire(now + Duration::from_millis(ANNOUNCE_LIFETIME));
+        assert!(announcements.is_empty());
+    }
+}
@@ -1,52 +1,119 @@
 use {
     std::{
         collections::{
+            BTreeMap,
             HashMap,
             HashSet,
             hash_map::Keys
         },
         iter::FromIterator,
+        time::{Duration, Instant},
         vec::Vec
     },
     copernica_packets::{LinkId, HBFI, HBFIOnlyKeys},
+    copernica_common::constants::{BAYES_PENALTY_FACTOR, BAYES_HALF_LIFE, BAYES_BFIS_SIZE, BAYES_FORGET_THRESHOLD},
 };
+// A count which halves every half-life, so what a link served a week ago
+// weighs less than what it serves now. A zero half-life never decays.
+#[derive(Clone, Copy, Debug)]
+struct Count {
+    value: f64,
+    at: Instant,
+}
+impl Count {
+    fn new(value: f64, now: Instant) -> Count {
+        Count { value, at: now }
+    }
+    fn decayed(&self, now: Instant, half_life: Duration) -> f64 {
+        if half_life.as_nanos() == 0 {
+            return self.value;
+        }
+        let elapsed = now.saturating_duration_since(self.at).as_secs_f64();
+        self.value * 0.5f64.powf(elapsed / half_life.as_secs_f64())
+    }
+    fn add(&mut self, value: f64, now: Instant, half_life: Duration) {
+        self.value = self.decayed(now, half_life) + value;
+        self.at = now;
+    }
+}
+struct Trained {
+    linkids: HashMap<LinkId, Count>,
+    tick: u64,
+}
+// The BFI counts, ordered by when they were last trained so the stalest
+// is found without a scan.
 struct BFIs {
-    bfis: HashMap<HBFIOnlyKeys, HashMap<LinkId, i64>>,
+    bfis: HashMap<HBFIOnlyKeys, Trained>,
+    order: BTreeMap<u64, HBFIOnlyKeys>,
+    tick: u64,
+    capacity: usize,
 }
 impl BFIs {
     pub fn new() -> BFIs {
         BFIs {
             bfis: HashMap::new(),
+            order: BTreeMap::new(),
+            tick: 0,
+            capacity: BAYES_BFIS_SIZE,
         }
     }
-    fn train(&mut self, bfis: &HBFI, link: &LinkId) {
+    fn add(&mut self, bfis: &HBFI, link: &LinkId, value: f64, now: Instant, half_life: Duration) {
+        let key = HBFIOnlyKeys(bfis.clone());
+        if !self.bfis.contains_key(&key) && self.bfis.len() >= self.capacity {
+            self.evict_stalest();
+        }
+        self.tick += 1;
+        let tick = self.tick;
+        let trained = self.bfis
+            .entry(key.clone())
+            .or_insert(Trained { linkids: HashMap::new(), tick });
+        self.order.remove(&trained.tick);
+        self.order.insert(tick, key);
+        trained.tick = tick;
+        let count = trained.linkids.entry(link.clone()).or_insert(Count::new(0.0, now));
+        count.add(value, now, half_life);
+    }
+    fn train(&mut self, bfis: &HBFI, link: &LinkId, now: Instant, half_life: Duration) {
         //debug!("train {:?}", bfis);
-        let linkids = self.bfis
-            .entry(HBFIOnlyKeys(bfis.clone()))
-            .or_insert(HashMap::new());
-        let value = linkids.entry(link.clone()).or_insert(0);
-        *value += 1;
+        self.add(bfis, link, 1.0, now, half_life);
     }
-    fn super_train(&mut self, bfis: &HBFI, link: &LinkId) {
+    fn super_train(&mut self, bfis: &HBFI, link: &LinkId, now: Instant, half_life: Duration) {
         //debug!("supertrain {:?}", bfis);
-        let linkids = self.bfis
-            .entry(HBFIOnlyKeys(bfis.clone()))
-            .or_insert(HashMap::new());
-        let value = linkids.entry(link.clone()).or_insert(0);
-        *value += 4;
+        self.add(bfis, link, 4.0, now, half_life);
     }
-    fn get_frequency(&mut self, bfis: &HBFIOnlyKeys, linkid: &LinkId) -> (Option<&i64>, bool) {
+    fn get_frequency(&mut self, bfis: &HBFIOnlyKeys, linkid: &LinkId, now: Instant, half_life: Duration) -> (Option<f64>, bool) {
         match self.bfis.get(bfis) {
-            Some(linkids) => match linkids.get(linkid) {
-                Some(value) => return (Some(value), true),
+            Some(trained) => match trained.linkids.get(linkid) {
+                Some(count) => return (Some(count.decayed(now, half_life)), true),
                 None => return (None, true),
             },
             None => return (None, false),
         }
     }
+    // The least recently trained entry goes first once the map is full.
+    fn evict_stalest(&mut self) {
+        let stalest = self.order.keys().next().cloned();
+        if let Some(key) = stalest.and_then(|tick| self.order.remove(&tick)) {
+            self.bfis.remove(&key);
+        }
+    }
+    // Keeps the links for which f holds, dropping the BFIs left without any.
+    fn retain_links<F: FnMut(&LinkId, &Count) -> bool>(&mut self, mut f: F) {
+        let order = &mut self.order;
+        self.bfis.retain(|_, trained| {
+            trained.linkids.retain(|linkid, count| f(linkid, count));
+            if trained.linkids.is_empty() {
+                order.remove(&trained.tick);
+            }
+            !trained.linkids.is_empty()
+        });
+    }
+    fn forget(&mut self, now: Instant, half_life: Duration) {
+        self.retain_links(|_, count| count.decayed(now, half_life) >= BAYES_FORGET_THRESHOLD);
+    }
 }
 struct Links {
-    count: HashMap<LinkId, i64>,
+    count: HashMap<LinkId, Count>,
 }
 impl Links {
     pub fn new() -> Links {
@@ -54,45 +121,47 @@ impl Links {
             count: HashMap::new(),
         }
     }
-    fn train(&mut self, link: &LinkId) {
-        let value = self.count.entry(link.clone()).or_insert(0);
-        *value += 1;
+    fn train(&mut self, link: &LinkId, now: Instant, half_life: Duration) {
+        let value = self.count.entry(link.clone()).or_insert(Count::new(0.0, now));
+        value.add(1.0, now, half_life);
     }
-    fn super_train(&mut self, link: &LinkId) {
-        let value = self.count.entry(link.clone()).or_insert(0);
-        *value += 4;
+    fn super_train(&mut self, link: &LinkId, now: Instant, half_life: Duration) {
+        let value = self.count.entry(link.clone()).or_insert(Count::new(0.0, now));
+        value.add(4.0, now, half_life);
     }
-    fn get_count(&mut self, link: &LinkId) -> Option<&i64> {
-        return self.count.get(link);
+    fn get_count(&mut self, link: &LinkId, now: Instant, half_life: Duration) -> Option<f64> {
+        return self.count.get(link).map(|count| count.decayed(now, half_life));
     }
-    fn get_linkids(&mut self) -> Keys<LinkId, i64> {
+    fn get_linkids(&mut self) -> Keys<LinkId, Count> {
         return self.count.keys();
     }
-    fn get_total(&mut self) -> i64 {
-        return self.count.values().fold(0, |acc, x| acc + x);
+    fn get_total(&mut self, now: Instant, half_life: Duration) -> f64 {
+        return self.count.values().fold(0.0, |acc, x| acc + x.decayed(now, half_life));
     }
 }
 struct Model {
     links: Links,
     bfis: BFIs,
+    half_life: Duration,
 }
 impl Model {
     pub fn new() -> Model {
         Model {
             links: Links::new(),
             bfis: BFIs::new(),
+            half_life: Duration::from_millis(BAYES_HALF_LIFE),
         }
     }
-    fn add_link(&mut self, linkid: &LinkId) {
-        self.links.train(linkid);
+    fn add_link(&mut self, linkid: &LinkId, now: Instant) {
+        self.links.train(linkid, now, self.half_life);
     }
-    fn train(&mut self, data: &HBFI, linkid: &LinkId) {
-        self.links.train(linkid);
-        self.bfis.train(data, linkid);
+    fn train(&mut self, data: &HBFI, linkid: &LinkId, now: Instant) {
+        self.links.train(linkid, now, self.half_life);
+        self.bfis.train(data, linkid, now, self.half_life);
     }
-    fn super_train(&mut self, data: &HBFI, linkid: &LinkId) {
-        self.links.super_train(linkid);
-        self.bfis.super_train(data, linkid);
+    fn super_train(&mut self, data: &HBFI, linkid: &LinkId, now: Instant) {
+        self.links.super_train(linkid, now, self.half_life);
+        self.bfis.super_train(data, linkid, now, self.half_life);
     }
 }
 #[derive(Debug)]
@@ -103,7 +172,8 @@ pub struct LinkWeight{
 pub struct Bayes {
     model: Model,
     min_prob: f64,
-    min_log_prob: f64
+    min_log_prob: f64,
+    penalties: HashMap<LinkId, Instant>,
 }
 impl Bayes {
     pub fn new() -> Bayes {
@@ -111,33 +181,90 @@ impl Bayes {
             model: Model::new(),
             min_prob: 1e-9,
             min_log_prob: -100.0,
+            penalties: HashMap::new(),
+        }
+    }
+    // How long until a count has lost half its weight.
+    pub fn set_half_life(&mut self, half_life: Duration) {
+        self.model.half_life = half_life;
+    }
+    pub fn half_life(&self) -> Duration {
+        self.model.half_life
+    }
+    // The most HBFIs remembered, the least recently trained are forgotten first.
+    pub fn set_bfis_capacity(&mut self, capacity: usize) {
+        self.model.bfis.capacity = capacity;
+        while self.model.bfis.bfis.len() > capacity {
+            self.model.bfis.evict_stalest();
         }
     }
+    pub fn bfis_len(&self) -> usize {
+        self.model.bfis.bfis.len()
+    }
     pub fn add_link(&mut self, linkid: &LinkId) {
-        self.model.add_link(&linkid);
+        self.add_link_at(linkid, Instant::now());
+    }
+    pub fn add_link_at(&mut self, linkid: &LinkId, now: Instant) {
+        self.model.add_link(&linkid, now);
+    }
+    // Forgets everything learnt about a link, under any of its reply addresses.
+    pub fn remove_link(&mut self, linkid: &LinkId) {
+        let lookup_id = linkid.lookup_id().ok();
+        let same = |other: &LinkId| other.lookup_id().ok() == lookup_id;
+        self.model.links.count.retain(|other, _| !same(other));
+        self.penalties.retain(|other, _| !same(other));
+        self.model.bfis.retain_links(|other, _| !same(other));
+    }
+    pub fn linkids(&mut self) -> Vec<LinkId> {
+        self.model.links.get_linkids().cloned().collect()
+    }
+    // Scales the link's weight down by BAYES_PENALTY_FACTOR until the penalty runs out.
+    pub fn penalise(&mut self, linkid: &LinkId, duration: Duration) {
+        let until = Instant::now() + duration;
+        let penalty = self.penalties.entry(linkid.clone()).or_insert(until);
+        if *penalty < until {
+            *penalty = until;
+        }
+    }
+    pub fn is_penalised(&self, linkid: &LinkId) -> bool {
+        match self.penalties.get(linkid) {
+            Some(until) => *until > Instant::now(),
+            None => false,
+        }
+    }
+    fn penalty(&mut self, linkid: &LinkId) -> f64 {
+        if self.is_penalised(linkid) {
+            return BAYES_PENALTY_FACTOR
+        }
+        self.penalties.remove(linkid);
+        1.0
     }
-    fn prior(&mut self, linkid: &LinkId) -> Option<f64> {
-        let total = *(&self.model.links.get_total()) as f64;
-        let linkid = &self.model.links.get_count(linkid);
+    fn prior(&mut self, linkid: &LinkId, now: Instant) -> Option<f64> {
+        let half_life = self.model.half_life;
+        let total = self.model.links.get_total(now, half_life);
+        let linkid = self.model.links.get_count(linkid, now, half_life);
         if linkid.is_some() && total > 0.0 {
-            return Some(*linkid.unwrap() as f64 / total);
+            return Some(linkid.unwrap() / total);
         } else {
             return None;
         }
     }
-    fn log_prior(&mut self, linkid: &LinkId) -> Option<f64> {
-        let total = *(&self.model.links.get_total()) as f64;
-        let linkid = &self.model.links.get_count(linkid);
+    fn log_prior(&mut self, linkid: &LinkId, now: Instant) -> Option<f64> {
+        let half_life = self.model.half_life;
+        let total = self.model.links.get_total(now, half_life);
+        let linkid = self.model.links.get_count(linkid, now, half_life);
         if linkid.is_some() && total > 0.0 {
-            return Some((*linkid.unwrap() as f64).ln() - total.ln());
+            return Some(linkid.unwrap().ln() - total.ln());
         } else {
             return None;
         }
     }
-    fn calculate_attr_prob(&mut self, bfis: &HBFIOnlyKeys, linkid: &LinkId) -> Option<f64> {
-        match self.model.bfis.get_frequency(bfis, linkid) {
-            (Some(frequency), true) => match self.model.links.get_count(linkid) {
-                Some(count) => return Some((*frequency as f64) / (*count as f64)),
+    fn calculate_attr_prob(&mut self, bfis: &HBFIOnlyKeys, linkid: &LinkId, now: Instant) -> Option<f64> {
+        let half_life = self.model.half_life;
+        match self.model.bfis.get_frequency(bfis, linkid, now, half_life) {
+            (Some(frequency), true) => match self.model.links.get_count(linkid, now, half_life) {
+                Some(count) if count > 0.0 => return Some(frequency / count),
+                Some(_) => return Some(self.min_prob),
                 None => return None,
             },
             (None, true) => return Some(self.min_prob),
@@ -145,10 +272,12 @@ impl Bayes {
             (Some(_), false) => None,
         }
     }
-    fn calculate_attr_log_prob(&mut self, bfis: &HBFIOnlyKeys, linkid: &LinkId) -> Option<f64> {
-        match self.model.bfis.get_frequency(bfis, linkid) {
-            (Some(frequency), true) => match self.model.links.get_count(linkid) {
-                Some(count) => return Some((*frequency as f64).ln() - (*count as f64).ln()),
+    fn calculate_attr_log_prob(&mut self, bfis: &HBFIOnlyKeys, linkid: &LinkId, now: Instant) -> Option<f64> {
+        let half_life = self.model.half_life;
+        match self.model.bfis.get_frequency(bfis, linkid, now, half_life) {
+            (Some(frequency), true) => match self.model.links.get_count(linkid, now, half_life) {
+                Some(count) if count > 0.0 => return Some(frequency.ln() - count.ln()),
+                Some(_) => return Some(self.min_log_prob),
                 None => return None,
             },
             (None, true) => return Some(self.min_log_prob),
@@ -156,10 +285,10 @@ impl Bayes {
             (Some(_), false) => None,
         }
     }
-    fn link_prob(&mut self, linkid: &LinkId, bfismap: &HashSet<HBFIOnlyKeys>) -> Vec<f64> {
+    fn link_prob(&mut self, linkid: &LinkId, bfismap: &HashSet<HBFIOnlyKeys>, now: Instant) -> Vec<f64> {
         let mut probs: Vec<f64> = Vec::new();
         for bfis in bfismap {
-            match self.calculate_attr_prob(bfis, linkid) {
+            match self.calculate_attr_prob(bfis, linkid, now) {
                 Some(p) => {
                     probs.push(p);
                 }
@@ -168,10 +297,10 @@ impl Bayes {
         }
         return probs;
     }
-    fn link_log_prob(&mut self, linkid: &LinkId, bfismap: &HashSet<HBFIOnlyKeys>) -> Vec<f64> {
+    fn link_log_prob(&mut self, linkid: &LinkId, bfismap: &HashSet<HBFIOnlyKeys>, now: Instant) -> Vec<f64> {
         let mut probs: Vec<f64> = Vec::new();
         for bfis in bfismap {
-            match self.calculate_attr_log_prob(bfis, linkid) {
+            match self.calculate_attr_log_prob(bfis, linkid, now) {
                 Some(p) => {
                     probs.push(p);
                 }
@@ -181,21 +310,71 @@ impl Bayes {
         return probs;
     }
     pub fn train(&mut self, data: &HBFI, linkid: &LinkId) {
-        self.model.train(data, linkid);
+        self.train_at(data, linkid, Instant::now());
+    }
+    pub fn train_at(&mut self, data: &HBFI, linkid: &LinkId, now: Instant) {
+        self.model.train(data, linkid, now);
     }
     pub fn super_train(&mut self, data: &HBFI, linkid: &LinkId) {
-        self.model.super_train(data, linkid);
+        self.super_train_at(data, linkid, Instant::now());
+    }
+    pub fn super_train_at(&mut self, data: &HBFI, linkid: &LinkId, now: Instant) {
+        self.model.super_train(data, linkid, now);
+    }
+    // Drops the BFI counts which have decayed to nothing and the penalties which have run out.
+    pub fn expire(&mut self, now: Instant) {
+        let half_life = self.model.half_life;
+        self.model.bfis.forget(now, half_life);
+        self.penalties.retain(|_, until| *until > now);
+    }
+    // The decayed counts, for persisting the model.
+    pub fn link_counts(&self) -> Vec<(LinkId, f64)> {
+        let now = Instant::now();
+        let half_life = self.model.half_life;
+        self.model.links.count
+            .iter()
+            .map(|(linkid, count)| (linkid.clone(), count.decayed(now, half_life)))
+            .collect()
+    }
+    pub fn bfi_counts(&self) -> Vec<(HBFI, Vec<(LinkId, f64)>)> {
+        let now = Instant::now();
+        let half_life = self.model.half_life;
+        self.model.bfis.bfis
+            .iter()
+            .map(|(bfis, trained)| {
+                let counts = trained.linkids
+                    .iter()
+                    .map(|(linkid, count)| (linkid.clone(), count.decayed(now, half_life)))
+                    .collect();
+                (bfis.0.clone(), counts)
+            })
+            .collect()
+    }
+    // Overwrites the counts learnt so far with those of a snapshot.
+    pub fn restore_link(&mut self, linkid: &LinkId, count: f64) {
+        self.model.links.count.insert(linkid.clone(), Count::new(count, Instant::now()));
+    }
+    pub fn restore_bfi(&mut self, data: &HBFI, linkid: &LinkId, count: f64) {
+        let now = Instant::now();
+        let half_life = self.model.half_life;
+        self.model.bfis.add(data, linkid, 0.0, now, half_life);
+        if let Some(trained) = self.model.bfis.bfis.get_mut(&HBFIOnlyKeys(data.clone())) {
+            trained.linkids.insert(linkid.clone(), Count::new(count, now));
+        }
     }
     pub fn classify(&mut self, data: &HBFI) -> Vec<LinkWeight> {
+        self.classify_at(data, Instant::now())
+    }
+    pub fn classify_at(&mut self, data: &HBFI, now: Instant) -> Vec<LinkWeight> {
         let mut bfis_set: HashSet<HBFIOnlyKeys> = HashSet::new();
         bfis_set.insert(HBFIOnlyKeys(data.clone()));
         let mut result: Vec<LinkWeight> = vec![];
         let linkids: HashSet<LinkId> =
             HashSet::from_iter(self.model.links.get_linkids().into_iter().cloned());
         for linkid in linkids {
-            let p = self.link_prob(&linkid, &bfis_set);
+            let p = self.link_prob(&linkid, &bfis_set, now);
             let p_iter = p.into_iter().fold(1.0, |acc, x| acc * x);
-            let weight = p_iter * self.prior(&linkid).unwrap();
+            let weight = p_iter * self.prior(&linkid, now).unwrap_or(0.0) * self.penalty(&linkid);
             let linkid = linkid.clone();
             let lw = LinkWeight { linkid, weight };
             result.push(lw);
@@ -207,16 +386,17 @@ impl Bayes {
     /// classify a `BFIS` returning a map of links and log-probabilities
     /// as keys and values, respectively. Using `log_classify` may prevent underflows.
     pub fn log_classify(&mut self, data: &HBFIOnlyKeys) -> Vec<LinkWeight> {
+        let now = Instant::now();
         let mut bfis_set: HashSet<HBFIOnlyKeys> = HashSet::new();
         bfis_set.insert(data.clone());
         let mut result: Vec<LinkWeight> = vec![];
         let linkids: HashSet<LinkId> =
             HashSet::from_iter(self.model.links.get_linkids().into_iter().cloned());
         for linkid in linkids {
-            let p = self.link_log_prob(&linkid, &bfis_set);
+            let p = self.link_log_prob(&linkid, &bfis_set, now);
             let max = p.iter().cloned().fold(-1./0. /* inf */, f64::max);
             let p_iter = p.into_iter().fold(0.0, |acc, x| acc + (x - max).exp());
-            let weight = max + p_iter.ln() + self.log_prior(&linkid).unwrap();
+            let weight = max + p_iter.ln() + self.log_prior(&linkid, now).unwrap_or(self.min_log_prob) + self.penalty(&linkid).ln();
             let linkid = linkid.clone();
             result.push(LinkWeight { linkid, weight });
         }
@@ -225,6 +405,94 @@ impl Bayes {
         result
     }
 }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use copernica_packets::{PrivateIdentityInterface, PublicIdentityInterface, ReplyTo};
+
+    fn link() -> LinkId {
+        LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::Absent, ReplyTo::Mpsc)
+    }
+    fn hbfi(arg: &str) -> HBFI {
+        let response_sid = PrivateIdentityInterface::new_key();
+        HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "app", "m0d", "fun", arg).unwrap()
+    }
+    #[test]
+    fn test_recovers_after_producer_moves() {
+        let hbfi = hbfi("arg");
+        let (old, new) = (link(), link());
+        let mut bayes = Bayes::new();
+        let now = Instant::now();
+        bayes.set_half_life(Duration::from_millis(20));
+        bayes.add_link_at(&old, now);
+        bayes.add_link_at(&new, now);
+        for _ in 0..100 {
+            bayes.super_train_at(&hbfi, &old, now);
+        }
+        assert_eq!(bayes.classify_at(&hbfi, now)[0].linkid, old);
+        // the producer moved behind the new link, 10 half-lives ago the old link served it
+        let later = now + Duration::from_millis(200);
+        bayes.super_train_at(&hbfi, &new, later);
+        assert_eq!(bayes.classify_at(&hbfi, later)[0].linkid, new);
+    }
+    #[test]
+    fn test_never_forgets_without_decay() {
+        let hbfi = hbfi("arg");
+        let (old, new) = (link(), link());
+        let mut bayes = Bayes::new();
+        let now = Instant::now();
+        bayes.set_half_life(Duration::from_millis(0));
+        bayes.add_link_at(&old, now);
+        bayes.add_link_at(&new, now);
+        for _ in 0..100 {
+            bayes.super_train_at(&hbfi, &old, now);
+        }
+        let later = now + Duration::from_millis(200);
+        bayes.super_train_at(&hbfi, &new, later);
+        assert_eq!(bayes.classify_at(&hbfi, later)[0].linkid, old);
+    }
+    #[test]
+    fn test_bounded_bfis() {
+        let link = link();
+        let mut bayes = Bayes::new();
+        bayes.set_bfis_capacity(3);
+        let first = hbfi("first");
+        bayes.super_train(&first, &link);
+        for arg in &["a", "b", "c"] {
+            bayes.super_train(&hbfi(arg), &link);
+        }
+        assert_eq!(bayes.bfis_len(), 3);
+        assert!(bayes.bfi_counts().iter().all(|(hbfi, _)| *hbfi != first));
+    }
+    #[test]
+    fn test_retraining_keeps_bfis() {
+        let link = link();
+        let mut bayes = Bayes::new();
+        bayes.set_bfis_capacity(2);
+        let (first, second, third) = (hbfi("first"), hbfi("second"), hbfi("third"));
+        bayes.super_train(&first, &link);
+        bayes.super_train(&second, &link);
+        bayes.train(&first, &link);
+        bayes.super_train(&third, &link);
+        let kept: Vec<HBFI> = bayes.bfi_counts().into_iter().map(|(hbfi, _)| hbfi).collect();
+        assert!(kept.contains(&first));
+        assert!(!kept.contains(&second));
+        bayes.remove_link(&link);
+        assert_eq!(bayes.bfis_len(), 0);
+        assert!(bayes.model.bfis.order.is_empty());
+    }
+    #[test]
+    fn test_expire_forgets_decayed_bfis() {
+        let link = link();
+        let mut bayes = Bayes::new();
+        bayes.set_half_life(Duration::from_millis(10));
+        bayes.super_train(&hbfi("arg"), &link);
+        bayes.expire(Instant::now());
+        assert_eq!(bayes.bfis_len(), 1);
+        bayes.expire(Instant::now() + Duration::from_secs(1));
+        assert_eq!(bayes.bfis_len(), 0);
+    }
+}
 /*
 #[cfg(test)]
 mod test_bfis {
@@ -1,20 +1,41 @@
 use {
     crate::{
-        bloom_filter::Blooms,
-        router::Router,
-        Bayes,
+        router::{Router, RouterContext, ShardState},
+        pending_table::{PendingRequest},
+        replay_cache::{ReplayCache},
+        nacks::{Nacks},
+        defcon::{DefconPolicy, Mitigator},
+        response_store::{ResponseStore, CachePolicy, CacheStats},
+        snapshot::{Snapshot},
+        explain::{Decision, Decisions},
+        announcements::{Announcements},
+        digests::{Digests},
+        credits::{Credits, CreditPolicy, Grants},
+        prefetch::{Prefetcher, PrefetchPolicy},
+        qos::{QosPolicy},
+        scheduler::{Scheduler, QueueStats, Sent},
+        shard::{Shard},
+        filter::{FilterPolicy},
+        role::{Role},
+        stats::{BrokerStats, Counters},
+        metrics,
+        bayes::{Bayes, LinkWeight},
     },
-    copernica_packets::{LinkId, InterLinkPacket, NarrowWaistPacket },
-    copernica_common::{ constants::{RESPONSE_STORE_SIZE, LABEL_SIZE, BOUNDED_BUFFER_SIZE}, Operations},
+    copernica_packets::{LinkId, InterLinkPacket, LinkPacket, NarrowWaistPacket, CacheDigest, HBFI, DecodeFailures },
+    copernica_common::{ constants::{LABEL_SIZE, BOUNDED_BUFFER_SIZE, PENDING_REQUEST_LIFETIME, PENDING_TABLE_SWEEP_INTERVAL, ANNOUNCE_LIFETIME, CACHE_DIGEST_ENTRIES}, Operations},
     anyhow::{anyhow, Result},
-    crossbeam_channel::{bounded, Receiver, Sender},
-    uluru::LRUCache,
+    crossbeam_channel::{bounded, unbounded, Receiver, Sender, Select, RecvTimeoutError, TrySendError},
     arrayvec::ArrayString,
     std::{
-        collections::HashMap,
+        collections::{HashMap, HashSet},
+        net::SocketAddr,
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}},
+        thread::JoinHandle,
+        time::{Duration, Instant},
     },
     log::{
-        error, trace,
+        error, trace, warn,
         //debug
     },
 };
@@ -42,109 +63,604 @@ use {
     +-----------+               +-----------+               |           Broker           |   +-----------+   +-----------+
                                                             +----------------------------+
 */
-pub type ResponseStore = LRUCache<NarrowWaistPacket, RESPONSE_STORE_SIZE>;
 pub struct Broker {
     label:  ArrayString<LABEL_SIZE>,
     ops:    Operations,
-    rs:     ResponseStore,
+    shards: Vec<Shard>,
+    decisions: Arc<Mutex<Decisions>>,
+    counters: Arc<Mutex<Counters>>,
+    credits: Arc<Mutex<Credits>>,
     l2b_tx: Sender<InterLinkPacket>,                         // give to link
     l2b_rx: Receiver<InterLinkPacket>,                       // keep in broker
-    b2l:    HashMap<u32, Sender<InterLinkPacket>>,           // keep in broker
-    r2b_tx: Sender<InterLinkPacket>,                // give to router
+    b2l:    Arc<Mutex<HashMap<u32, Sender<InterLinkPacket>>>>, // keep in broker
+    r2b_tx: Sender<InterLinkPacket>,                // give to router, unbounded so the router never blocks
     r2b_rx: Receiver<InterLinkPacket>,  // keep in broker
-    blooms: HashMap<LinkId, Blooms>,
+    scheduler: Arc<Mutex<Scheduler>>,
+    qos_policy: QosPolicy,
+    filter: Arc<Mutex<FilterPolicy>>,
+    filter_path: Option<PathBuf>,
+    role: Role,
+    upstream: Arc<Mutex<HashSet<LinkId>>>,
+    links:  Arc<Mutex<HashSet<LinkId>>>,
+    pending_lifetime: Duration,
+    announce_lifetime: Duration,
+    cache_digests: Option<Duration>,
+    flow_control: Option<CreditPolicy>,
+    prefetch_policy: Option<PrefetchPolicy>,
+    defcon_policy: DefconPolicy,
+    persist: Option<(PathBuf, Duration)>,
+    running: Arc<AtomicBool>,
+    router: Option<JoinHandle<Result<()>>>,
 }
 impl Broker {
-    pub fn new((label, ops): (ArrayString<LABEL_SIZE>, Operations)) -> Self {
+    pub fn new((label, ops): (ArrayString<LABEL_SIZE>, Operations), role: Role) -> Self {
         let (l2b_tx, l2b_rx) = bounded::<InterLinkPacket>(BOUNDED_BUFFER_SIZE);
-        let (r2b_tx, r2b_rx) = bounded::<InterLinkPacket>(BOUNDED_BUFFER_SIZE);
-        let b2l = HashMap::new();
-        let blooms = HashMap::new();
-        let rs = ResponseStore::default();
+        let (r2b_tx, r2b_rx) = unbounded::<InterLinkPacket>();
+        let b2l = Arc::new(Mutex::new(HashMap::new()));
+        let links = Arc::new(Mutex::new(HashSet::new()));
+        let pending_lifetime = Duration::from_millis(PENDING_REQUEST_LIFETIME);
+        let shards = vec![Shard::new(role.response_store(), Bayes::new())];
+        let decisions = Arc::new(Mutex::new(Decisions::new()));
         ops.register_router(label.clone());
         Self {
             label,
-            rs,
+            shards,
+            decisions,
+            counters: Arc::new(Mutex::new(Counters::default())),
+            credits: Arc::new(Mutex::new(Credits::new())),
             l2b_tx,
             l2b_rx,
             r2b_tx,
             r2b_rx,
+            scheduler: Arc::new(Mutex::new(Scheduler::new())),
+            qos_policy: QosPolicy::default(),
+            filter: Arc::new(Mutex::new(FilterPolicy::default())),
+            filter_path: None,
+            role,
+            upstream: Arc::new(Mutex::new(HashSet::new())),
             b2l,
-            blooms,
+            links,
+            pending_lifetime,
+            announce_lifetime: Duration::from_millis(ANNOUNCE_LIFETIME),
+            cache_digests: None,
+            flow_control: None,
+            prefetch_policy: None,
+            defcon_policy: DefconPolicy::default(),
+            persist: None,
+            running: Arc::new(AtomicBool::new(true)),
+            router: None,
             ops,
         }
     }
+    pub fn role(&self) -> Role {
+        self.role
+    }
+    // Marks a link as leading toward the core of the network, the routers
+    // see it from their next packet on.
+    pub fn upstream(&mut self, link_id: LinkId) {
+        self.upstream.lock().unwrap().insert(link_id);
+    }
+    // Replaces the content store, the capacity is in bytes of encoded Responses.
+    pub fn cache(&mut self, policy: CachePolicy, capacity: usize) {
+        let capacity = capacity / self.shards.len();
+        for shard in &self.shards {
+            *shard.rs.lock().unwrap() = ResponseStore::new(policy, capacity);
+        }
+    }
+    pub fn cache_capacity(&self, capacity: usize) {
+        let capacity = capacity / self.shards.len();
+        for shard in &self.shards {
+            shard.rs.lock().unwrap().set_capacity(capacity);
+        }
+    }
+    pub fn cache_stats(&self) -> CacheStats {
+        Shard::cache_stats(&self.shards)
+    }
+    // Routes on `threads` threads, each owning the content store and Bayes
+    // counts for its share of names and an even share of the cache capacity.
+    // Call it before restoring a snapshot, anything already cached is dropped.
+    // The shards are replaced, so it's refused once stats are subscribed to
+    // or served, or the broker runs, as those hold on to the old ones.
+    pub fn router_threads(&mut self, threads: usize) -> Result<()> {
+        if Arc::strong_count(&self.shards[0].rs) > 1 {
+            return Err(anyhow!("Set the router threads before the broker's stats are used or it runs"))
+        }
+        let threads = threads.max(1);
+        let stats = self.cache_stats();
+        let half_life = self.shards[0].bayes.lock().unwrap().half_life();
+        self.shards = (0..threads)
+            .map(|_| {
+                let mut bayes = Bayes::new();
+                bayes.set_half_life(half_life);
+                for link_id in self.links.lock().unwrap().iter() {
+                    bayes.add_link(link_id);
+                }
+                Shard::new(ResponseStore::new(stats.policy, stats.capacity / threads), bayes)
+            })
+            .collect();
+        Ok(())
+    }
+    // How long a Request waits in the pending table for its Response.
+    pub fn pending_lifetime(&mut self, lifetime: Duration) {
+        self.pending_lifetime = lifetime;
+    }
+    // How long a route learnt from a producer's Announce lasts without being announced again.
+    pub fn announce_lifetime(&mut self, lifetime: Duration) {
+        self.announce_lifetime = lifetime;
+    }
+    // Sends each neighbour a digest of the cached HBFIs every interval, off by default.
+    pub fn cache_digests(&mut self, interval: Duration) {
+        self.cache_digests = Some(interval);
+    }
+    // Grants each peer credits for the Requests and Responses it may send,
+    // fewer the further the broker falls behind, off by default. Credits a
+    // peer grants are kept to whether or not this is on.
+    pub fn flow_control(&mut self, policy: CreditPolicy) {
+        self.flow_control = Some(policy);
+    }
+    // Rate limits on Requests, the classes packets are queued in and what
+    // goes when a link's queue is full.
+    pub fn qos(&mut self, policy: QosPolicy) {
+        self.scheduler.lock().unwrap().set_policy(policy.queue_depth, policy.drop);
+        self.qos_policy = policy;
+    }
+    // What the broker will carry, it takes effect at once on a running broker.
+    pub fn filter(&mut self, policy: FilterPolicy) {
+        *self.filter.lock().unwrap() = policy;
+    }
+    // Loads the filter policy from `path` and remembers it for reload_filter.
+    pub fn filter_file(&mut self, path: PathBuf) -> Result<()> {
+        self.filter(FilterPolicy::load(&path)?);
+        self.filter_path = Some(path);
+        Ok(())
+    }
+    // Reads the filter file again, a bad file leaves the current policy in place.
+    pub fn reload_filter(&mut self) -> Result<()> {
+        let path = self.filter_path.clone().ok_or_else(|| anyhow!("No filter file to reload"))?;
+        self.filter(FilterPolicy::load(&path)?);
+        Ok(())
+    }
+    // Counters and gauges of everything the broker does, as of now.
+    pub fn stats(&self) -> BrokerStats {
+        BrokerStats::collect(&self.shards, &self.scheduler, &self.counters, &self.credits)
+    }
+    // Sends the broker's stats every `interval` until the broker stops or
+    // the receiver is dropped. A subscriber still busy with the last stats
+    // misses the next rather than holding up the broker.
+    pub fn subscribe(&self, interval: Duration) -> Receiver<BrokerStats> {
+        let (tx, rx) = bounded::<BrokerStats>(1);
+        let shards = self.shards.clone();
+        let scheduler = self.scheduler.clone();
+        let counters = self.counters.clone();
+        let credits = self.credits.clone();
+        let running = self.running.clone();
+        std::thread::spawn(move || {
+            while running.load(Ordering::SeqCst) {
+                std::thread::sleep(interval);
+                match tx.try_send(BrokerStats::collect(&shards, &scheduler, &counters, &credits)) {
+                    Ok(_) | Err(TrySendError::Full(_)) => {},
+                    Err(TrySendError::Disconnected(_)) => break,
+                }
+            }
+        });
+        rx
+    }
+    // Serves the stats in the Prometheus text format at http://addr/metrics
+    // while the broker runs, returning the address it's listening on.
+    pub fn serve_metrics(&self, addr: SocketAddr) -> Result<SocketAddr> {
+        let label = self.label.clone();
+        let shards = self.shards.clone();
+        let scheduler = self.scheduler.clone();
+        let counters = self.counters.clone();
+        let credits = self.credits.clone();
+        metrics::serve(addr, self.running.clone(), move || {
+            let stats = BrokerStats::collect(&shards, &scheduler, &counters, &credits);
+            metrics::render(&label, &stats, Instant::now())
+        })
+    }
+    // The depth of each link's queues and how many packets were sent and dropped.
+    pub fn queue_stats(&self) -> Vec<(LinkId, QueueStats)> {
+        self.scheduler.lock().unwrap().stats()
+    }
+    // Requests frames ahead of sequential readers of popular objects, off by default.
+    pub fn prefetch(&mut self, policy: PrefetchPolicy) {
+        self.prefetch_policy = Some(policy);
+    }
+    // The mitigations applied to Requests at each Defcon level.
+    pub fn defcon(&mut self, policy: DefconPolicy) {
+        self.defcon_policy = policy;
+    }
+    // How long until what a link taught the routing model has lost half its weight.
+    pub fn half_life(&mut self, half_life: Duration) {
+        for shard in &self.shards {
+            shard.bayes.lock().unwrap().set_half_life(half_life);
+        }
+    }
+    // How many of the most recent routing decisions are kept for explaining.
+    pub fn decision_log_size(&mut self, size: usize) {
+        self.decisions.lock().unwrap().set_capacity(size);
+    }
+    pub fn decisions(&self) -> Vec<Decision> {
+        self.decisions.lock().unwrap().iter().cloned().collect()
+    }
+    // The recent routing decisions taken on Requests for the HBFI, oldest first.
+    pub fn explain(&self, hbfi: &HBFI) -> Vec<Decision> {
+        self.decisions.lock().unwrap().explain(hbfi)
+    }
+    // The Requests still waiting on their Responses.
+    pub fn pending(&self) -> Vec<(HBFI, PendingRequest)> {
+        self.shards
+            .iter()
+            .flat_map(|shard| {
+                let pit = shard.pit.lock().unwrap();
+                pit.iter().map(|(hbfi, pending)| (hbfi.clone(), pending.clone())).collect::<Vec<_>>()
+            })
+            .collect()
+    }
+    // The HBFIs of the cached Responses.
+    pub fn cached(&self) -> Vec<HBFI> {
+        self.shards
+            .iter()
+            .flat_map(|shard| shard.rs.lock().unwrap().iter().map(|nw| nw.hbfi()).collect::<Vec<_>>())
+            .collect()
+    }
+    // How the routing model weighs each link for the HBFI, heaviest first.
+    pub fn weights(&self, hbfi: &HBFI) -> Vec<LinkWeight> {
+        self.shards[Shard::of(hbfi, self.shards.len())].bayes.lock().unwrap().classify(hbfi)
+    }
+    // Empties the content store, its policy and capacity stay as they were.
+    pub fn flush_cache(&self) {
+        for shard in &self.shards {
+            shard.rs.lock().unwrap().clear();
+        }
+    }
+    // Snapshots the routing state to `path` every `interval` and on shutdown.
+    pub fn persist(&mut self, path: PathBuf, interval: Duration) {
+        self.persist = Some((path, interval));
+    }
+    // Warm starts from a snapshot, call it after peering with the links.
+    pub fn restore(&mut self, path: &Path) -> Result<()> {
+        let snapshot = Snapshot::load(path)?;
+        Shard::restore(&self.shards, &snapshot, &self.links.lock().unwrap());
+        Ok(())
+    }
+    pub fn snapshot(&self, path: &Path) -> Result<()> {
+        Shard::snapshot(&self.shards).save(path)
+    }
+    // Stops the router, writing a last snapshot if persistence is on.
+    pub fn shutdown(&mut self) -> Result<()> {
+        self.running.store(false, Ordering::SeqCst);
+        match self.router.take() {
+            Some(router) => router.join().map_err(|_| anyhow!("Router thread panicked"))?,
+            None => Ok(()),
+        }
+    }
+    // Links may be peered before or while the broker runs.
     pub fn peer_with_link(
         &mut self,
         link_id: LinkId,
     ) -> Result<(Sender<InterLinkPacket>, Receiver<InterLinkPacket>)> {
-        match self.blooms.get(&link_id) {
-            Some(_) => Err(anyhow!("Channel already initialized")),
-            None => {
-                let (b2l_tx, b2l_rx) = bounded::<InterLinkPacket>(BOUNDED_BUFFER_SIZE);
-                self.b2l.insert(link_id.lookup_id()?, b2l_tx.clone());
-                self.blooms.insert(link_id, Blooms::new());
-                Ok((self.l2b_tx.clone(), b2l_rx))
-            }
+        let (l2b_tx, b2l_rx, _) = self.peer(link_id)?;
+        Ok((l2b_tx, b2l_rx))
+    }
+    // Like peer_with_link, also handing back what the link should count the
+    // packets it couldn't decode into, see Link::count_decode_failures.
+    pub fn peer(
+        &mut self,
+        link_id: LinkId,
+    ) -> Result<(Sender<InterLinkPacket>, Receiver<InterLinkPacket>, DecodeFailures)> {
+        let mut b2l = self.b2l.lock().unwrap();
+        if b2l.contains_key(&link_id.lookup_id()?) {
+            return Err(anyhow!("Channel already initialized"))
+        }
+        let (b2l_tx, b2l_rx) = bounded::<InterLinkPacket>(BOUNDED_BUFFER_SIZE);
+        b2l.insert(link_id.lookup_id()?, b2l_tx);
+        for shard in &self.shards {
+            shard.bayes.lock().unwrap().add_link(&link_id);
+        }
+        let decode_failures = self.counters.lock().unwrap().peer(&link_id);
+        self.links.lock().unwrap().insert(link_id);
+        Ok((self.l2b_tx.clone(), b2l_rx, decode_failures))
+    }
+    // Drops a link from a running broker along with all it taught the
+    // routing model. The link's receiver is disconnected so it can stop.
+    pub fn unpeer(&mut self, link_id: &LinkId) -> Result<()> {
+        let lookup_id = link_id.lookup_id()?;
+        if self.b2l.lock().unwrap().remove(&lookup_id).is_none() {
+            return Err(anyhow!("No link {} to unpeer", lookup_id))
+        }
+        let same = |other: &LinkId| other.lookup_id().ok() == Some(lookup_id);
+        self.links.lock().unwrap().retain(|other| !same(other));
+        self.upstream.lock().unwrap().retain(|other| !same(other));
+        for shard in &self.shards {
+            shard.bayes.lock().unwrap().remove_link(link_id);
         }
+        self.scheduler.lock().unwrap().remove_link(link_id);
+        self.credits.lock().unwrap().remove_link(link_id);
+        self.counters.lock().unwrap().unpeer(link_id);
+        Ok(())
     }
-    #[allow(unreachable_code)]
     pub fn run(&mut self) -> Result<()> {
         let l2b_rx = self.l2b_rx.clone();
-        let mut blooms = self.blooms.clone();
+        let links = self.links.clone();
+        let mut mitigator = Mitigator::new(self.defcon_policy.clone());
+        mitigator.set_rate_limits(self.qos_policy.link_rate_limit, self.qos_policy.requester_rate_limit);
+        let mitigator = Arc::new(Mutex::new(mitigator));
+        // shared by the router threads so neither a link's replays nor the
+        // prefetch budget are split between them
+        let replays = Arc::new(Mutex::new(ReplayCache::new()));
+        let prefetcher = self.prefetch_policy.map(|policy| Arc::new(Mutex::new(Prefetcher::new(policy, Instant::now()))));
+        // a credit grant wakes the scheduler to send what it held back
+        let (wake_tx, wake_rx) = bounded::<()>(1);
+        let mut announcements = Announcements::new();
+        announcements.set_lifetime(self.announce_lifetime);
+        let announcements = Arc::new(Mutex::new(announcements));
+        let digests = Arc::new(Mutex::new(Digests::new()));
+        let cache_digests = self.cache_digests.filter(|_| self.role.caches());
+        let mut grants = self.flow_control.clone().map(Grants::new);
+        let sweep = Duration::from_millis(PENDING_TABLE_SWEEP_INTERVAL);
         let choke = LinkId::choke();
-        let mut b2l = self.b2l.clone();
+        let b2l = self.b2l.clone();
+        let peers = self.b2l.clone();
         let r2b_tx = self.r2b_tx.clone();
         let r2b_rx = self.r2b_rx.clone();
-        let mut bayes = Bayes::new();
-        for (link_id, _) in &blooms {
-            bayes.add_link(&link_id);
-        }
-        let rs = self.rs.clone();
+        let shards = self.shards.clone();
+        let counters = self.counters.clone();
+        let persist = self.persist.clone();
+        let running = self.running.clone();
         let ops = self.ops.clone();
         let label = self.label.clone();
-        std::thread::spawn(move || {
-            loop {
-                match l2b_rx.recv() {
+        let mut workers = vec![];
+        for (index, shard) in self.shards.iter().enumerate() {
+            let (d2w_tx, d2w_rx) = bounded::<InterLinkPacket>(BOUNDED_BUFFER_SIZE);
+            workers.push((d2w_tx, self.route(index, shard.clone(), d2w_rx, replays.clone(), mitigator.clone(), prefetcher.clone(), announcements.clone(), digests.clone(), wake_tx.clone())));
+        }
+        // hands each packet to the router thread owning its name
+        self.router = Some(std::thread::spawn(move || {
+            let mut last_sweep = Instant::now();
+            let mut last_snapshot = Instant::now();
+            let mut last_digest = Instant::now();
+            let mut last_grant = Instant::now();
+            while running.load(Ordering::SeqCst) {
+                match l2b_rx.recv_timeout(sweep) {
                     Ok(ilp) => {
                         trace!("\t\t|  |  |  broker-to-router");
                         ops.message_from(label.clone());
-                        if !blooms.contains_key(&ilp.link_id()) {
-                            trace!("ADDING {:?} to BLOOMS", ilp);
-                            blooms.insert(ilp.link_id(), Blooms::new());
-                            bayes.add_link(&ilp.link_id());
+                        let peered = ilp.link_id()
+                            .lookup_id()
+                            .map(|lookup_id| peers.lock().unwrap().contains_key(&lookup_id))
+                            .unwrap_or(false);
+                        if peered {
+                            counters.lock().unwrap().received(&ilp.link_id(), Instant::now());
+                            links.lock().unwrap().insert(ilp.link_id());
+                            if let Some(grants) = &mut grants {
+                                if grants.received(&ilp.link_id(), &ilp.narrow_waist()) {
+                                    let (requests, responses) = grants.grant(&ilp.link_id(), l2b_rx.len(), BOUNDED_BUFFER_SIZE);
+                                    if let Err(error) = Broker::send_credit(&ilp.link_id(), requests, responses, &r2b_tx) {
+                                        error!("{}: credit grant failed: {}", label, error);
+                                    }
+                                }
+                            }
+                            let (d2w_tx, _) = &workers[Shard::of(&ilp.narrow_waist().hbfi(), workers.len())];
+                            if let Err(error) = d2w_tx.send(ilp) {
+                                error!("{}: router thread gone: {}", label, error);
+                            }
+                        } else {
+                            // whatever an unpeered link still had in flight
+                            trace!("{}: packet from an unpeered link dropped", label);
                         }
-                        Router::handle_packet(&label, &ops, &ilp, r2b_tx.clone(), &mut rs.clone(), &mut blooms, &mut bayes, &choke)?;
                     }
+                    Err(RecvTimeoutError::Timeout) => {},
                     Err(error) => error!("{}", error),
                 }
+                if last_sweep.elapsed() >= sweep {
+                    replays.lock().unwrap().expire(Instant::now());
+                    mitigator.lock().unwrap().expire(Instant::now());
+                    if let Some(prefetcher) = &prefetcher {
+                        prefetcher.lock().unwrap().expire(Instant::now());
+                    }
+                    announcements.lock().unwrap().expire(Instant::now());
+                    digests.lock().unwrap().expire(Instant::now());
+                    last_sweep = Instant::now();
+                }
+                if let Some(interval) = cache_digests {
+                    if last_digest.elapsed() >= interval {
+                        let mut digest = CacheDigest::new();
+                        let entries = (CACHE_DIGEST_ENTRIES / shards.len()).max(1);
+                        for shard in &shards {
+                            digest.union(&Digests::digest(&shard.rs.lock().unwrap(), entries));
+                        }
+                        let links = links.lock().unwrap().clone();
+                        for link_id in links.iter().filter(|link_id| **link_id != choke) {
+                            if let Err(error) = Broker::send_digest(link_id, &digest, &r2b_tx) {
+                                error!("{}: cache digest failed: {}", label, error);
+                            }
+                        }
+                        last_digest = Instant::now();
+                    }
+                }
+                if let Some(grants) = &mut grants {
+                    // a peer that used no credit, or whose last grant was lost, is granted anew
+                    if last_grant.elapsed() >= grants.interval() {
+                        let links = links.lock().unwrap().clone();
+                        for link_id in links.iter().filter(|link_id| **link_id != choke) {
+                            let (requests, responses) = grants.grant(link_id, l2b_rx.len(), BOUNDED_BUFFER_SIZE);
+                            if let Err(error) = Broker::send_credit(link_id, requests, responses, &r2b_tx) {
+                                error!("{}: credit grant failed: {}", label, error);
+                            }
+                        }
+                        last_grant = Instant::now();
+                    }
+                }
+                if let Some((path, interval)) = &persist {
+                    if last_snapshot.elapsed() >= *interval {
+                        if let Err(error) = Shard::snapshot(&shards).save(path) {
+                            error!("{}: snapshot failed: {}", label, error);
+                        }
+                        last_snapshot = Instant::now();
+                    }
+                }
+            }
+            for (_, worker) in workers {
+                worker.join().map_err(|_| anyhow!("Router thread panicked"))??;
+            }
+            if let Some((path, _)) = &persist {
+                Shard::snapshot(&shards).save(path)?;
             }
             Ok::<(), anyhow::Error>(())
-        });
+        }));
+        let running = self.running.clone();
         let ops = self.ops.clone();
         let label = self.label.clone();
+        let scheduler = self.scheduler.clone();
+        let credits = self.credits.clone();
+        let qos_policy = self.qos_policy.clone();
         std::thread::spawn(move || {
-            loop {
-                if let Ok(ilp) = r2b_rx.recv() {
-                    match &ilp.link_id().lookup_id() {
-                        Ok(id) => {
-                            match b2l.get_mut(id) {
-                                Some(b2l_tx) => {
-                                    trace!("\t\t|  |  |  router-to-broker");
-                                    ops.message_from(label.clone());
-                                    match b2l_tx.send(ilp) {
-                                        Ok(_) => {},
-                                        Err(e) => error!("broker {:?}", e),
-                                    }
-                                },
-                                None => { continue }
-                            }
+            // the links that were full, keyed by their lookup ids
+            let mut full: HashMap<u32, Sender<InterLinkPacket>> = HashMap::new();
+            while running.load(Ordering::SeqCst) {
+                // waits for a packet, a credit grant or room on a full link
+                {
+                    let mut select = Select::new();
+                    select.recv(&r2b_rx);
+                    select.recv(&wake_rx);
+                    for b2l_tx in full.values() {
+                        select.send(b2l_tx);
+                    }
+                    let _ = select.ready_timeout(sweep);
+                }
+                wake_rx.try_iter().for_each(drop);
+                full.clear();
+                let mut scheduler = scheduler.lock().unwrap();
+                for ilp in r2b_rx.try_iter() {
+                    let class = qos_policy.classify(&ilp.narrow_waist());
+                    if !scheduler.enqueue(ilp, class) {
+                        warn!("{}: {} queue full, packet dropped", label, class);
+                    }
+                }
+                let b2l = b2l.lock().unwrap();
+                let mut credits = credits.lock().unwrap();
+                scheduler.drain(|ilp| {
+                    let (id, b2l_tx) = match ilp.link_id().lookup_id() {
+                        Ok(id) => match b2l.get(&id) {
+                            Some(b2l_tx) => (id, b2l_tx),
+                            None => return Sent::Gone,
                         },
-                        Err(_e) => { continue },
+                        Err(_e) => return Sent::Gone,
                     };
-                }
+                    // held back until the peer grants more of its kind
+                    let nw = ilp.narrow_waist();
+                    if !credits.allows(&ilp.link_id(), &nw) {
+                        return Sent::Held
+                    }
+                    match b2l_tx.try_send(ilp.clone()) {
+                        Ok(_) => {
+                            credits.spend(&ilp.link_id(), &nw);
+                            trace!("\t\t|  |  |  router-to-broker");
+                            ops.message_from(label.clone());
+                            Sent::Sent
+                        },
+                        Err(TrySendError::Full(_)) => {
+                            full.insert(id, b2l_tx.clone());
+                            Sent::Full
+                        },
+                        Err(e) => {
+                            error!("broker {:?}", e);
+                            Sent::Gone
+                        },
+                    }
+                });
             }
         });
         Ok(())
     }
+    // A router thread, routing the packets for its shard's names.
+    fn route(
+        &self,
+        index: usize,
+        shard: Shard,
+        d2w_rx: Receiver<InterLinkPacket>,
+        replays: Arc<Mutex<ReplayCache>>,
+        mitigator: Arc<Mutex<Mitigator>>,
+        prefetcher: Option<Arc<Mutex<Prefetcher>>>,
+        announcements: Arc<Mutex<Announcements>>,
+        digests: Arc<Mutex<Digests>>,
+        wake_tx: Sender<()>,
+    ) -> JoinHandle<Result<()>> {
+        let mut links = self.links.lock().unwrap().clone();
+        shard.pit.lock().unwrap().set_lifetime(self.pending_lifetime);
+        let mut nacks = Nacks::new();
+        let sweep = Duration::from_millis(PENDING_TABLE_SWEEP_INTERVAL);
+        let context = RouterContext {
+            label: self.label.clone(),
+            ops: self.ops.clone(),
+            r2b_tx: self.r2b_tx.clone(),
+            replays,
+            mitigator,
+            filter: self.filter.clone(),
+            role: self.role,
+            upstream: self.upstream.clone(),
+            announcements,
+            digests,
+            credits: self.credits.clone(),
+            prefetcher,
+            decisions: self.decisions.clone(),
+            counters: self.counters.clone(),
+            choke: LinkId::choke(),
+        };
+        let running = self.running.clone();
+        std::thread::spawn(move || {
+            let mut last_sweep = Instant::now();
+            while running.load(Ordering::SeqCst) {
+                match d2w_rx.recv_timeout(sweep) {
+                    Ok(ilp) => {
+                        let mut rs = shard.rs.lock().unwrap();
+                        let mut bayes = shard.bayes.lock().unwrap();
+                        let mut pit = shard.pit.lock().unwrap();
+                        if links.insert(ilp.link_id()) {
+                            trace!("ADDING {:?} to LINKS", ilp);
+                            bayes.add_link(&ilp.link_id());
+                        }
+                        let state = ShardState { rs: &mut rs, pit: &mut pit, bayes: &mut bayes, nacks: &mut nacks };
+                        if let Err(error) = Router::handle_packet(&context, state, &ilp) {
+                            error!("{}: {:?} dropped: {}", context.label, ilp, error);
+                            context.counters.lock().unwrap().router_error();
+                        }
+                        if let NarrowWaistPacket::Credit { .. } = ilp.narrow_waist() {
+                            let _ = wake_tx.try_send(());
+                        }
+                        context.counters.lock().unwrap().pending(index, pit.len());
+                    }
+                    Err(RecvTimeoutError::Timeout) => {},
+                    Err(error) => error!("{}", error),
+                }
+                if last_sweep.elapsed() >= sweep {
+                    for hbfi in shard.rs.lock().unwrap().expire(Instant::now()) {
+                        trace!("\t\t|  |  |  STALE RESPONSE EVICTED {:?}", hbfi);
+                    }
+                    shard.bayes.lock().unwrap().expire(Instant::now());
+                    let mut pit = shard.pit.lock().unwrap();
+                    for (hbfi, _) in pit.expire(Instant::now()) {
+                        trace!("\t\t|  |  |  PENDING REQUEST EXPIRED {:?}", hbfi);
+                    }
+                    context.counters.lock().unwrap().pending(index, pit.len());
+                    nacks.expire(Instant::now());
+                    last_sweep = Instant::now();
+                }
+            }
+            Ok::<(), anyhow::Error>(())
+        })
+    }
+    fn send_digest(link_id: &LinkId, digest: &CacheDigest, r2b_tx: &Sender<InterLinkPacket>) -> Result<()> {
+        let nw = NarrowWaistPacket::digest(link_id.link_sid()?, digest.clone())?;
+        let lp = LinkPacket::new(link_id.reply_to()?, nw);
+        r2b_tx.send(InterLinkPacket::new(link_id.clone(), lp))?;
+        Ok(())
+    }
+    fn send_credit(link_id: &LinkId, requests: u64, responses: u64, r2b_tx: &Sender<InterLinkPacket>) -> Result<()> {
+        let nw = NarrowWaistPacket::credit(link_id.link_sid()?, requests, responses)?;
+        let lp = LinkPacket::new(link_id.reply_to()?, nw);
+        r2b_tx.send(InterLinkPacket::new(link_id.clone(), lp))?;
+        Ok(())
+    }
 }
@@ -0,0 +1,254 @@
+use {
+    copernica_packets::{LinkId, NarrowWaistPacket, PublicIdentity, PublicIdentityInterface},
+    copernica_common::constants::{FLOW_CONTROL_REQUEST_CREDITS, FLOW_CONTROL_RESPONSE_CREDITS, FLOW_CONTROL_GRANT_INTERVAL},
+    std::{
+        collections::HashMap,
+        time::Duration,
+    },
+};
+// How many Requests and Responses a peer may send on a link before it's
+// granted more, and how often grants go out even if nothing was used.
+#[derive(Clone, Debug)]
+pub struct CreditPolicy {
+    pub requests: u64,
+    pub responses: u64,
+    pub interval: Duration,
+}
+impl Default for CreditPolicy {
+    fn default() -> Self {
+        Self {
+            requests: FLOW_CONTROL_REQUEST_CREDITS,
+            responses: FLOW_CONTROL_RESPONSE_CREDITS,
+            interval: Duration::from_millis(FLOW_CONTROL_GRANT_INTERVAL),
+        }
+    }
+}
+// Only Requests and Responses take credit, control packets are free.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CreditKind {
+    Request,
+    Response,
+}
+impl CreditKind {
+    pub fn of(nw: &NarrowWaistPacket) -> Option<Self> {
+        match nw {
+            NarrowWaistPacket::Request { .. } => Some(CreditKind::Request),
+            NarrowWaistPacket::Response { .. } => Some(CreditKind::Response),
+            _ => None,
+        }
+    }
+}
+// What a peer lets this broker send it, None until the peer's first grant.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CreditStats {
+    pub requests: Option<u64>,
+    pub responses: Option<u64>,
+    // times the link ran out of credit with packets waiting
+    pub starved: u64,
+}
+// The credits each peer has granted this broker. A peer that never grants
+// any, a protocol or a broker without flow control, isn't held back. Grants
+// are only taken from the link's neighbour, the remote identity it was set up
+// with or else the first to sign a grant on it.
+#[derive(Clone, Debug, Default)]
+pub struct Credits {
+    links: HashMap<u32, (CreditStats, bool)>,
+    signers: HashMap<u32, PublicIdentity>,
+}
+impl Credits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    // Returns false if the grant wasn't signed by the link's neighbour.
+    pub fn grant(&mut self, link_id: &LinkId, signer: &PublicIdentity, requests: u64, responses: u64) -> bool {
+        let lookup_id = match link_id.lookup_id() {
+            Ok(lookup_id) => lookup_id,
+            Err(_) => return false,
+        };
+        let expected = match link_id.remote_link_pid() {
+            Ok(PublicIdentityInterface::Present { public_identity }) => public_identity,
+            _ => self.signers.entry(lookup_id).or_insert_with(|| signer.clone()).clone(),
+        };
+        if expected != *signer {
+            return false
+        }
+        let (credits, starving) = self.links.entry(lookup_id).or_default();
+        credits.requests = Some(requests);
+        credits.responses = Some(responses);
+        *starving = false;
+        true
+    }
+    // Whether the packet may go to the link now.
+    pub fn allows(&mut self, link_id: &LinkId, nw: &NarrowWaistPacket) -> bool {
+        let (kind, lookup_id) = match (CreditKind::of(nw), link_id.lookup_id()) {
+            (Some(kind), Ok(lookup_id)) => (kind, lookup_id),
+            _ => return true,
+        };
+        let (credits, starving) = match self.links.get_mut(&lookup_id) {
+            Some(link) => link,
+            None => return true,
+        };
+        let left = match kind {
+            CreditKind::Request => credits.requests,
+            CreditKind::Response => credits.responses,
+        };
+        if left != Some(0) {
+            return true
+        }
+        // counted once until the next grant
+        if !*starving {
+            credits.starved += 1;
+            *starving = true;
+        }
+        false
+    }
+    // Takes the credit for a packet sent to the link.
+    pub fn spend(&mut self, link_id: &LinkId, nw: &NarrowWaistPacket) {
+        let (kind, lookup_id) = match (CreditKind::of(nw), link_id.lookup_id()) {
+            (Some(kind), Ok(lookup_id)) => (kind, lookup_id),
+            _ => return,
+        };
+        if let Some((credits, _)) = self.links.get_mut(&lookup_id) {
+            let left = match kind {
+                CreditKind::Request => &mut credits.requests,
+                CreditKind::Response => &mut credits.responses,
+            };
+            if let Some(left) = left {
+                *left = left.saturating_sub(1);
+            }
+        }
+    }
+    pub fn stats(&self, link_id: &LinkId) -> CreditStats {
+        link_id.lookup_id()
+            .ok()
+            .and_then(|lookup_id| self.links.get(&lookup_id))
+            .map(|(credits, _)| credits.clone())
+            .unwrap_or_default()
+    }
+    pub fn remove_link(&mut self, link_id: &LinkId) {
+        if let Ok(lookup_id) = link_id.lookup_id() {
+            self.links.remove(&lookup_id);
+            self.signers.remove(&lookup_id);
+        }
+    }
+}
+// The credits this broker has granted each peer and not yet seen used.
+#[derive(Clone, Debug)]
+pub struct Grants {
+    policy: CreditPolicy,
+    links: HashMap<u32, (u64, u64)>,
+}
+impl Grants {
+    pub fn new(policy: CreditPolicy) -> Self {
+        Self {
+            policy,
+            links: HashMap::new(),
+        }
+    }
+    pub fn interval(&self) -> Duration {
+        self.policy.interval
+    }
+    // Counts a packet arriving on the link, true once the peer has used half
+    // of a window or was never granted any, so it's time to grant again.
+    pub fn received(&mut self, link_id: &LinkId, nw: &NarrowWaistPacket) -> bool {
+        let (kind, lookup_id) = match (CreditKind::of(nw), link_id.lookup_id()) {
+            (Some(kind), Ok(lookup_id)) => (kind, lookup_id),
+            _ => return false,
+        };
+        match self.links.get_mut(&lookup_id) {
+            Some((requests, responses)) => match kind {
+                CreditKind::Request => {
+                    *requests = requests.saturating_sub(1);
+                    *requests <= self.policy.requests / 2
+                },
+                CreditKind::Response => {
+                    *responses = responses.saturating_sub(1);
+                    *responses <= self.policy.responses / 2
+                },
+            },
+            None => true,
+        }
+    }
+    // The Requests and Responses to grant the link, a window of each shrunk
+    // by how full `backlog` is of the `capacity` packets waiting to be routed.
+    pub fn grant(&mut self, link_id: &LinkId, backlog: usize, capacity: usize) -> (u64, u64) {
+        let free = capacity.saturating_sub(backlog) as u64;
+        let capacity = capacity.max(1) as u64;
+        let requests = self.policy.requests * free / capacity;
+        let responses = self.policy.responses * free / capacity;
+        if let Ok(lookup_id) = link_id.lookup_id() {
+            self.links.insert(lookup_id, (requests, responses));
+        }
+        (requests, responses)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use copernica_packets::{HBFI, PrivateIdentityInterface, PublicIdentityInterface, ReplyTo, RequestOptions};
+
+    #[test]
+    fn test_credits_pace_and_count_starvation() {
+        let link0 = LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+        let response_sid = PrivateIdentityInterface::new_key();
+        let hbfi = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "app", "m0d", "fun", "arg").unwrap();
+        let request = NarrowWaistPacket::request(hbfi.clone(), RequestOptions::default()).unwrap();
+        let response = NarrowWaistPacket::response(response_sid.clone(), hbfi, vec![0; 8]).unwrap();
+        let announce = NarrowWaistPacket::announce(response_sid, "app").unwrap();
+        let peer = PrivateIdentityInterface::new_key().public_id();
+        let mut credits = Credits::new();
+        // unlimited until the peer grants
+        assert!(credits.allows(&link0, &request));
+        assert!(credits.grant(&link0, &peer, 2, 0));
+        for _ in 0..2 {
+            assert!(credits.allows(&link0, &request));
+            credits.spend(&link0, &request);
+        }
+        assert!(!credits.allows(&link0, &request));
+        assert!(!credits.allows(&link0, &request));
+        assert!(!credits.allows(&link0, &response));
+        assert!(credits.allows(&link0, &announce));
+        assert_eq!(credits.stats(&link0), CreditStats { requests: Some(0), responses: Some(0), starved: 1 });
+        assert!(credits.grant(&link0, &peer, 1, 1));
+        assert!(credits.allows(&link0, &request));
+        credits.remove_link(&link0);
+        assert_eq!(credits.stats(&link0), CreditStats::default());
+    }
+    #[test]
+    fn test_grants_signed_by_another_key_are_dropped() {
+        let peer = PrivateIdentityInterface::new_key().public_id();
+        let stranger = PrivateIdentityInterface::new_key().public_id();
+        let response_sid = PrivateIdentityInterface::new_key();
+        let hbfi = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "app", "m0d", "fun", "arg").unwrap();
+        let request = NarrowWaistPacket::request(hbfi, RequestOptions::default()).unwrap();
+        let mut credits = Credits::new();
+        // pinned to the first signer on a link with no remote identity
+        let link0 = LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+        assert!(credits.grant(&link0, &peer, 4, 4));
+        assert!(!credits.grant(&link0, &stranger, 0, 0));
+        assert!(credits.allows(&link0, &request));
+        assert_eq!(credits.stats(&link0).requests, Some(4));
+        // pinned to the remote identity the link was set up with
+        let link1 = LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::new(peer.clone()), ReplyTo::Mpsc);
+        assert!(!credits.grant(&link1, &stranger, 0, 0));
+        assert!(credits.allows(&link1, &request));
+        assert!(credits.grant(&link1, &peer, 0, 0));
+        assert!(!credits.allows(&link1, &request));
+    }
+    #[test]
+    fn test_grants_follow_use_and_backlog() {
+        let link0 = LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+        let response_sid = PrivateIdentityInterface::new_key();
+        let hbfi = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "app", "m0d", "fun", "arg").unwrap();
+        let request = NarrowWaistPacket::request(hbfi, RequestOptions::default()).unwrap();
+        let mut grants = Grants::new(CreditPolicy { requests: 8, responses: 16, interval: Duration::from_millis(100) });
+        assert!(grants.received(&link0, &request));
+        assert_eq!(grants.grant(&link0, 0, 10), (8, 16));
+        for _ in 0..3 {
+            assert!(!grants.received(&link0, &request));
+        }
+        assert!(grants.received(&link0, &request));
+        assert_eq!(grants.grant(&link0, 5, 10), (4, 8));
+        assert_eq!(grants.grant(&link0, 10, 10), (0, 0));
+    }
+}
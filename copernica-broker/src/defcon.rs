@@ -0,0 +1,308 @@
+use {
+    crate::{
+        token_bucket::{TokenBucket},
+        Bayes,
+    },
+    copernica_packets::{LinkId, NarrowWaistPacket, PublicIdentityInterface},
+    copernica_common::{Operations, constants::LABEL_SIZE},
+    arrayvec::ArrayString,
+    log::{warn},
+    std::{
+        collections::{HashMap, hash_map::Entry},
+        hash::Hash,
+        fmt,
+        time::{Duration, Instant},
+    },
+};
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Defcon {
+    One,
+    Two,
+    Three,
+    Four,
+}
+impl Defcon {
+    // The litmus is the choke link's Bayes weight, or a link's replay rate, as a percentage.
+    pub fn from_litmus(litmus: u64) -> Self {
+        match litmus {
+            0..=35 => Defcon::Four,
+            36..=59 => Defcon::Three,
+            60..=89 => Defcon::Two,
+            90..=u64::MAX => Defcon::One,
+        }
+    }
+    pub fn level(&self) -> u8 {
+        match self {
+            Defcon::One => 1,
+            Defcon::Two => 2,
+            Defcon::Three => 3,
+            Defcon::Four => 4,
+        }
+    }
+}
+impl fmt::Display for Defcon {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Defcon {}", self.level())
+    }
+}
+// Requests per second with room for bursts of up to `burst` Requests.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimit {
+    pub rate: u32,
+    pub burst: u32,
+}
+impl RateLimit {
+    // The lower rate and burst of the two limits.
+    pub fn tighter(self, other: Option<RateLimit>) -> RateLimit {
+        match other {
+            Some(other) => RateLimit { rate: self.rate.min(other.rate), burst: self.burst.min(other.burst) },
+            None => self,
+        }
+    }
+}
+#[derive(Clone, Debug, Default)]
+pub struct Mitigation {
+    pub link_rate_limit: Option<RateLimit>,
+    pub requester_rate_limit: Option<RateLimit>,
+    pub require_signed: bool,
+    pub penalty: Option<Duration>,
+    pub alert: bool,
+    pub drop: bool,
+}
+#[derive(Clone, Debug)]
+pub struct DefconPolicy {
+    pub four: Mitigation,
+    pub three: Mitigation,
+    pub two: Mitigation,
+    pub one: Mitigation,
+}
+impl DefconPolicy {
+    pub fn mitigation(&self, defcon: Defcon) -> &Mitigation {
+        match defcon {
+            Defcon::Four => &self.four,
+            Defcon::Three => &self.three,
+            Defcon::Two => &self.two,
+            Defcon::One => &self.one,
+        }
+    }
+}
+// Defcon 4 is reached in ordinary operation, before the first Response has
+// trained the real links, so it only logs.
+impl Default for DefconPolicy {
+    fn default() -> Self {
+        Self {
+            four: Mitigation::default(),
+            three: Mitigation {
+                link_rate_limit: Some(RateLimit { rate: 200, burst: 400 }),
+                requester_rate_limit: Some(RateLimit { rate: 100, burst: 200 }),
+                ..Mitigation::default()
+            },
+            two: Mitigation {
+                link_rate_limit: Some(RateLimit { rate: 50, burst: 100 }),
+                requester_rate_limit: Some(RateLimit { rate: 20, burst: 40 }),
+                require_signed: true,
+                penalty: Some(Duration::from_secs(5)),
+                alert: true,
+                drop: false,
+            },
+            one: Mitigation {
+                penalty: Some(Duration::from_secs(30)),
+                alert: true,
+                drop: true,
+                ..Mitigation::default()
+            },
+        }
+    }
+}
+// The one rate limiter of the broker. Every Request takes a token from its
+// link's bucket and, if it names one, its requester's. The buckets run at the
+// QosPolicy's rate limits until a Defcon level tightens them to its
+// mitigation's, and stay tightened until they've refilled.
+#[derive(Clone)]
+pub struct Mitigator {
+    policy: DefconPolicy,
+    link_rate_limit: Option<RateLimit>,
+    requester_rate_limit: Option<RateLimit>,
+    links: HashMap<LinkId, TokenBucket>,
+    requesters: HashMap<PublicIdentityInterface, TokenBucket>,
+}
+impl Mitigator {
+    pub fn new(policy: DefconPolicy) -> Self {
+        Self {
+            policy,
+            link_rate_limit: None,
+            requester_rate_limit: None,
+            links: HashMap::new(),
+            requesters: HashMap::new(),
+        }
+    }
+    pub fn policy(&self) -> &DefconPolicy {
+        &self.policy
+    }
+    pub fn set_policy(&mut self, policy: DefconPolicy) {
+        self.policy = policy;
+    }
+    // The rate limits of the QosPolicy, applied at every Defcon level.
+    pub fn set_rate_limits(&mut self, link_rate_limit: Option<RateLimit>, requester_rate_limit: Option<RateLimit>) {
+        self.link_rate_limit = link_rate_limit;
+        self.requester_rate_limit = requester_rate_limit;
+    }
+    // Returns false if the Request is over its link's or requester's rate.
+    // Cleartext Requests don't name a requester so only the link limit
+    // applies to them, one flooding link can't hold back the rest.
+    pub fn admit(&mut self, nw: &NarrowWaistPacket, link_id: &LinkId, now: Instant) -> bool {
+        if !Mitigator::take(&mut self.links, link_id.clone(), self.link_rate_limit, now) {
+            return false
+        }
+        match nw.hbfi().request_pid {
+            PublicIdentityInterface::Absent => true,
+            requester => Mitigator::take(&mut self.requesters, requester, self.requester_rate_limit, now),
+        }
+    }
+    // Returns false if the Request must not be forwarded. It has been
+    // admitted already, so tightening its buckets holds back the Requests
+    // after it.
+    pub fn mitigate(&mut self,
+        defcon: Defcon,
+        nw: &NarrowWaistPacket,
+        link_id: &LinkId,
+        bayes: &mut Bayes,
+        label: &ArrayString<LABEL_SIZE>,
+        ops: &Operations,
+        now: Instant,
+    ) -> bool {
+        let mitigation = self.policy.mitigation(defcon).clone();
+        if mitigation.alert {
+            ops.alert(label.clone(), defcon.level());
+        }
+        if let Some(penalty) = mitigation.penalty {
+            bayes.penalise(link_id, penalty);
+        }
+        if mitigation.drop {
+            warn!("{}: Deep Sixed packet: {:?}", defcon, nw);
+            return false
+        }
+        if mitigation.require_signed && !nw.is_signed() {
+            warn!("{}: Dropped unsigned packet: {:?}", defcon, nw);
+            return false
+        }
+        if let Some(limit) = mitigation.link_rate_limit {
+            if !Mitigator::tighten(&mut self.links, link_id.clone(), limit.tighter(self.link_rate_limit), now) {
+                warn!("{}: Rate limited link: {:?}", defcon, link_id);
+                return false
+            }
+        }
+        let requester = nw.hbfi().request_pid;
+        if let Some(limit) = mitigation.requester_rate_limit.filter(|_| requester != PublicIdentityInterface::Absent) {
+            if !Mitigator::tighten(&mut self.requesters, requester, limit.tighter(self.requester_rate_limit), now) {
+                warn!("{}: Rate limited requester: {:?}", defcon, nw);
+                return false
+            }
+        }
+        if defcon != Defcon::Four {
+            warn!("{}: Mitigated packet: {:?}", defcon, nw);
+        }
+        true
+    }
+    // Takes a token from the key's bucket, there's only a bucket to take from
+    // once a limit applies.
+    fn take<K: Eq + Hash>(buckets: &mut HashMap<K, TokenBucket>, key: K, limit: Option<RateLimit>, now: Instant) -> bool {
+        match (buckets.entry(key), limit) {
+            (Entry::Occupied(entry), _) => entry.into_mut().try_take(now),
+            (Entry::Vacant(entry), Some(RateLimit { rate, burst })) => entry.insert(TokenBucket::new(rate, burst, now)).try_take(now),
+            (Entry::Vacant(_), None) => true,
+        }
+    }
+    // The Request already took its token from a bucket that was there, a new
+    // bucket starts out with it taken.
+    fn tighten<K: Eq + Hash>(buckets: &mut HashMap<K, TokenBucket>, key: K, RateLimit { rate, burst }: RateLimit, now: Instant) -> bool {
+        match buckets.entry(key) {
+            Entry::Occupied(entry) => {
+                entry.into_mut().set_rate(rate, burst);
+                true
+            },
+            Entry::Vacant(entry) => entry.insert(TokenBucket::new(rate, burst, now)).try_take(now),
+        }
+    }
+    // Forgets the buckets that have refilled, they'd start out full anyway.
+    pub fn expire(&mut self, now: Instant) {
+        self.links.retain(|_, bucket| bucket.tokens(now) < bucket.burst());
+        self.requesters.retain(|_, bucket| bucket.tokens(now) < bucket.burst());
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use copernica_packets::{HBFI, PrivateIdentityInterface, ReplyTo, RequestOptions};
+
+    #[test]
+    fn test_requests_are_limited_per_link_and_requester() {
+        let response_sid = PrivateIdentityInterface::new_key();
+        let request_sid = PrivateIdentityInterface::new_key();
+        let cleartext = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "app", "m0d", "fun", "arg").unwrap();
+        let cyphertext = HBFI::new(request_sid.public_id(), response_sid.public_id(), "app", "m0d", "fun", "arg").unwrap();
+        let link0 = LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+        let link1 = LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+        let mut limiter = Mitigator::new(DefconPolicy::default());
+        limiter.set_rate_limits(Some(RateLimit { rate: 1, burst: 2 }), Some(RateLimit { rate: 1, burst: 1 }));
+        let now = Instant::now();
+        let request = NarrowWaistPacket::request(cleartext, RequestOptions::default()).unwrap();
+        assert!(limiter.admit(&request, &link0, now));
+        assert!(limiter.admit(&request, &link0, now));
+        assert!(!limiter.admit(&request, &link0, now));
+        // the requester's limit follows it onto another link
+        let request = NarrowWaistPacket::request(cyphertext, RequestOptions::default()).unwrap();
+        assert!(limiter.admit(&request, &link1, now));
+        assert!(!limiter.admit(&request, &link1, now));
+        assert!(limiter.admit(&request, &link1, now + Duration::from_secs(1)));
+        limiter.expire(now + Duration::from_secs(10));
+        assert!(limiter.links.is_empty());
+        assert!(limiter.requesters.is_empty());
+    }
+    #[test]
+    fn test_defcon_tightens_the_same_buckets() {
+        let response_sid = PrivateIdentityInterface::new_key();
+        let hbfi = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "app", "m0d", "fun", "arg").unwrap();
+        let link_id = LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+        let mut policy = DefconPolicy::default();
+        policy.three.link_rate_limit = Some(RateLimit { rate: 1, burst: 2 });
+        policy.three.requester_rate_limit = None;
+        let mut limiter = Mitigator::new(policy);
+        limiter.set_rate_limits(Some(RateLimit { rate: 100, burst: 100 }), None);
+        let mut bayes = Bayes::new();
+        let (label, ops) = Operations::turned_off().label("mitigator");
+        let now = Instant::now();
+        let request = NarrowWaistPacket::request(hbfi, RequestOptions::default()).unwrap();
+        assert!(limiter.admit(&request, &link_id, now));
+        assert!(limiter.mitigate(Defcon::Three, &request, &link_id, &mut bayes, &label, &ops, now));
+        // one token per Request, from the bucket Defcon 3 cut down to a burst of 2
+        assert!(limiter.admit(&request, &link_id, now));
+        assert!(limiter.admit(&request, &link_id, now));
+        assert!(!limiter.admit(&request, &link_id, now));
+        assert_eq!(limiter.links.len(), 1);
+    }
+    #[test]
+    fn test_requester_limits_leave_cleartext_to_the_link_limit() {
+        let response_sid = PrivateIdentityInterface::new_key();
+        let hbfi = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "app", "m0d", "fun", "arg").unwrap();
+        let link0 = LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+        let link1 = LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+        let mut policy = DefconPolicy::default();
+        policy.three.link_rate_limit = None;
+        policy.three.requester_rate_limit = Some(RateLimit { rate: 1, burst: 1 });
+        let mut limiter = Mitigator::new(policy);
+        limiter.set_rate_limits(Some(RateLimit { rate: 1, burst: 2 }), Some(RateLimit { rate: 1, burst: 1 }));
+        let mut bayes = Bayes::new();
+        let (label, ops) = Operations::turned_off().label("mitigator");
+        let now = Instant::now();
+        let request = NarrowWaistPacket::request(hbfi, RequestOptions::default()).unwrap();
+        // link0 floods and runs out of its own tokens only
+        for _ in 0..2 {
+            assert!(limiter.admit(&request, &link0, now));
+            assert!(limiter.mitigate(Defcon::Three, &request, &link0, &mut bayes, &label, &ops, now));
+        }
+        assert!(!limiter.admit(&request, &link0, now));
+        assert!(limiter.admit(&request, &link1, now));
+        assert!(limiter.requesters.is_empty());
+    }
+}
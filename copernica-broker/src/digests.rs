@@ -0,0 +1,123 @@
+use {
+    crate::response_store::{ResponseStore},
+    copernica_packets::{CacheDigest, HBFI, LinkId, PublicIdentity, PublicIdentityInterface},
+    copernica_common::constants::CACHE_DIGEST_LIFETIME,
+    std::{
+        collections::HashMap,
+        time::{Duration, Instant},
+    },
+};
+// The last cache digest each neighbouring broker sent, so Requests can be
+// sent toward a neighbour that probably holds the Response already. Only the
+// link's remote identity may sign its digests, or on a cleartext link
+// whoever signed the first one.
+#[derive(Clone)]
+pub struct Digests {
+    lifetime: Duration,
+    digests: HashMap<LinkId, (CacheDigest, Instant)>,
+    signers: HashMap<LinkId, PublicIdentity>,
+}
+impl Digests {
+    pub fn new() -> Self {
+        Self {
+            lifetime: Duration::from_millis(CACHE_DIGEST_LIFETIME),
+            digests: HashMap::new(),
+            signers: HashMap::new(),
+        }
+    }
+    pub fn set_lifetime(&mut self, lifetime: Duration) {
+        self.lifetime = lifetime;
+    }
+    pub fn len(&self) -> usize {
+        self.digests.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.digests.is_empty()
+    }
+    // Returns false if the digest wasn't signed by the link's neighbour.
+    pub fn learn(&mut self, link_id: &LinkId, signer: &PublicIdentity, digest: CacheDigest, now: Instant) -> bool {
+        let expected = match link_id.remote_link_pid() {
+            Ok(PublicIdentityInterface::Present { public_identity }) => public_identity,
+            _ => self.signers.entry(link_id.clone()).or_insert_with(|| signer.clone()).clone(),
+        };
+        if expected != *signer {
+            return false
+        }
+        self.digests.insert(link_id.clone(), (digest, now + self.lifetime));
+        true
+    }
+    // The links whose neighbour claims to have the HBFI cached.
+    pub fn holders(&self, hbfi: &HBFI, now: Instant) -> Vec<LinkId> {
+        self.digests
+            .iter()
+            .filter(|(_, (digest, expiry))| *expiry > now && digest.contains(hbfi))
+            .map(|(link_id, _)| link_id.clone())
+            .collect()
+    }
+    pub fn expire(&mut self, now: Instant) {
+        self.digests.retain(|_, (_, expiry)| *expiry > now);
+    }
+    // Only the most recently used entries are digested so the Bloom filter
+    // doesn't fill up, whatever the size of the store.
+    pub fn digest(rs: &ResponseStore, entries: usize) -> CacheDigest {
+        let mut digest = CacheDigest::new();
+        for nw in rs.most_recent(entries) {
+            digest.insert(&nw.hbfi());
+        }
+        digest
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use copernica_packets::{NarrowWaistPacket, PrivateIdentityInterface, ReplyTo};
+    use copernica_common::constants::CACHE_DIGEST_ENTRIES;
+
+    #[test]
+    fn test_holders_of_cached_responses() {
+        let response_sid = PrivateIdentityInterface::new_key();
+        let hbfi = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "app", "m0d", "fun", "arg").unwrap();
+        let mut rs = ResponseStore::default();
+        for frm in 0..8 {
+            rs.insert(NarrowWaistPacket::response(response_sid.clone(), hbfi.clone().offset(frm), vec![0; 8]).unwrap());
+        }
+        let link0 = LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+        let signer = PrivateIdentityInterface::new_key().public_id();
+        let now = Instant::now();
+        let mut digests = Digests::new();
+        assert!(digests.learn(&link0, &signer, Digests::digest(&rs, CACHE_DIGEST_ENTRIES), now));
+        for frm in 0..8 {
+            assert_eq!(digests.holders(&hbfi.clone().offset(frm), now), vec![link0.clone()]);
+        }
+        assert!(digests.holders(&hbfi.clone().offset(8), now).is_empty());
+        digests.expire(now + Duration::from_millis(CACHE_DIGEST_LIFETIME));
+        assert!(digests.is_empty());
+    }
+    #[test]
+    fn test_only_the_neighbour_signs_its_digests() {
+        let remote_sid = PrivateIdentityInterface::new_key();
+        let stranger = PrivateIdentityInterface::new_key().public_id();
+        let cleartext = LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+        let cyphertext = LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::new(remote_sid.public_id()), ReplyTo::Mpsc);
+        let now = Instant::now();
+        let mut digests = Digests::new();
+        assert!(!digests.learn(&cyphertext, &stranger, CacheDigest::new(), now));
+        assert!(digests.learn(&cyphertext, &remote_sid.public_id(), CacheDigest::new(), now));
+        assert!(digests.learn(&cleartext, &remote_sid.public_id(), CacheDigest::new(), now));
+        assert!(!digests.learn(&cleartext, &stranger, CacheDigest::new(), now));
+    }
+    #[test]
+    fn test_digest_is_capped_to_the_most_recent() {
+        let response_sid = PrivateIdentityInterface::new_key();
+        let hbfi = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "app", "m0d", "fun", "arg").unwrap();
+        let mut rs = ResponseStore::default();
+        for frm in 0..4 {
+            rs.insert(NarrowWaistPacket::response(response_sid.clone(), hbfi.clone().offset(frm), vec![0; 8]).unwrap());
+        }
+        let digest = Digests::digest(&rs, 2);
+        assert!(!digest.contains(&hbfi.clone().offset(0)));
+        assert!(!digest.contains(&hbfi.clone().offset(1)));
+        assert!(digest.contains(&hbfi.clone().offset(2)));
+        assert!(digest.contains(&hbfi.clone().offset(3)));
+    }
+}
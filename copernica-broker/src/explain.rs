@@ -0,0 +1,150 @@
+use {
+    crate::defcon::{Defcon},
+    copernica_packets::{HBFI, LinkId},
+    copernica_common::constants::DECISION_LOG_SIZE,
+    std::{
+        collections::VecDeque,
+        fmt,
+        time::Instant,
+    },
+};
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Skipped {
+    Choke,
+    // the link the Request arrived on, or another link to the same peer
+    SameLink,
+    // already waiting on a Response from that link
+    Pending,
+    // the filter policy keeps cleartext off the link
+    Filtered,
+    // the broker's role keeps Requests from upstream off the link
+    Role,
+}
+#[derive(Clone, Debug, PartialEq)]
+pub struct Candidate {
+    pub linkid: LinkId,
+    pub weight: f64,
+    pub skipped: Option<Skipped>,
+}
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    Forwarded(Vec<LinkId>),
+    Unroutable,
+    FoundInCache,
+    FoundNack,
+    DroppedDuplicate,
+    DroppedReplay,
+    DroppedHopLimit,
+    DroppedExpired,
+    RateLimited,
+    Filtered,
+    Mitigated(Defcon),
+}
+// Why a Request went where it went: the weights Bayes gave each link, the
+// links passed over and what the router did in the end.
+#[derive(Clone, Debug)]
+pub struct Decision {
+    pub at: Instant,
+    pub hbfi: HBFI,
+    pub from: LinkId,
+    pub candidates: Vec<Candidate>,
+    pub action: Action,
+}
+impl Decision {
+    pub fn new(hbfi: HBFI, from: LinkId, now: Instant) -> Self {
+        Self { at: now, hbfi, from, candidates: vec![], action: Action::Unroutable }
+    }
+}
+fn link(f: &mut fmt::Formatter, linkid: &LinkId) -> fmt::Result {
+    match linkid.lookup_id() {
+        Ok(lookup_id) => write!(f, "link {}", lookup_id),
+        Err(_) => write!(f, "choke"),
+    }
+}
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.action {
+            Action::Forwarded(links) => {
+                write!(f, "forwarded to")?;
+                for linkid in links {
+                    write!(f, " ")?;
+                    link(f, linkid)?;
+                }
+            },
+            Action::Unroutable => write!(f, "unroutable")?,
+            Action::FoundInCache => write!(f, "found in cache")?,
+            Action::FoundNack => write!(f, "found a nack")?,
+            Action::DroppedDuplicate => write!(f, "dropped duplicate")?,
+            Action::DroppedReplay => write!(f, "dropped replay")?,
+            Action::DroppedHopLimit => write!(f, "dropped at hop limit")?,
+            Action::DroppedExpired => write!(f, "dropped once its lifetime ran out")?,
+            Action::RateLimited => write!(f, "dropped over rate limit")?,
+            Action::Filtered => write!(f, "dropped by filter")?,
+            Action::Mitigated(defcon) => write!(f, "dropped at {}", defcon)?,
+        }
+        write!(f, " {} from ", self.hbfi)?;
+        link(f, &self.from)?;
+        for candidate in &self.candidates {
+            write!(f, ", ")?;
+            link(f, &candidate.linkid)?;
+            write!(f, " {:.3}", candidate.weight)?;
+            match candidate.skipped {
+                Some(Skipped::Choke) => write!(f, " skipped choke")?,
+                Some(Skipped::SameLink) => write!(f, " skipped same link")?,
+                Some(Skipped::Pending) => write!(f, " skipped pending")?,
+                Some(Skipped::Filtered) => write!(f, " skipped filtered")?,
+                Some(Skipped::Role) => write!(f, " skipped by role")?,
+                None => {},
+            }
+        }
+        Ok(())
+    }
+}
+// The most recent routing decisions, oldest first.
+#[derive(Clone)]
+pub struct Decisions {
+    capacity: usize,
+    entries: VecDeque<Decision>,
+}
+impl Decisions {
+    pub fn new() -> Self {
+        Self {
+            capacity: DECISION_LOG_SIZE,
+            entries: VecDeque::new(),
+        }
+    }
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.entries.len() > capacity {
+            self.entries.pop_front();
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    pub fn record(&mut self, decision: Decision) {
+        if self.capacity == 0 {
+            return
+        }
+        while self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(decision);
+    }
+    pub fn iter(&self) -> impl Iterator<Item = &Decision> {
+        self.entries.iter()
+    }
+    pub fn explain(&self, hbfi: &HBFI) -> Vec<Decision> {
+        self.entries
+            .iter()
+            .filter(|decision| decision.hbfi == *hbfi)
+            .cloned()
+            .collect()
+    }
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
@@ -0,0 +1,188 @@
+use {
+    copernica_packets::{bloom_filter_index as bfi, BFI, HBFI, HBFIExcludeFrame, LinkId, PublicIdentity, PublicIdentityInterface},
+    anyhow::{anyhow, Result},
+    std::{
+        collections::HashSet,
+        fs,
+        path::Path,
+        str::FromStr,
+    },
+};
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FilterAction {
+    Allow,
+    Deny,
+}
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Matcher {
+    ResponsePid(PublicIdentity),
+    RequestPid(PublicIdentity),
+    App(BFI),
+    Module(BFI),
+    // every frame of one name
+    Name(HBFIExcludeFrame),
+}
+impl Matcher {
+    pub fn app(app: &str) -> Result<Self> {
+        Ok(Matcher::App(bfi(app)?))
+    }
+    pub fn module(m0d: &str) -> Result<Self> {
+        Ok(Matcher::Module(bfi(m0d)?))
+    }
+    pub fn matches(&self, hbfi: &HBFI) -> bool {
+        match self {
+            Matcher::ResponsePid(pid) => hbfi.response_pid == *pid,
+            Matcher::RequestPid(pid) => match &hbfi.request_pid {
+                PublicIdentityInterface::Present { public_identity } => public_identity == pid,
+                PublicIdentityInterface::Absent => false,
+            },
+            Matcher::App(app) => hbfi.app == *app,
+            Matcher::Module(m0d) => hbfi.m0d == *m0d,
+            Matcher::Name(name) => HBFIExcludeFrame(hbfi.clone()) == *name,
+        }
+    }
+    fn parse(kind: &str, value: &str) -> Result<Self> {
+        match kind {
+            "response" => Ok(Matcher::ResponsePid(pid(value)?)),
+            "request" => Ok(Matcher::RequestPid(pid(value)?)),
+            "app" => Matcher::app(value),
+            "module" => Matcher::module(value),
+            "name" => Ok(Matcher::Name(HBFIExcludeFrame(value.parse()?))),
+            _ => Err(anyhow!("unknown match {}, expected response, request, app, module or name", kind)),
+        }
+    }
+}
+fn pid(value: &str) -> Result<PublicIdentity> {
+    PublicIdentity::from_str(value).map_err(|error| anyhow!("bad identity {}: {:?}", value, error))
+}
+// What the broker will carry. The first allow or deny rule matching a
+// packet's HBFI decides, otherwise the default does. Responses matching a
+// nocache rule are forwarded but not cached, and no cleartext packet is
+// sent over a link named in a no-cleartext rule. As a file, one rule a line:
+//
+//   # comments and blank lines are ignored
+//   default deny
+//   allow response <pid>
+//   deny request <pid>
+//   deny app <app>
+//   allow module <module>
+//   deny name cop://<pid>/<app>/<module>/<fun>/<arg>
+//   nocache app <app>
+//   no-cleartext <link pid>
+#[derive(Clone, Debug)]
+pub struct FilterPolicy {
+    pub default: FilterAction,
+    rules: Vec<(FilterAction, Matcher)>,
+    nocache: Vec<Matcher>,
+    no_cleartext: HashSet<PublicIdentity>,
+}
+impl Default for FilterPolicy {
+    fn default() -> Self {
+        Self {
+            default: FilterAction::Allow,
+            rules: vec![],
+            nocache: vec![],
+            no_cleartext: HashSet::new(),
+        }
+    }
+}
+impl FilterPolicy {
+    pub fn allow(&mut self, matcher: Matcher) {
+        self.rules.push((FilterAction::Allow, matcher));
+    }
+    pub fn deny(&mut self, matcher: Matcher) {
+        self.rules.push((FilterAction::Deny, matcher));
+    }
+    pub fn nocache(&mut self, matcher: Matcher) {
+        self.nocache.push(matcher);
+    }
+    pub fn no_cleartext(&mut self, link_pid: PublicIdentity) {
+        self.no_cleartext.insert(link_pid);
+    }
+    pub fn admits(&self, hbfi: &HBFI) -> bool {
+        let action = self.rules
+            .iter()
+            .find(|(_, matcher)| matcher.matches(hbfi))
+            .map_or(self.default, |(action, _)| *action);
+        action == FilterAction::Allow
+    }
+    pub fn caches(&self, hbfi: &HBFI) -> bool {
+        !self.nocache.iter().any(|matcher| matcher.matches(hbfi))
+    }
+    // False if the HBFI is cleartext and the link mustn't carry cleartext.
+    pub fn carries(&self, hbfi: &HBFI, link_id: &LinkId) -> bool {
+        if hbfi.request_pid != PublicIdentityInterface::Absent || self.no_cleartext.is_empty() {
+            return true
+        }
+        match link_id.link_pid() {
+            Ok(link_pid) => !self.no_cleartext.contains(&link_pid),
+            Err(_) => true,
+        }
+    }
+    pub fn load(path: &Path) -> Result<Self> {
+        fs::read_to_string(path)?.parse()
+    }
+}
+impl FromStr for FilterPolicy {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let mut policy = FilterPolicy::default();
+        for (n, line) in s.lines().enumerate() {
+            // only a word starting with # starts a comment, a cop:// URI
+            // keeps its #frm
+            let words: Vec<&str> = line.split_whitespace().take_while(|word| !word.starts_with('#')).collect();
+            let parsed = match words.as_slice() {
+                [] => Ok(()),
+                ["default", "allow"] => { policy.default = FilterAction::Allow; Ok(()) },
+                ["default", "deny"] => { policy.default = FilterAction::Deny; Ok(()) },
+                ["allow", kind, value] => Matcher::parse(kind, value).map(|matcher| policy.allow(matcher)),
+                ["deny", kind, value] => Matcher::parse(kind, value).map(|matcher| policy.deny(matcher)),
+                ["nocache", kind, value] => Matcher::parse(kind, value).map(|matcher| policy.nocache(matcher)),
+                ["no-cleartext", value] => pid(value).map(|link_pid| policy.no_cleartext(link_pid)),
+                _ => Err(anyhow!("unknown rule")),
+            };
+            parsed.map_err(|error| anyhow!("filter line {}: {}: {}", n + 1, line.trim(), error))?;
+        }
+        Ok(policy)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use copernica_packets::{PrivateIdentityInterface, ReplyTo};
+
+    #[test]
+    fn test_first_matching_rule_decides() {
+        let producer = PrivateIdentityInterface::new_key().public_id();
+        let blocked = PrivateIdentityInterface::new_key().public_id();
+        let link_sid = PrivateIdentityInterface::new_key();
+        let link_id = LinkId::link_with_type(link_sid.clone(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+        let config = format!("
+            # only the echo app, and never from {blocked}
+            default deny
+            deny response {blocked}
+            deny name cop://{producer}/echo/ping/fun/arg#3?for={producer} # not to ourselves
+            allow app echo
+            nocache module ping
+            no-cleartext {link}
+        ", blocked = blocked, link = link_sid.public_id(), producer = producer);
+        let policy: FilterPolicy = config.parse().unwrap();
+        let echo = HBFI::new(PublicIdentityInterface::Absent, producer.clone(), "echo", "ping", "fun", "arg").unwrap();
+        let other = HBFI::new(PublicIdentityInterface::Absent, producer.clone(), "other", "m0d", "fun", "arg").unwrap();
+        let blocked_echo = HBFI::new(PublicIdentityInterface::Absent, blocked, "echo", "m0d", "fun", "arg").unwrap();
+        let cyphertext = HBFI::new(PublicIdentityInterface::new(producer.clone()), producer.clone(), "echo", "m0d", "fun", "arg").unwrap();
+        assert!(policy.admits(&echo));
+        assert!(!policy.admits(&other));
+        assert!(!policy.admits(&blocked_echo));
+        assert!(!policy.caches(&echo));
+        assert!(policy.caches(&cyphertext));
+        assert!(!policy.carries(&echo, &link_id));
+        assert!(policy.carries(&cyphertext, &link_id));
+        let to_ourselves = HBFI::new(PublicIdentityInterface::new(producer.clone()), producer, "echo", "ping", "fun", "arg").unwrap();
+        assert!(!policy.admits(&to_ourselves));
+        assert!(!policy.admits(&to_ourselves.offset(7)));
+        assert!(policy.carries(&echo, &LinkId::choke()));
+        assert!("deny app".parse::<FilterPolicy>().is_err());
+        assert!("permit app echo".parse::<FilterPolicy>().is_err());
+    }
+}
@@ -3,15 +3,50 @@ extern crate copernica_packets;
 extern crate copernica_common;
 extern crate log;
 extern crate anyhow;
-extern crate uluru;
 extern crate crossbeam_channel;
 extern crate arrayvec;
-mod bloom_filter;
 mod broker;
 pub mod bayes;
 mod router;
+mod pending_table;
+mod replay_cache;
+mod token_bucket;
+mod defcon;
+mod response_store;
+mod snapshot;
+mod explain;
+mod announcements;
+mod digests;
+mod credits;
+mod nacks;
+mod prefetch;
+mod qos;
+mod scheduler;
+mod shard;
+mod filter;
+mod role;
+mod stats;
+mod metrics;
 pub use crate::{
-    broker::{Broker, ResponseStore},
+    broker::{Broker},
+    response_store::{ResponseStore, CachePolicy, CacheStats},
+    snapshot::{Snapshot},
+    explain::{Action, Candidate, Decision, Decisions, Skipped},
+    announcements::{Announcements},
+    digests::{Digests},
+    credits::{Credits, CreditKind, CreditPolicy, CreditStats, Grants},
+    nacks::{Nacks},
+    prefetch::{Prefetcher, PrefetchPolicy},
+    qos::{QosClass, QosPolicy, DropPolicy},
+    scheduler::{Scheduler, QueueStats, Sent},
+    shard::{Shard},
+    filter::{FilterPolicy, FilterAction, Matcher},
+    role::{Role},
+    stats::{BrokerStats, LinkStats, RequestCounts, Counters},
+    pending_table::{PendingTable, PendingRequest},
+    replay_cache::{ReplayCache, ReplayCounter, Seen},
+    token_bucket::{TokenBucket},
+    defcon::{Defcon, DefconPolicy, Mitigation, Mitigator, RateLimit},
     router::Router,
     bayes::{Bayes, LinkWeight},
 };
@@ -0,0 +1,172 @@
+use {
+    crate::{
+        stats::{BrokerStats, LinkStats},
+        qos::{QosClass},
+    },
+    copernica_packets::{LinkId},
+    anyhow::Result,
+    log::{error},
+    std::{
+        fmt::Write as _,
+        io::{Read, Write},
+        net::{SocketAddr, TcpListener, TcpStream},
+        sync::{Arc, atomic::{AtomicBool, Ordering}},
+        time::{Duration, Instant},
+    },
+};
+// Renders the broker's stats in the Prometheus text exposition format.
+pub fn render(broker: &str, stats: &BrokerStats, now: Instant) -> String {
+    let broker = escape(broker);
+    let mut out = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, samples: Vec<(String, String)>| {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        for (labels, value) in samples {
+            let _ = writeln!(out, "{}{{broker=\"{}\"{}}} {}", name, broker, labels, value);
+        }
+    };
+    let per_link = |value: &dyn Fn(&LinkStats) -> String| {
+        stats.links
+            .iter()
+            .map(|link| (format!(",link=\"{}\"", link_label(&link.link_id)), value(link)))
+            .collect::<Vec<_>>()
+    };
+    metric("copernica_link_up", "gauge", "Whether a packet has arrived on the link lately.",
+        per_link(&|link| (link.is_up(now) as u8).to_string()));
+    metric("copernica_link_received_packets_total", "counter", "Packets received on the link.",
+        per_link(&|link| link.received.to_string()));
+    metric("copernica_link_sent_packets_total", "counter", "Packets sent on the link.",
+        per_link(&|link| link.queue.sent.to_string()));
+    metric("copernica_link_dropped_packets_total", "counter", "Packets dropped on the way to the link.",
+        per_link(&|link| link.queue.dropped.to_string()));
+    let depths = stats.links
+        .iter()
+        .flat_map(|link| QosClass::ALL.iter().map(move |class| {
+            (format!(",link=\"{}\",class=\"{}\"", link_label(&link.link_id), class), link.queue.depth(*class).to_string())
+        }))
+        .collect();
+    metric("copernica_link_queue_depth", "gauge", "Packets queued for the link.", depths);
+    let credits = stats.links
+        .iter()
+        .flat_map(|link| {
+            let label = link_label(&link.link_id);
+            vec![("request", link.credits.requests), ("response", link.credits.responses)]
+                .into_iter()
+                .filter_map(move |(kind, left)| left.map(|left| (format!(",link=\"{}\",kind=\"{}\"", label, kind), left.to_string())))
+        })
+        .collect();
+    metric("copernica_link_credits", "gauge", "Requests or Responses the peer will still take on the link.", credits);
+    metric("copernica_link_credit_starved_total", "counter", "Times the link ran out of credit with packets waiting.",
+        per_link(&|link| link.credits.starved.to_string()));
+    let cache = &stats.cache;
+    metric("copernica_cache_hits_total", "counter", "Requests answered from the cache.", vec![(String::new(), cache.hits.to_string())]);
+    metric("copernica_cache_misses_total", "counter", "Requests not in the cache.", vec![(String::new(), cache.misses.to_string())]);
+    metric("copernica_cache_insertions_total", "counter", "Responses cached.", vec![(String::new(), cache.insertions.to_string())]);
+    metric("copernica_cache_evictions_total", "counter", "Responses evicted from the cache.", vec![(String::new(), cache.evictions.to_string())]);
+    metric("copernica_cache_rejections_total", "counter", "Responses the cache declined.", vec![(String::new(), cache.rejections.to_string())]);
+    metric("copernica_cache_entries", "gauge", "Responses in the cache.", vec![(String::new(), cache.entries.to_string())]);
+    metric("copernica_cache_bytes", "gauge", "Bytes of Responses in the cache.", vec![(String::new(), cache.bytes.to_string())]);
+    metric("copernica_cache_capacity_bytes", "gauge", "The cache capacity.", vec![(String::new(), cache.capacity.to_string())]);
+    let requests = &stats.requests;
+    let decisions = vec![
+        ("forwarded", requests.forwarded),
+        ("unroutable", requests.unroutable),
+        ("found_in_cache", requests.found_in_cache),
+        ("found_nack", requests.found_nack),
+        ("duplicate", requests.duplicate),
+        ("replay", requests.replay),
+        ("hop_limit", requests.hop_limit),
+        ("expired", requests.expired),
+        ("rate_limited", requests.rate_limited),
+        ("filtered", requests.filtered),
+        ("mitigated", requests.mitigated),
+    ];
+    metric("copernica_requests_total", "counter", "Routing decisions taken on Requests.",
+        decisions.into_iter().map(|(action, count)| (format!(",action=\"{}\"", action), count.to_string())).collect());
+    metric("copernica_pending_requests", "gauge", "Requests waiting on a Response.", vec![(String::new(), stats.pending.to_string())]);
+    metric("copernica_decode_failures_total", "counter", "Packets that couldn't be verified or made sense of.", vec![(String::new(), stats.decode_failures.to_string())]);
+    metric("copernica_router_errors_total", "counter", "Packets the router failed to handle.", vec![(String::new(), stats.router_errors.to_string())]);
+    metric("copernica_bayes_bfis", "gauge", "HBFIs the routing model holds counts for.", vec![(String::new(), stats.bayes_bfis.to_string())]);
+    out
+}
+fn link_label(link_id: &LinkId) -> String {
+    match link_id.lookup_id() {
+        Ok(lookup_id) => lookup_id.to_string(),
+        Err(_) => "choke".into(),
+    }
+}
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+// Serves `GET /metrics` until `running` is cleared, returning the bound
+// address so port 0 can be used.
+pub fn serve<F>(addr: SocketAddr, running: Arc<AtomicBool>, metrics: F) -> Result<SocketAddr>
+where
+    F: Fn() -> String + Send + 'static,
+{
+    let listener = TcpListener::bind(addr)?;
+    listener.set_nonblocking(true)?;
+    let addr = listener.local_addr()?;
+    std::thread::spawn(move || {
+        while running.load(Ordering::SeqCst) {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    if let Err(error) = respond(stream, &metrics) {
+                        error!("metrics {}: {}", addr, error);
+                    }
+                },
+                Err(ref error) if error.kind() == std::io::ErrorKind::WouldBlock => {
+                    std::thread::sleep(Duration::from_millis(50));
+                },
+                Err(error) => error!("metrics {}: {}", addr, error),
+            }
+        }
+    });
+    Ok(addr)
+}
+fn respond<F: Fn() -> String>(mut stream: TcpStream, metrics: &F) -> Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+    let mut request = [0u8; 1024];
+    let n = stream.read(&mut request)?;
+    let request = String::from_utf8_lossy(&request[..n]);
+    let mut words = request.split_whitespace();
+    let (status, body) = match (words.next(), words.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", metrics()),
+        _ => ("404 Not Found", "not found\n".into()),
+    };
+    write!(stream,
+        "HTTP/1.0 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, body.len(), body)?;
+    Ok(())
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Bayes, response_store::ResponseStore, scheduler::Scheduler, shard::Shard,
+        stats::Counters, explain::Action, credits::Credits,
+    };
+    use copernica_packets::{PrivateIdentityInterface, PublicIdentityInterface, ReplyTo};
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_render() {
+        let link0 = LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+        let counters = Mutex::new(Counters::default());
+        counters.lock().unwrap().received(&link0, Instant::now());
+        counters.lock().unwrap().request(&Action::FoundInCache);
+        let shards = vec![Shard::new(ResponseStore::default(), Bayes::new())];
+        let credits = Mutex::new(Credits::new());
+        credits.lock().unwrap().grant(&link0, &PrivateIdentityInterface::new_key().public_id(), 0, 16);
+        let stats = BrokerStats::collect(&shards, &Mutex::new(Scheduler::new()), &counters, &credits);
+        let text = render("router \"0\"", &stats, Instant::now());
+        let link = link_label(&link0);
+        assert!(text.contains(&format!("copernica_link_up{{broker=\"router \\\"0\\\"\",link=\"{}\"}} 1\n", link)));
+        assert!(text.contains(&format!("copernica_link_received_packets_total{{broker=\"router \\\"0\\\"\",link=\"{}\"}} 1\n", link)));
+        assert!(text.contains("copernica_requests_total{broker=\"router \\\"0\\\"\",action=\"found_in_cache\"} 1\n"));
+        assert!(text.contains("# TYPE copernica_cache_hits_total counter\n"));
+        assert!(text.contains(&format!("copernica_link_credits{{broker=\"router \\\"0\\\"\",link=\"{}\",kind=\"response\"}} 16\n", link)));
+        assert!(text.contains(&format!("copernica_link_credit_starved_total{{broker=\"router \\\"0\\\"\",link=\"{}\"}} 0\n", link)));
+    }
+}
@@ -0,0 +1,72 @@
+use {
+    copernica_packets::{HBFI, NarrowWaistPacket},
+    copernica_common::constants::{NACK_LIFETIME, PENDING_TABLE_SIZE},
+    std::{
+        collections::HashMap,
+        time::{Duration, Instant},
+    },
+};
+// The Nacks producers sent back recently, so Requests for a name the
+// producer just turned down are answered without asking it again. They're
+// only kept briefly as the producer may serve the name a moment later.
+#[derive(Clone)]
+pub struct Nacks {
+    lifetime: Duration,
+    capacity: usize,
+    entries: HashMap<HBFI, (NarrowWaistPacket, Instant)>,
+}
+impl Nacks {
+    pub fn new() -> Self {
+        Self {
+            lifetime: Duration::from_millis(NACK_LIFETIME),
+            capacity: PENDING_TABLE_SIZE,
+            entries: HashMap::new(),
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    pub fn insert(&mut self, nw: NarrowWaistPacket, now: Instant) {
+        let hbfi = nw.hbfi();
+        if !self.entries.contains_key(&hbfi) && self.entries.len() >= self.capacity {
+            self.expire(now);
+            if self.entries.len() >= self.capacity {
+                return
+            }
+        }
+        self.entries.insert(hbfi, (nw, now + self.lifetime));
+    }
+    pub fn get(&self, hbfi: &HBFI, now: Instant) -> Option<&NarrowWaistPacket> {
+        match self.entries.get(hbfi) {
+            Some((nw, expiry)) if *expiry > now => Some(nw),
+            _ => None,
+        }
+    }
+    pub fn expire(&mut self, now: Instant) {
+        self.entries.retain(|_, (_, expiry)| *expiry > now);
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use copernica_packets::{NackReason, PrivateIdentityInterface, PublicIdentityInterface};
+
+    #[test]
+    fn test_nacks_are_kept_briefly() {
+        let response_sid = PrivateIdentityInterface::new_key();
+        let hbfi = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "app", "m0d", "fun", "arg").unwrap();
+        let nack = NarrowWaistPacket::nack(response_sid, hbfi.clone(), NackReason::UnknownName).unwrap();
+        let now = Instant::now();
+        let mut nacks = Nacks::new();
+        nacks.insert(nack.clone(), now);
+        assert_eq!(nacks.get(&hbfi, now), Some(&nack));
+        assert_eq!(nacks.get(&hbfi.clone().offset(1), now), None);
+        let later = now + Duration::from_millis(NACK_LIFETIME);
+        assert_eq!(nacks.get(&hbfi, later), None);
+        nacks.expire(later);
+        assert!(nacks.is_empty());
+    }
+}
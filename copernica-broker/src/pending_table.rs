@@ -0,0 +1,211 @@
+use {
+    copernica_packets::{HBFI, LinkId},
+    copernica_common::constants::{PENDING_TABLE_SIZE, PENDING_REQUEST_LIFETIME},
+    std::{
+        collections::{HashMap, HashSet},
+        time::{Duration, Instant},
+    },
+};
+#[derive(Clone, Debug)]
+pub struct PendingRequest {
+    pub expiry: Instant,
+    pub downstream: HashSet<LinkId>,
+    pub upstream: HashSet<LinkId>,
+    pub retransmissions: u32,
+}
+impl PendingRequest {
+    fn new(expiry: Instant) -> Self {
+        Self {
+            expiry,
+            downstream: HashSet::new(),
+            upstream: HashSet::new(),
+            retransmissions: 0,
+        }
+    }
+}
+// The pending request table, one entry per HBFI (frame included) holding the
+// links Requests arrived on and the links they were forwarded to. Entries
+// are removed once a Response satisfies them or their lifetime runs out.
+#[derive(Clone)]
+pub struct PendingTable {
+    lifetime: Duration,
+    capacity: usize,
+    entries: HashMap<HBFI, PendingRequest>,
+}
+impl PendingTable {
+    pub fn new() -> Self {
+        Self {
+            lifetime: Duration::from_millis(PENDING_REQUEST_LIFETIME),
+            capacity: PENDING_TABLE_SIZE,
+            entries: HashMap::new(),
+        }
+    }
+    pub fn set_lifetime(&mut self, lifetime: Duration) {
+        self.lifetime = lifetime;
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    pub fn get(&self, hbfi: &HBFI) -> Option<&PendingRequest> {
+        self.entries.get(hbfi)
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (&HBFI, &PendingRequest)> {
+        self.entries.iter()
+    }
+    // A pending request is used to determine the direction of downstream
+    // (from where the Request originated, NOT where the Response might be).
+    // A Request arriving again on a link that is already downstream is a
+    // retransmission and refreshes the entry's lifetime. A Request with a
+    // lifetime is only kept as long as its requester still waits, though the
+    // entry lives on while any link downstream does.
+    pub fn create_pending_request(&mut self, hbfi: &HBFI, link_id: &LinkId, now: Instant, remaining: Option<Duration>) {
+        let lifetime = remaining.map_or(self.lifetime, |remaining| remaining.min(self.lifetime));
+        let expiry = now + lifetime;
+        if !self.entries.contains_key(hbfi) && self.entries.len() >= self.capacity {
+            self.evict_soonest();
+        }
+        let entry = self.entries
+            .entry(hbfi.clone())
+            .or_insert_with(|| PendingRequest::new(expiry));
+        if entry.expiry <= now {
+            *entry = PendingRequest::new(expiry);
+        }
+        if !entry.downstream.insert(link_id.clone()) {
+            entry.retransmissions += 1;
+        }
+        entry.expiry = entry.expiry.max(expiry);
+    }
+    pub fn contains_pending_request(&self, hbfi: &HBFI, link_id: &LinkId, now: Instant) -> bool {
+        match self.entries.get(hbfi) {
+            Some(entry) if entry.expiry > now => entry.downstream.contains(link_id),
+            _ => false,
+        }
+    }
+    // A forwarded request is used to determine the direction or a potential
+    // direction of upstream (where the Response might be). If a link has a
+    // pending_request on it, it means that link is facing DOWNSTREAM (towards
+    // the Request) hence we will not forward the Request on that link.
+    pub fn create_forwarded_request(&mut self, hbfi: &HBFI, link_id: &LinkId) {
+        if let Some(entry) = self.entries.get_mut(hbfi) {
+            entry.upstream.insert(link_id.clone());
+        }
+    }
+    pub fn contains_forwarded_request(&self, hbfi: &HBFI, link_id: &LinkId, now: Instant) -> bool {
+        match self.entries.get(hbfi) {
+            Some(entry) if entry.expiry > now => entry.upstream.contains(link_id),
+            _ => false,
+        }
+    }
+    // Removes the entry a Response satisfies, handing back the downstream links.
+    pub fn satisfy(&mut self, hbfi: &HBFI) -> Option<PendingRequest> {
+        self.entries.remove(hbfi)
+    }
+    // Withdraws the link's interest in the HBFI. Once no link downstream is
+    // left the entry is removed and handed back, so its upstream links can
+    // be told to forget it too.
+    pub fn cancel(&mut self, hbfi: &HBFI, link_id: &LinkId) -> Option<PendingRequest> {
+        let entry = self.entries.get_mut(hbfi)?;
+        if !entry.downstream.remove(link_id) || !entry.downstream.is_empty() {
+            return None
+        }
+        self.entries.remove(hbfi)
+    }
+    pub fn expire(&mut self, now: Instant) -> Vec<(HBFI, PendingRequest)> {
+        let expired: Vec<HBFI> = self.entries
+            .iter()
+            .filter(|(_, entry)| entry.expiry <= now)
+            .map(|(hbfi, _)| hbfi.clone())
+            .collect();
+        expired
+            .into_iter()
+            .filter_map(|hbfi| self.entries.remove(&hbfi).map(|entry| (hbfi, entry)))
+            .collect()
+    }
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+    fn evict_soonest(&mut self) {
+        let soonest = self.entries
+            .iter()
+            .min_by_key(|(_, entry)| entry.expiry)
+            .map(|(hbfi, _)| hbfi.clone());
+        if let Some(hbfi) = soonest {
+            self.entries.remove(&hbfi);
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use copernica_packets::{PrivateIdentityInterface, PublicIdentityInterface, ReplyTo};
+
+    fn link() -> LinkId {
+        LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::Absent, ReplyTo::Mpsc)
+    }
+    fn hbfi() -> HBFI {
+        let response_sid = PrivateIdentityInterface::new_key();
+        HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "app", "m0d", "fun", "arg").unwrap()
+    }
+
+    #[test]
+    fn test_capacity_evicts_soonest_to_expire() {
+        let (hbfi, link) = (hbfi(), link());
+        let now = Instant::now();
+        let mut pt = PendingTable::new();
+        pt.capacity = 2;
+        pt.create_pending_request(&hbfi.clone().offset(0), &link, now, Some(Duration::from_millis(300)));
+        pt.create_pending_request(&hbfi.clone().offset(1), &link, now, Some(Duration::from_millis(100)));
+        pt.create_pending_request(&hbfi.clone().offset(2), &link, now, Some(Duration::from_millis(200)));
+        assert_eq!(pt.len(), 2);
+        assert!(pt.contains_pending_request(&hbfi.clone().offset(0), &link, now));
+        assert!(!pt.contains_pending_request(&hbfi.clone().offset(1), &link, now));
+        assert!(pt.contains_pending_request(&hbfi.clone().offset(2), &link, now));
+        // a Request for an HBFI already pending never evicts another entry
+        pt.create_pending_request(&hbfi.clone().offset(2), &link, now, None);
+        assert!(pt.contains_pending_request(&hbfi.clone().offset(0), &link, now));
+    }
+
+    #[test]
+    fn test_expiry() {
+        let (hbfi, downstream, upstream) = (hbfi(), link(), link());
+        let now = Instant::now();
+        let mut pt = PendingTable::new();
+        pt.set_lifetime(Duration::from_millis(100));
+        pt.create_pending_request(&hbfi, &downstream, now, None);
+        pt.create_forwarded_request(&hbfi, &upstream);
+        let later = now + Duration::from_millis(100);
+        assert!(pt.contains_forwarded_request(&hbfi, &upstream, now));
+        assert!(!pt.contains_pending_request(&hbfi, &downstream, later));
+        assert!(!pt.contains_forwarded_request(&hbfi, &upstream, later));
+        assert!(pt.expire(now).is_empty());
+        let expired = pt.expire(later);
+        assert_eq!(expired.len(), 1);
+        assert!(expired[0].1.upstream.contains(&upstream));
+        assert!(pt.is_empty());
+    }
+
+    #[test]
+    fn test_retransmissions_are_counted_per_link() {
+        let (hbfi, link0, link1) = (hbfi(), link(), link());
+        let now = Instant::now();
+        let mut pt = PendingTable::new();
+        pt.set_lifetime(Duration::from_millis(100));
+        pt.create_pending_request(&hbfi, &link0, now, None);
+        pt.create_pending_request(&hbfi, &link1, now, None);
+        assert_eq!(pt.get(&hbfi).map(|entry| entry.retransmissions), Some(0));
+        let later = now + Duration::from_millis(50);
+        pt.create_pending_request(&hbfi, &link0, later, None);
+        assert_eq!(pt.get(&hbfi).map(|entry| entry.retransmissions), Some(1));
+        // the retransmission refreshed the entry's lifetime
+        assert!(pt.contains_pending_request(&hbfi, &link0, now + Duration::from_millis(120)));
+        // an expired entry starts afresh
+        let expired = later + Duration::from_millis(100);
+        pt.create_pending_request(&hbfi, &link0, expired, None);
+        let entry = pt.get(&hbfi).unwrap();
+        assert_eq!(entry.retransmissions, 0);
+        assert!(!entry.downstream.contains(&link1));
+    }
+}
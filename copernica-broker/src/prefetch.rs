@@ -0,0 +1,198 @@
+use {
+    crate::{
+        token_bucket::{TokenBucket},
+    },
+    copernica_packets::{HBFI, HBFIExcludeFrame},
+    copernica_common::constants::{
+        PREFETCH_DEPTH, PREFETCH_POPULARITY, PREFETCH_BANDWIDTH, PREFETCH_STREAMS, PREFETCH_IDLE,
+        CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_DATA_END,
+    },
+    std::{
+        collections::HashMap,
+        time::{Duration, Instant},
+    },
+};
+// `depth` frames are requested ahead of a sequential reader once an object
+// has been requested `popularity` times. The bandwidth is in bytes per second
+// of prefetched Responses with a second's worth allowed in a burst, less than
+// a Response a second turns prefetching off.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PrefetchPolicy {
+    pub depth: u64,
+    pub popularity: u32,
+    pub bandwidth: u32,
+}
+impl Default for PrefetchPolicy {
+    fn default() -> Self {
+        Self {
+            depth: PREFETCH_DEPTH,
+            popularity: PREFETCH_POPULARITY,
+            bandwidth: PREFETCH_BANDWIDTH,
+        }
+    }
+}
+#[derive(Clone, Debug)]
+struct Stream {
+    requests: u32,
+    last_frm: u64,
+    sequential: bool,
+    prefetched_to: Option<u64>,
+    // the last frame, once the producer turned down a frame past it
+    end: Option<u64>,
+    last: Instant,
+}
+// Follows how often each object is requested and whether its frames are read
+// in order, handing back the frames worth requesting before anyone asks.
+#[derive(Clone)]
+pub struct Prefetcher {
+    policy: PrefetchPolicy,
+    budget: TokenBucket,
+    streams: HashMap<HBFIExcludeFrame, Stream>,
+    prefetched: u64,
+}
+impl Prefetcher {
+    pub fn new(policy: PrefetchPolicy, now: Instant) -> Self {
+        let responses = Prefetcher::responses_per_second(policy.bandwidth);
+        Self {
+            policy,
+            budget: TokenBucket::new(responses, responses, now),
+            streams: HashMap::new(),
+            prefetched: 0,
+        }
+    }
+    fn responses_per_second(bandwidth: u32) -> u32 {
+        (bandwidth as usize / CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_DATA_END) as u32
+    }
+    pub fn policy(&self) -> PrefetchPolicy {
+        self.policy
+    }
+    pub fn len(&self) -> usize {
+        self.streams.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.streams.is_empty()
+    }
+    // The number of Requests issued ahead of demand so far.
+    pub fn prefetched(&self) -> u64 {
+        self.prefetched
+    }
+    // Records a Request and returns the frames after it to prefetch, in order.
+    pub fn observe(&mut self, hbfi: &HBFI, now: Instant) -> Vec<HBFI> {
+        let key = HBFIExcludeFrame(hbfi.clone());
+        if !self.streams.contains_key(&key) && self.streams.len() >= PREFETCH_STREAMS {
+            self.evict_idlest();
+        }
+        let stream = self.streams
+            .entry(key)
+            .or_insert(Stream { requests: 0, last_frm: hbfi.frm, sequential: false, prefetched_to: None, end: None, last: now });
+        stream.requests = stream.requests.saturating_add(1);
+        if hbfi.frm != stream.last_frm {
+            stream.sequential = stream.last_frm.checked_add(1) == Some(hbfi.frm);
+            stream.last_frm = hbfi.frm;
+        }
+        stream.last = now;
+        if !stream.sequential || stream.requests < self.policy.popularity || self.budget.rate() == 0.0 {
+            return vec![]
+        }
+        let from = match stream.prefetched_to {
+            Some(prefetched_to) if prefetched_to > hbfi.frm => prefetched_to + 1,
+            _ => hbfi.frm + 1,
+        };
+        let to = hbfi.frm.saturating_add(self.policy.depth);
+        let to = stream.end.map_or(to, |end| end.min(to));
+        let frames: Vec<HBFI> = (from..=to).map(|frm| hbfi.clone().offset(frm)).collect();
+        if let Some(last) = frames.last() {
+            stream.prefetched_to = Some(last.frm);
+        }
+        frames
+    }
+    // The producer doesn't know the frame, so nothing past the one before it
+    // is prefetched.
+    pub fn nacked(&mut self, hbfi: &HBFI) {
+        if let Some(stream) = self.streams.get_mut(&HBFIExcludeFrame(hbfi.clone())) {
+            let last = hbfi.frm.saturating_sub(1);
+            stream.end = Some(stream.end.map_or(last, |end| end.min(last)));
+        }
+    }
+    // Takes from the bandwidth budget, false if it has run out.
+    pub fn admit(&mut self, now: Instant) -> bool {
+        if self.budget.try_take(now) {
+            self.prefetched += 1;
+            return true
+        }
+        false
+    }
+    pub fn expire(&mut self, now: Instant) {
+        let idle = Duration::from_millis(PREFETCH_IDLE);
+        self.streams.retain(|_, stream| now.saturating_duration_since(stream.last) < idle);
+    }
+    fn evict_idlest(&mut self) {
+        let idlest = self.streams
+            .iter()
+            .min_by_key(|(_, stream)| stream.last)
+            .map(|(key, _)| key.clone());
+        if let Some(key) = idlest {
+            self.streams.remove(&key);
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use copernica_packets::{PrivateIdentityInterface, PublicIdentityInterface};
+
+    #[test]
+    fn test_sequential_popular_objects_are_prefetched() {
+        let response_sid = PrivateIdentityInterface::new_key();
+        let hbfi = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "app", "m0d", "fun", "arg").unwrap();
+        let now = Instant::now();
+        let policy = PrefetchPolicy { depth: 3, popularity: 2, bandwidth: u32::MAX };
+        let mut prefetcher = Prefetcher::new(policy, now);
+        assert!(prefetcher.observe(&hbfi.clone().offset(0), now).is_empty());
+        let frames: Vec<u64> = prefetcher.observe(&hbfi.clone().offset(1), now).iter().map(|hbfi| hbfi.frm).collect();
+        assert_eq!(frames, vec![2, 3, 4]);
+        // only the frame not yet prefetched is asked for next
+        let frames: Vec<u64> = prefetcher.observe(&hbfi.clone().offset(2), now).iter().map(|hbfi| hbfi.frm).collect();
+        assert_eq!(frames, vec![5]);
+        // random access isn't prefetched
+        assert!(prefetcher.observe(&hbfi.clone().offset(9), now).is_empty());
+        prefetcher.expire(now + Duration::from_millis(PREFETCH_IDLE));
+        assert!(prefetcher.is_empty());
+    }
+    #[test]
+    fn test_budget_limits_prefetching() {
+        let now = Instant::now();
+        let bandwidth = (CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_DATA_END * 2) as u32;
+        let mut prefetcher = Prefetcher::new(PrefetchPolicy { bandwidth, ..PrefetchPolicy::default() }, now);
+        assert!(prefetcher.admit(now));
+        assert!(prefetcher.admit(now));
+        assert!(!prefetcher.admit(now));
+        assert!(prefetcher.admit(now + Duration::from_secs(1)));
+        assert_eq!(prefetcher.prefetched(), 3);
+    }
+    #[test]
+    fn test_no_bandwidth_no_prefetching() {
+        let response_sid = PrivateIdentityInterface::new_key();
+        let hbfi = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "app", "m0d", "fun", "arg").unwrap();
+        let now = Instant::now();
+        let mut prefetcher = Prefetcher::new(PrefetchPolicy { bandwidth: 0, popularity: 1, ..PrefetchPolicy::default() }, now);
+        assert!(prefetcher.observe(&hbfi.clone().offset(0), now).is_empty());
+        assert!(prefetcher.observe(&hbfi.clone().offset(1), now).is_empty());
+        assert!(!prefetcher.admit(now + Duration::from_secs(1)));
+    }
+    #[test]
+    fn test_prefetching_stops_at_the_last_frame() {
+        let response_sid = PrivateIdentityInterface::new_key();
+        let hbfi = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "app", "m0d", "fun", "arg").unwrap();
+        let now = Instant::now();
+        let policy = PrefetchPolicy { depth: 4, popularity: 1, bandwidth: u32::MAX };
+        let mut prefetcher = Prefetcher::new(policy, now);
+        prefetcher.observe(&hbfi.clone().offset(0), now);
+        let frames: Vec<u64> = prefetcher.observe(&hbfi.clone().offset(1), now).iter().map(|hbfi| hbfi.frm).collect();
+        assert_eq!(frames, vec![2, 3, 4, 5]);
+        prefetcher.nacked(&hbfi.clone().offset(5));
+        prefetcher.nacked(&hbfi.clone().offset(4));
+        assert!(prefetcher.observe(&hbfi.clone().offset(2), now).is_empty());
+        assert!(prefetcher.observe(&hbfi.clone().offset(3), now).is_empty());
+    }
+}
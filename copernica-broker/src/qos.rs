@@ -0,0 +1,104 @@
+use {
+    crate::{
+        defcon::{RateLimit},
+    },
+    copernica_packets::{bloom_filter_index as bfi, BFI, NarrowWaistPacket},
+    copernica_common::constants::{QOS_QUEUE_DEPTH, QOS_INTERACTIVE_WEIGHT, QOS_BULK_WEIGHT, QOS_BACKGROUND_WEIGHT},
+    anyhow::Result,
+    std::{
+        collections::HashMap,
+        fmt,
+    },
+};
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum QosClass {
+    Interactive,
+    Bulk,
+    Background,
+}
+impl QosClass {
+    pub const ALL: [QosClass; 3] = [QosClass::Interactive, QosClass::Bulk, QosClass::Background];
+    pub fn index(&self) -> usize {
+        match self {
+            QosClass::Interactive => 0,
+            QosClass::Bulk => 1,
+            QosClass::Background => 2,
+        }
+    }
+    // Packets sent of each class per round of the scheduler.
+    pub fn weight(&self) -> usize {
+        match self {
+            QosClass::Interactive => QOS_INTERACTIVE_WEIGHT,
+            QosClass::Bulk => QOS_BULK_WEIGHT,
+            QosClass::Background => QOS_BACKGROUND_WEIGHT,
+        }
+    }
+}
+impl fmt::Display for QosClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QosClass::Interactive => write!(f, "interactive"),
+            QosClass::Bulk => write!(f, "bulk"),
+            QosClass::Background => write!(f, "background"),
+        }
+    }
+}
+// Which packet goes when a link's queue for a class is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DropPolicy {
+    Newest,
+    Oldest,
+}
+// Requests and Responses are Bulk unless their app is given a class,
+// credit grants and Cancels are Interactive, Announces and cache digests are Background. Rate limits apply to the
+// Requests arriving on each link and from each requester, the Mitigator keeps their buckets.
+#[derive(Clone, Debug)]
+pub struct QosPolicy {
+    pub link_rate_limit: Option<RateLimit>,
+    pub requester_rate_limit: Option<RateLimit>,
+    pub queue_depth: usize,
+    pub drop: DropPolicy,
+    pub default_class: QosClass,
+    apps: HashMap<BFI, QosClass>,
+}
+impl QosPolicy {
+    pub fn app(&mut self, app: &str, class: QosClass) -> Result<()> {
+        self.apps.insert(bfi(app)?, class);
+        Ok(())
+    }
+    pub fn classify(&self, nw: &NarrowWaistPacket) -> QosClass {
+        match nw {
+            NarrowWaistPacket::Credit { .. } | NarrowWaistPacket::Cancel { .. } => QosClass::Interactive,
+            NarrowWaistPacket::Announce { .. } | NarrowWaistPacket::Digest { .. } => QosClass::Background,
+            _ => *self.apps.get(&nw.hbfi().app).unwrap_or(&self.default_class),
+        }
+    }
+}
+impl Default for QosPolicy {
+    fn default() -> Self {
+        Self {
+            link_rate_limit: None,
+            requester_rate_limit: None,
+            queue_depth: QOS_QUEUE_DEPTH,
+            drop: DropPolicy::Newest,
+            default_class: QosClass::Bulk,
+            apps: HashMap::new(),
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use copernica_packets::{HBFI, PrivateIdentityInterface, PublicIdentityInterface, RequestOptions};
+
+    #[test]
+    fn test_classify_by_app() {
+        let response_sid = PrivateIdentityInterface::new_key();
+        let mut policy = QosPolicy::default();
+        policy.app("chat", QosClass::Interactive).unwrap();
+        let chat = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "chat", "m0d", "fun", "arg").unwrap();
+        let video = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "video", "m0d", "fun", "arg").unwrap();
+        assert_eq!(policy.classify(&NarrowWaistPacket::request(chat, RequestOptions::default()).unwrap()), QosClass::Interactive);
+        assert_eq!(policy.classify(&NarrowWaistPacket::request(video, RequestOptions::default()).unwrap()), QosClass::Bulk);
+    }
+}
@@ -0,0 +1,137 @@
+use {
+    copernica_packets::{Nonce, HBFI, LinkId},
+    copernica_common::constants::{REPLAY_CACHE_SIZE, REPLAY_WINDOW},
+    std::{
+        collections::{HashMap, VecDeque},
+        time::{Duration, Instant},
+    },
+};
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Seen {
+    Fresh,
+    // arrived again over a different link, i.e. the Request looped or took a second path
+    Duplicate,
+    // arrived again over the link it first arrived on
+    Replay,
+}
+#[derive(Clone, Debug)]
+pub struct ReplayCounter {
+    pub requests: u64,
+    pub replays: u64,
+    window_start: Instant,
+}
+impl ReplayCounter {
+    fn new(now: Instant) -> Self {
+        Self { requests: 0, replays: 0, window_start: now }
+    }
+}
+// Remembers the nonce and HBFI of recently routed Requests so that a Request
+// which loops back to a broker, arrives again over a second path or is
+// replayed by an attacker is dropped. Oldest entries fall out once the cache
+// is full or the replay window has passed. Replays are counted per link.
+#[derive(Clone)]
+pub struct ReplayCache {
+    window: Duration,
+    capacity: usize,
+    seen: HashMap<(Nonce, HBFI), (Instant, LinkId)>,
+    order: VecDeque<((Nonce, HBFI), Instant)>,
+    counters: HashMap<LinkId, ReplayCounter>,
+}
+impl ReplayCache {
+    pub fn new() -> Self {
+        Self {
+            window: Duration::from_millis(REPLAY_WINDOW),
+            capacity: REPLAY_CACHE_SIZE,
+            seen: HashMap::new(),
+            order: VecDeque::new(),
+            counters: HashMap::new(),
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.seen.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.seen.is_empty()
+    }
+    pub fn check(&mut self, nonce: &Nonce, hbfi: &HBFI, link_id: &LinkId, now: Instant) -> Seen {
+        let window = self.window;
+        let counter = self.counters
+            .entry(link_id.clone())
+            .or_insert_with(|| ReplayCounter::new(now));
+        if now.duration_since(counter.window_start) >= window {
+            *counter = ReplayCounter::new(now);
+        }
+        counter.requests += 1;
+        let key = (nonce.clone(), hbfi.clone());
+        if let Some((expiry, first_link)) = self.seen.get(&key) {
+            if *expiry > now {
+                if first_link == link_id {
+                    counter.replays += 1;
+                    return Seen::Replay
+                }
+                return Seen::Duplicate
+            }
+        }
+        while self.order.len() >= self.capacity {
+            self.pop_oldest();
+        }
+        let expiry = now + self.window;
+        self.seen.insert(key.clone(), (expiry, link_id.clone()));
+        self.order.push_back((key, expiry));
+        Seen::Fresh
+    }
+    pub fn counter(&self, link_id: &LinkId) -> Option<&ReplayCounter> {
+        self.counters.get(link_id)
+    }
+    // The percentage of the link's Requests in the current window that were
+    // replays, on the same scale as the litmus weight of the Defcon levels.
+    pub fn litmus(&self, link_id: &LinkId) -> u64 {
+        match self.counters.get(link_id) {
+            Some(counter) if counter.requests > 0 => counter.replays * 100 / counter.requests,
+            _ => 0,
+        }
+    }
+    pub fn expire(&mut self, now: Instant) {
+        while let Some((_, expiry)) = self.order.front() {
+            if *expiry > now {
+                break
+            }
+            self.pop_oldest();
+        }
+        let window = self.window;
+        self.counters.retain(|_, counter| now.duration_since(counter.window_start) < window * 2);
+    }
+    fn pop_oldest(&mut self) {
+        if let Some((key, expiry)) = self.order.pop_front() {
+            // a Request seen again after it expired has a newer entry further back
+            if self.seen.get(&key).map(|(e, _)| *e) == Some(expiry) {
+                self.seen.remove(&key);
+            }
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use copernica_packets::{PrivateIdentityInterface, PublicIdentityInterface, ReplyTo};
+
+    #[test]
+    fn test_replay_and_duplicate() {
+        let response_sid = PrivateIdentityInterface::new_key();
+        let hbfi = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "app", "m0d", "fun", "arg").unwrap();
+        let link0 = LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+        let link1 = LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+        let mut replays = ReplayCache::new();
+        let now = Instant::now();
+        let nonce = Nonce::new();
+        assert_eq!(replays.check(&nonce, &hbfi, &link0, now), Seen::Fresh);
+        assert_eq!(replays.check(&nonce, &hbfi, &link1, now), Seen::Duplicate);
+        assert_eq!(replays.check(&nonce, &hbfi, &link0, now), Seen::Replay);
+        assert_eq!(replays.check(&Nonce::new(), &hbfi, &link0, now), Seen::Fresh);
+        assert_eq!(replays.litmus(&link0), 33);
+        assert_eq!(replays.litmus(&link1), 0);
+        let later = now + Duration::from_millis(REPLAY_WINDOW);
+        assert_eq!(replays.check(&nonce, &hbfi, &link0, later), Seen::Fresh);
+        assert_eq!(replays.litmus(&link0), 0);
+    }
+}
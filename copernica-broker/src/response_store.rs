@@ -0,0 +1,618 @@
+use {
+    copernica_packets::{HBFI, NarrowWaistPacket, Freshness},
+    copernica_common::constants::{
+        RESPONSE_STORE_CAPACITY, TINY_LFU_SKETCH_WIDTH, TINY_LFU_SKETCH_DEPTH,
+    },
+    std::{
+        collections::{BTreeMap, HashMap, hash_map::DefaultHasher},
+        hash::{Hash, Hasher},
+        fmt,
+        str::FromStr,
+        time::Instant,
+    },
+    anyhow::{anyhow, Result},
+};
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum CachePolicy {
+    Lru,
+    Lfu,
+    Arc,
+    TinyLfu,
+}
+impl fmt::Display for CachePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let out = match self {
+            CachePolicy::Lru => "lru",
+            CachePolicy::Lfu => "lfu",
+            CachePolicy::Arc => "arc",
+            CachePolicy::TinyLfu => "tinylfu",
+        };
+        write!(f, "{}", out)
+    }
+}
+impl FromStr for CachePolicy {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "lru" => Ok(CachePolicy::Lru),
+            "lfu" => Ok(CachePolicy::Lfu),
+            "arc" => Ok(CachePolicy::Arc),
+            "tinylfu" => Ok(CachePolicy::TinyLfu),
+            _ => Err(anyhow!("unknown cache policy: {}", s)),
+        }
+    }
+}
+#[derive(Clone, Debug, PartialEq)]
+pub struct CacheStats {
+    pub policy: CachePolicy,
+    pub capacity: usize,
+    pub bytes: usize,
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub insertions: u64,
+    pub evictions: u64,
+    pub rejections: u64,
+}
+impl CacheStats {
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            return 0.0
+        }
+        self.hits as f64 / lookups as f64
+    }
+}
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Segment {
+    Recent,
+    Frequent,
+}
+#[derive(Clone)]
+struct Entry {
+    nw: NarrowWaistPacket,
+    size: usize,
+    tick: u64,
+    hits: u64,
+    segment: Segment,
+    stored: Instant,
+}
+impl Entry {
+    // Freshness is counted from when this broker cached the Response, as
+    // clocks across the network can't be trusted to agree.
+    fn is_stale(&self, now: Instant) -> bool {
+        match self.nw.fresh_for() {
+            Some(fresh_for) => self.stored + fresh_for <= now,
+            None => false,
+        }
+    }
+}
+// Count-Min sketch used by TinyLFU to estimate how often a name has been
+// seen, halved every `sample` observations so old popularity fades away.
+#[derive(Clone)]
+struct Sketch {
+    counters: Vec<[u8; TINY_LFU_SKETCH_WIDTH]>,
+    observations: usize,
+    sample: usize,
+}
+impl Sketch {
+    fn new() -> Self {
+        Self {
+            counters: vec![[0; TINY_LFU_SKETCH_WIDTH]; TINY_LFU_SKETCH_DEPTH],
+            observations: 0,
+            sample: TINY_LFU_SKETCH_WIDTH * 10,
+        }
+    }
+    fn index(hbfi: &HBFI, row: usize) -> usize {
+        let mut hasher = DefaultHasher::new();
+        row.hash(&mut hasher);
+        hbfi.hash(&mut hasher);
+        (hasher.finish() as usize) % TINY_LFU_SKETCH_WIDTH
+    }
+    fn increment(&mut self, hbfi: &HBFI) {
+        for row in 0..TINY_LFU_SKETCH_DEPTH {
+            let i = Sketch::index(hbfi, row);
+            if self.counters[row][i] < 15 {
+                self.counters[row][i] += 1;
+            }
+        }
+        self.observations += 1;
+        if self.observations >= self.sample {
+            for row in self.counters.iter_mut() {
+                for counter in row.iter_mut() {
+                    *counter /= 2;
+                }
+            }
+            self.observations /= 2;
+        }
+    }
+    fn estimate(&self, hbfi: &HBFI) -> u8 {
+        (0..TINY_LFU_SKETCH_DEPTH)
+            .map(|row| self.counters[row][Sketch::index(hbfi, row)])
+            .min()
+            .unwrap_or(0)
+    }
+}
+// ARC's B1 or B2 list, the names and sizes of the entries evicted from T1
+// or T2 oldest first, with the names indexed for the lookup on every miss.
+#[derive(Clone, Default)]
+struct Ghosts {
+    order: BTreeMap<u64, HBFI>,
+    names: HashMap<HBFI, (u64, usize)>,
+    bytes: usize,
+    tick: u64,
+}
+impl Ghosts {
+    // Forgets the oldest ghosts once they add up to more than `capacity`.
+    fn push(&mut self, hbfi: HBFI, size: usize, capacity: usize) {
+        self.remove(&hbfi);
+        self.tick += 1;
+        self.order.insert(self.tick, hbfi.clone());
+        self.names.insert(hbfi, (self.tick, size));
+        self.bytes += size;
+        while self.bytes > capacity {
+            let oldest = match self.order.values().next() {
+                Some(hbfi) => hbfi.clone(),
+                None => break,
+            };
+            self.remove(&oldest);
+        }
+    }
+    fn remove(&mut self, hbfi: &HBFI) -> bool {
+        match self.names.remove(hbfi) {
+            Some((tick, size)) => {
+                self.order.remove(&tick);
+                self.bytes -= size;
+                true
+            },
+            None => false,
+        }
+    }
+}
+// The content store holding signed Responses. Capacity is measured in bytes
+// of encoded NarrowWaistPackets and the replacement policy is chosen at
+// runtime. `recent` orders entries by last use and serves LRU, TinyLFU and
+// ARC's T1 list, `frequent` is ARC's T2 list and `ranked` orders LFU entries
+// by (hits, last use). `recent_bytes` is what's in `recent`, which ARC weighs
+// against its target on every eviction.
+#[derive(Clone)]
+pub struct ResponseStore {
+    policy: CachePolicy,
+    capacity: usize,
+    used: usize,
+    tick: u64,
+    entries: HashMap<HBFI, Entry>,
+    recent: BTreeMap<u64, HBFI>,
+    recent_bytes: usize,
+    frequent: BTreeMap<u64, HBFI>,
+    ranked: BTreeMap<(u64, u64), HBFI>,
+    ghost_recent: Ghosts,
+    ghost_frequent: Ghosts,
+    target: usize,
+    sketch: Sketch,
+    hits: u64,
+    misses: u64,
+    insertions: u64,
+    evictions: u64,
+    rejections: u64,
+}
+impl Default for ResponseStore {
+    fn default() -> Self {
+        ResponseStore::new(CachePolicy::Lru, RESPONSE_STORE_CAPACITY)
+    }
+}
+impl ResponseStore {
+    pub fn new(policy: CachePolicy, capacity: usize) -> Self {
+        Self {
+            policy,
+            capacity,
+            used: 0,
+            tick: 0,
+            entries: HashMap::new(),
+            recent: BTreeMap::new(),
+            recent_bytes: 0,
+            frequent: BTreeMap::new(),
+            ranked: BTreeMap::new(),
+            ghost_recent: Ghosts::default(),
+            ghost_frequent: Ghosts::default(),
+            target: 0,
+            sketch: Sketch::new(),
+            hits: 0,
+            misses: 0,
+            insertions: 0,
+            evictions: 0,
+            rejections: 0,
+        }
+    }
+    pub fn policy(&self) -> CachePolicy {
+        self.policy
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            policy: self.policy,
+            capacity: self.capacity,
+            bytes: self.used,
+            entries: self.entries.len(),
+            hits: self.hits,
+            misses: self.misses,
+            insertions: self.insertions,
+            evictions: self.evictions,
+            rejections: self.rejections,
+        }
+    }
+    // Shrinking the capacity evicts until the store fits again.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.target = self.target.min(capacity);
+        while self.used > self.capacity {
+            if !self.evict(None) {
+                break
+            }
+        }
+    }
+    // Unlike get this neither counts as a hit or miss nor refreshes the entry.
+    pub fn contains(&self, hbfi: &HBFI) -> bool {
+        self.entries.contains_key(hbfi)
+    }
+    pub fn get(&mut self, hbfi: &HBFI) -> Option<NarrowWaistPacket> {
+        self.lookup(hbfi, Freshness::Any, Instant::now())
+    }
+    // A Request for the latest version is never answered from the store and
+    // one that must be fresh evicts a stale entry so the Response is fetched
+    // from upstream again. Any other takes a stale entry until expire evicts
+    // it.
+    pub fn lookup(&mut self, hbfi: &HBFI, freshness: Freshness, now: Instant) -> Option<NarrowWaistPacket> {
+        if freshness == Freshness::Latest {
+            return None
+        }
+        if self.policy == CachePolicy::TinyLfu {
+            self.sketch.increment(hbfi);
+        }
+        let stale = match self.entries.get(hbfi) {
+            Some(entry) => entry.is_stale(now),
+            None => {
+                self.misses += 1;
+                return None
+            },
+        };
+        if stale && freshness == Freshness::MustBeFresh {
+            if let Some(entry) = self.entries.remove(hbfi) {
+                self.unindex(&entry);
+                self.used -= entry.size;
+                self.evictions += 1;
+            }
+            self.misses += 1;
+            return None
+        }
+        self.hits += 1;
+        self.touch(hbfi);
+        self.entries.get(hbfi).map(|entry| entry.nw.clone())
+    }
+    pub fn insert(&mut self, nw: NarrowWaistPacket) {
+        let hbfi = match &nw {
+            NarrowWaistPacket::Response { hbfi, .. } => hbfi.clone(),
+            _ => return,
+        };
+        let size = nw.as_bytes().len();
+        if size > self.capacity {
+            self.rejections += 1;
+            return
+        }
+        if let Some(entry) = self.entries.get(&hbfi) {
+            // an older version never replaces a newer one
+            if entry.nw.version() > nw.version() {
+                return
+            }
+            self.touch(&hbfi);
+            if let Some(mut entry) = self.entries.remove(&hbfi) {
+                self.unindex(&entry);
+                self.used = self.used - entry.size + size;
+                entry.size = size;
+                entry.nw = nw;
+                entry.stored = Instant::now();
+                self.index(&hbfi, &entry);
+                self.entries.insert(hbfi.clone(), entry);
+            }
+            // the replaced entry was just touched so it's the last to go
+            while self.used > self.capacity {
+                if !self.evict(None) {
+                    break
+                }
+            }
+            return
+        }
+        let segment = match self.policy {
+            CachePolicy::Arc => self.adapt(&hbfi, size),
+            _ => Segment::Recent,
+        };
+        if self.policy == CachePolicy::TinyLfu {
+            self.sketch.increment(&hbfi);
+            if !self.admit(&hbfi, size) {
+                self.rejections += 1;
+                return
+            }
+        }
+        while self.used + size > self.capacity {
+            if !self.evict(Some(segment)) {
+                break
+            }
+        }
+        self.tick += 1;
+        let entry = Entry { nw, size, tick: self.tick, hits: 0, segment, stored: Instant::now() };
+        self.index(&hbfi, &entry);
+        self.used += size;
+        self.entries.insert(hbfi, entry);
+        self.insertions += 1;
+    }
+    pub fn remove(&mut self, hbfi: &HBFI) -> Option<NarrowWaistPacket> {
+        let entry = self.entries.remove(hbfi)?;
+        self.unindex(&entry);
+        self.used -= entry.size;
+        Some(entry.nw)
+    }
+    // Stale Responses are evicted rather than served on indefinitely, a
+    // Request for one goes back upstream once it's gone.
+    pub fn expire(&mut self, now: Instant) -> Vec<HBFI> {
+        let stale: Vec<HBFI> = self.entries
+            .iter()
+            .filter(|(_, entry)| entry.is_stale(now))
+            .map(|(hbfi, _)| hbfi.clone())
+            .collect();
+        for hbfi in &stale {
+            if self.remove(hbfi).is_some() {
+                self.evictions += 1;
+            }
+        }
+        stale
+    }
+    pub fn clear(&mut self) {
+        let policy = self.policy;
+        let capacity = self.capacity;
+        *self = ResponseStore::new(policy, capacity);
+    }
+    pub fn iter(&self) -> impl Iterator<Item = &NarrowWaistPacket> {
+        self.entries.values().map(|entry| &entry.nw)
+    }
+    // The count most recently used Responses, most recent first.
+    pub fn most_recent(&self, count: usize) -> Vec<&NarrowWaistPacket> {
+        let mut entries: Vec<&Entry> = self.entries.values().collect();
+        entries.sort_by(|a, b| b.tick.cmp(&a.tick));
+        entries.into_iter().take(count).map(|entry| &entry.nw).collect()
+    }
+    fn index(&mut self, hbfi: &HBFI, entry: &Entry) {
+        match (self.policy, entry.segment) {
+            (CachePolicy::Lfu, _) => { self.ranked.insert((entry.hits, entry.tick), hbfi.clone()); },
+            (CachePolicy::Arc, Segment::Frequent) => { self.frequent.insert(entry.tick, hbfi.clone()); },
+            _ => {
+                self.recent.insert(entry.tick, hbfi.clone());
+                self.recent_bytes += entry.size;
+            },
+        }
+    }
+    fn unindex(&mut self, entry: &Entry) {
+        match (self.policy, entry.segment) {
+            (CachePolicy::Lfu, _) => { self.ranked.remove(&(entry.hits, entry.tick)); },
+            (CachePolicy::Arc, Segment::Frequent) => { self.frequent.remove(&entry.tick); },
+            _ => {
+                if self.recent.remove(&entry.tick).is_some() {
+                    self.recent_bytes -= entry.size;
+                }
+            },
+        }
+    }
+    fn touch(&mut self, hbfi: &HBFI) {
+        let mut entry = match self.entries.remove(hbfi) {
+            Some(entry) => entry,
+            None => return,
+        };
+        self.unindex(&entry);
+        self.tick += 1;
+        entry.tick = self.tick;
+        entry.hits += 1;
+        if self.policy == CachePolicy::Arc {
+            entry.segment = Segment::Frequent;
+        }
+        self.index(hbfi, &entry);
+        self.entries.insert(hbfi.clone(), entry);
+    }
+    // ARC: a miss that hits a ghost list moves the target size of T1 towards
+    // the list that would have kept the name, and the name re-enters as frequent.
+    fn adapt(&mut self, hbfi: &HBFI, size: usize) -> Segment {
+        if self.ghost_recent.remove(hbfi) {
+            self.target = (self.target + size).min(self.capacity);
+            return Segment::Frequent
+        }
+        if self.ghost_frequent.remove(hbfi) {
+            self.target = self.target.saturating_sub(size);
+            return Segment::Frequent
+        }
+        Segment::Recent
+    }
+    // TinyLFU: only let a newcomer displace entries that are estimated to be
+    // less popular than itself.
+    fn admit(&self, hbfi: &HBFI, size: usize) -> bool {
+        let mut needed = (self.used + size).saturating_sub(self.capacity);
+        if needed == 0 {
+            return true
+        }
+        let candidate = self.sketch.estimate(hbfi);
+        for (_, victim) in self.recent.iter() {
+            if self.sketch.estimate(victim) >= candidate {
+                return false
+            }
+            if let Some(entry) = self.entries.get(victim) {
+                needed = needed.saturating_sub(entry.size);
+            }
+            if needed == 0 {
+                return true
+            }
+        }
+        false
+    }
+    fn victim(&self, incoming: Option<Segment>) -> Option<HBFI> {
+        match self.policy {
+            CachePolicy::Lru | CachePolicy::TinyLfu => self.recent.values().next().cloned(),
+            CachePolicy::Lfu => self.ranked.values().next().cloned(),
+            CachePolicy::Arc => {
+                let from_recent = !self.recent.is_empty()
+                    && (self.recent_bytes > self.target
+                        || (self.recent_bytes == self.target && incoming == Some(Segment::Frequent))
+                        || self.frequent.is_empty());
+                if from_recent {
+                    self.recent.values().next().cloned()
+                } else {
+                    self.frequent.values().next().cloned()
+                }
+            },
+        }
+    }
+    fn evict(&mut self, incoming: Option<Segment>) -> bool {
+        let hbfi = match self.victim(incoming) {
+            Some(hbfi) => hbfi,
+            None => return false,
+        };
+        let entry = match self.entries.remove(&hbfi) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        self.unindex(&entry);
+        self.used -= entry.size;
+        self.evictions += 1;
+        if self.policy == CachePolicy::Arc {
+            match entry.segment {
+                Segment::Recent => self.ghost_recent.push(hbfi, entry.size, self.capacity),
+                Segment::Frequent => self.ghost_frequent.push(hbfi, entry.size, self.capacity),
+            }
+        }
+        true
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use copernica_packets::{PrivateIdentityInterface, PublicIdentityInterface};
+    use std::time::Duration;
+    fn responses(count: u64) -> Vec<NarrowWaistPacket> {
+        let response_sid = PrivateIdentityInterface::new_key();
+        let hbfi = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "app", "m0d", "fun", "arg").unwrap();
+        (0..count)
+            .map(|frm| NarrowWaistPacket::response(response_sid.clone(), hbfi.clone().offset(frm), vec![0; 8]).unwrap())
+            .collect()
+    }
+    fn hbfi(nw: &NarrowWaistPacket) -> HBFI {
+        match nw {
+            NarrowWaistPacket::Response { hbfi, .. } => hbfi.clone(),
+            _ => panic!("expected a response"),
+        }
+    }
+    #[test]
+    fn lru_evicts_least_recently_used() {
+        let nws = responses(3);
+        let size = nws[0].as_bytes().len();
+        let mut rs = ResponseStore::new(CachePolicy::Lru, size * 2);
+        rs.insert(nws[0].clone());
+        rs.insert(nws[1].clone());
+        assert!(rs.get(&hbfi(&nws[0])).is_some());
+        rs.insert(nws[2].clone());
+        assert!(rs.get(&hbfi(&nws[0])).is_some());
+        assert!(rs.get(&hbfi(&nws[1])).is_none());
+        assert_eq!(rs.stats().evictions, 1);
+        assert_eq!(rs.stats().bytes, size * 2);
+    }
+    #[test]
+    fn lfu_evicts_least_frequently_used() {
+        let nws = responses(3);
+        let size = nws[0].as_bytes().len();
+        let mut rs = ResponseStore::new(CachePolicy::Lfu, size * 2);
+        rs.insert(nws[0].clone());
+        rs.insert(nws[1].clone());
+        rs.get(&hbfi(&nws[1]));
+        rs.get(&hbfi(&nws[1]));
+        rs.get(&hbfi(&nws[0]));
+        rs.insert(nws[2].clone());
+        assert!(rs.get(&hbfi(&nws[0])).is_none());
+        assert!(rs.get(&hbfi(&nws[1])).is_some());
+    }
+    #[test]
+    fn tiny_lfu_rejects_one_hit_wonders() {
+        let nws = responses(3);
+        let size = nws[0].as_bytes().len();
+        let mut rs = ResponseStore::new(CachePolicy::TinyLfu, size * 2);
+        rs.insert(nws[0].clone());
+        rs.insert(nws[1].clone());
+        for _ in 0..4 {
+            rs.get(&hbfi(&nws[0]));
+            rs.get(&hbfi(&nws[1]));
+        }
+        rs.insert(nws[2].clone());
+        assert!(rs.get(&hbfi(&nws[2])).is_none());
+        assert_eq!(rs.stats().rejections, 1);
+        assert_eq!(rs.len(), 2);
+    }
+    #[test]
+    fn hit_rate() {
+        let nws = responses(1);
+        let mut rs = ResponseStore::new(CachePolicy::Arc, RESPONSE_STORE_CAPACITY);
+        rs.insert(nws[0].clone());
+        rs.get(&hbfi(&nws[0]));
+        rs.get(&hbfi(&nws[0]).offset(1));
+        assert_eq!(rs.stats().hit_rate(), 0.5);
+    }
+    #[test]
+    fn arc_readmits_ghosts_as_frequent() {
+        let nws = responses(3);
+        let size = nws[0].as_bytes().len();
+        let mut rs = ResponseStore::new(CachePolicy::Arc, size * 2);
+        rs.insert(nws[0].clone());
+        rs.insert(nws[1].clone());
+        rs.insert(nws[2].clone());
+        assert!(!rs.contains(&hbfi(&nws[0])));
+        assert!(rs.ghost_recent.names.contains_key(&hbfi(&nws[0])));
+        assert_eq!(rs.recent_bytes, size * 2);
+        rs.insert(nws[0].clone());
+        assert!(!rs.ghost_recent.names.contains_key(&hbfi(&nws[0])));
+        assert_eq!(rs.target, size);
+        assert!(rs.frequent.values().any(|frequent| *frequent == hbfi(&nws[0])));
+        assert_eq!(rs.recent_bytes, size);
+        assert_eq!(rs.stats().bytes, size * 2);
+    }
+    #[test]
+    fn stale_responses_are_evicted() {
+        let response_sid = PrivateIdentityInterface::new_key();
+        let hbfi = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "app", "m0d", "fun", "arg").unwrap();
+        let fresh_for = Some(Duration::from_secs(1));
+        let nw = NarrowWaistPacket::response_with_freshness(response_sid, hbfi.clone(), vec![0; 8], fresh_for, 0).unwrap();
+        let mut rs = ResponseStore::new(CachePolicy::Lru, RESPONSE_STORE_CAPACITY);
+        rs.insert(nw.clone());
+        let now = Instant::now();
+        assert_eq!(rs.lookup(&hbfi, Freshness::MustBeFresh, now), Some(nw.clone()));
+        assert_eq!(rs.lookup(&hbfi, Freshness::Latest, now), None);
+        assert!(rs.expire(now).is_empty());
+        let later = now + Duration::from_secs(2);
+        assert_eq!(rs.lookup(&hbfi, Freshness::MustBeFresh, later), None);
+        assert!(!rs.contains(&hbfi));
+        rs.insert(nw);
+        assert_eq!(rs.expire(later), vec![hbfi.clone()]);
+        assert_eq!(rs.lookup(&hbfi, Freshness::Any, later), None);
+        assert_eq!(rs.stats().bytes, 0);
+    }
+    #[test]
+    fn newer_versions_replace_older_ones() {
+        let response_sid = PrivateIdentityInterface::new_key();
+        let hbfi = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "app", "m0d", "fun", "arg").unwrap();
+        let version = |version| NarrowWaistPacket::response_with_freshness(response_sid.clone(), hbfi.clone(), vec![0; 8], None, version).unwrap();
+        let mut rs = ResponseStore::new(CachePolicy::Lru, RESPONSE_STORE_CAPACITY);
+        rs.insert(version(1));
+        rs.insert(version(2));
+        assert_eq!(rs.get(&hbfi).and_then(|nw| nw.version()), Some(2));
+        rs.insert(version(1));
+        assert_eq!(rs.get(&hbfi).and_then(|nw| nw.version()), Some(2));
+        assert_eq!(rs.len(), 1);
+    }
+}
@@ -0,0 +1,105 @@
+use {
+    crate::response_store::{ResponseStore, CachePolicy},
+    copernica_packets::{LinkId},
+    copernica_common::constants::{EDGE_RESPONSE_STORE_CAPACITY},
+    std::{
+        collections::HashSet,
+        fmt,
+        str::FromStr,
+    },
+    anyhow::{anyhow, Result},
+};
+// Where a broker sits in the network. Links toward the core of the network
+// are marked upstream with Broker::upstream, the rest are downstream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    // anywhere in a mesh of peers, caches and forwards like any broker without a role
+    Mesh,
+    // near consumers, caches in a content store 4 times the default size
+    Edge,
+    // carries traffic between brokers, never caches, prefetches or sends cache digests
+    Core,
+    // serves consumers only, Requests from upstream are answered from cache or not at all
+    Leaf,
+    // fronts producers, Requests from upstream only go downstream toward them
+    Gateway,
+}
+impl Role {
+    pub fn caches(&self) -> bool {
+        *self != Role::Core
+    }
+    pub fn response_store(&self) -> ResponseStore {
+        match self {
+            Role::Edge => ResponseStore::new(CachePolicy::Lru, EDGE_RESPONSE_STORE_CAPACITY),
+            Role::Core => ResponseStore::new(CachePolicy::Lru, 0),
+            Role::Mesh | Role::Leaf | Role::Gateway => ResponseStore::default(),
+        }
+    }
+    // Whether a Request from `from` may be forwarded over `to`.
+    pub fn forwards(&self, upstream: &HashSet<LinkId>, from: &LinkId, to: &LinkId) -> bool {
+        match self {
+            Role::Mesh | Role::Edge | Role::Core => true,
+            Role::Leaf => !Role::is_upstream(upstream, from),
+            Role::Gateway => !(Role::is_upstream(upstream, from) && Role::is_upstream(upstream, to)),
+        }
+    }
+    fn is_upstream(upstream: &HashSet<LinkId>, link_id: &LinkId) -> bool {
+        upstream.iter().any(|up| up.link_pid().ok() == link_id.link_pid().ok())
+    }
+}
+impl Default for Role {
+    fn default() -> Self {
+        Role::Mesh
+    }
+}
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Role::Mesh => write!(f, "mesh"),
+            Role::Edge => write!(f, "edge"),
+            Role::Core => write!(f, "core"),
+            Role::Leaf => write!(f, "leaf"),
+            Role::Gateway => write!(f, "gateway"),
+        }
+    }
+}
+impl FromStr for Role {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "mesh" => Ok(Role::Mesh),
+            "edge" => Ok(Role::Edge),
+            "core" => Ok(Role::Core),
+            "leaf" => Ok(Role::Leaf),
+            "gateway" => Ok(Role::Gateway),
+            _ => Err(anyhow!("unknown role: {}", s)),
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use copernica_packets::{PrivateIdentityInterface, PublicIdentityInterface, ReplyTo};
+
+    #[test]
+    fn test_requests_from_upstream() {
+        let link = || LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+        let (up0, up1, down) = (link(), link(), link());
+        let mut upstream = HashSet::new();
+        upstream.insert(up0.clone());
+        upstream.insert(up1.clone());
+        assert!(Role::Mesh.forwards(&upstream, &up0, &up1));
+        assert!(Role::Edge.forwards(&upstream, &up0, &up1));
+        assert!(!Role::Leaf.forwards(&upstream, &up0, &down));
+        assert!(Role::Leaf.forwards(&upstream, &down, &up0));
+        assert!(!Role::Gateway.forwards(&upstream, &up0, &up1));
+        assert!(Role::Gateway.forwards(&upstream, &up0, &down));
+        assert!(Role::Gateway.forwards(&upstream, &down, &up1));
+        assert!(!Role::Core.caches());
+        assert_eq!(Role::Edge.response_store().stats().capacity, EDGE_RESPONSE_STORE_CAPACITY);
+        assert_eq!(Role::Mesh.response_store().stats().capacity, ResponseStore::default().stats().capacity);
+        assert_eq!("gateway".parse::<Role>().unwrap(), Role::Gateway);
+        assert_eq!("mesh".parse::<Role>().unwrap(), Role::default());
+        assert!("router".parse::<Role>().is_err());
+    }
+}
@@ -1,120 +1,461 @@
 use {
     crate::{
-        bloom_filter::{Blooms},
-        Bayes, LinkWeight, ResponseStore
+        pending_table::{PendingTable},
+        replay_cache::{ReplayCache, Seen},
+        nacks::{Nacks},
+        defcon::{Defcon, Mitigator},
+        response_store::{ResponseStore},
+        explain::{Action, Candidate, Decision, Decisions, Skipped},
+        announcements::{Announcements},
+        digests::{Digests},
+        credits::{Credits},
+        prefetch::{Prefetcher},
+        filter::{FilterPolicy},
+        role::{Role},
+        stats::{Counters},
+        Bayes, LinkWeight,
     },
-    copernica_packets::{LinkId, InterLinkPacket, LinkPacket, NarrowWaistPacket },
+    copernica_packets::{LinkId, InterLinkPacket, LinkPacket, NarrowWaistPacket, NackReason, RequestOptions, HBFI },
     copernica_common::{ Operations, constants },
     anyhow::Result,
     crossbeam_channel::{Sender},
     log::{warn, trace},
-    std::collections::HashMap,
+    std::{
+        collections::HashSet,
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    },
     arrayvec::ArrayString,
 };
+// What a router thread shares with the broker and the other router threads,
+// built once when the thread starts.
+pub struct RouterContext {
+    pub label: ArrayString<{constants::LABEL_SIZE}>,
+    pub ops: Operations,
+    pub r2b_tx: Sender<InterLinkPacket>,
+    pub replays: Arc<Mutex<ReplayCache>>,
+    pub mitigator: Arc<Mutex<Mitigator>>,
+    pub filter: Arc<Mutex<FilterPolicy>>,
+    pub role: Role,
+    pub upstream: Arc<Mutex<HashSet<LinkId>>>,
+    pub announcements: Arc<Mutex<Announcements>>,
+    pub digests: Arc<Mutex<Digests>>,
+    pub credits: Arc<Mutex<Credits>>,
+    pub prefetcher: Option<Arc<Mutex<Prefetcher>>>,
+    pub decisions: Arc<Mutex<Decisions>>,
+    pub counters: Arc<Mutex<Counters>>,
+    pub choke: LinkId,
+}
+// What's the router thread's alone, its shard locked for the packet at hand
+// and the Nacks it's kept.
+pub struct ShardState<'a> {
+    pub rs: &'a mut ResponseStore,
+    pub pit: &'a mut PendingTable,
+    pub bayes: &'a mut Bayes,
+    pub nacks: &'a mut Nacks,
+}
 #[derive(Clone)]
 pub struct Router {}
 impl Router {
-    pub fn handle_packet(
-        label: &ArrayString<{constants::LABEL_SIZE}>,
-        ops: &Operations,
-        ilp: &InterLinkPacket,
-        r2b_tx: Sender<InterLinkPacket>,
-        rs: &mut ResponseStore,
-        blooms: &mut HashMap<LinkId, Blooms>,
-        bayes: &mut Bayes,
-        choke: &LinkId,
-    ) -> Result<()> {
+    pub fn handle_packet(context: &RouterContext, state: ShardState, ilp: &InterLinkPacket) -> Result<()> {
+        let RouterContext {
+            label, ops, r2b_tx, replays, mitigator, filter, role, upstream,
+            announcements, digests, credits, prefetcher, decisions, counters, choke,
+        } = context;
+        let ShardState { rs, pit, bayes, nacks } = state;
+        let prefetcher = prefetcher.as_deref();
         let this_link: LinkId = ilp.link_id();
         let nw: NarrowWaistPacket = ilp.narrow_waist();
-        if let Some(this_bloom) = blooms.get_mut(&this_link) {
-            match nw.clone() {
-                NarrowWaistPacket::Request { hbfi, .. } => {
-                    match rs.find(|n|
-                        match n {
-                            NarrowWaistPacket::Response { hbfi: compare_hbfi, .. } => { compare_hbfi == &hbfi },
-                            NarrowWaistPacket::Request  { .. } => { false }
-                        }) {
-
-                        Some(nw) => {
-                            trace!("\t\t|  |  |  |  RESPONSE PACKET FOUND");
-                            ops.found_response_upstream(label.clone());
+        let now = Instant::now();
+        match nw.clone() {
+            NarrowWaistPacket::Request { hbfi, nonce, .. } => {
+                let mut decision = Decision::new(hbfi.clone(), this_link.clone(), now);
+                let seen = replays.lock().unwrap().check(&nonce, &hbfi, &this_link, now);
+                match seen {
+                    Seen::Fresh => {},
+                    Seen::Duplicate => {
+                        trace!("\t\t|  |  |  |  DUPLICATE REQUEST DROPPED");
+                        decision.action = Action::DroppedDuplicate;
+                        Router::decide(label, ops, decisions, counters, decision);
+                        return Ok(());
+                    },
+                    Seen::Replay => {
+                        warn!("Replayed request dropped: {:?} on {:?}", hbfi, this_link);
+                        // the replay still spends a token of the link's rate limit
+                        let defcon = Router::replay_defcon(replays.lock().unwrap().litmus(&this_link));
+                        let mut mitigator = mitigator.lock().unwrap();
+                        mitigator.admit(&nw, &this_link, now);
+                        mitigator.mitigate(defcon, &nw, &this_link, bayes, label, ops, now);
+                        decision.action = Action::DroppedReplay;
+                        Router::decide(label, ops, decisions, counters, decision);
+                        return Ok(());
+                    },
+                }
+                // the lifetime counts down from the Request's arrival here,
+                // once it's run out the requester has stopped waiting
+                let lifetime = nw.lifetime();
+                let expiry = lifetime.map(|lifetime| now + lifetime);
+                if lifetime == Some(Duration::from_millis(0)) {
+                    trace!("\t\t|  |  |  |  EXPIRED REQUEST DROPPED");
+                    decision.action = Action::DroppedExpired;
+                    Router::decide(label, ops, decisions, counters, decision);
+                    return Ok(());
+                }
+                if !mitigator.lock().unwrap().admit(&nw, &this_link, now) {
+                    trace!("\t\t|  |  |  |  RATE LIMITED ON {:?}", this_link);
+                    decision.action = Action::RateLimited;
+                    Router::decide(label, ops, decisions, counters, decision);
+                    return Ok(());
+                }
+                // the Response would have to come back over this link
+                let filtered = {
+                    let filter = filter.lock().unwrap();
+                    !filter.admits(&hbfi) || !filter.carries(&hbfi, &this_link)
+                };
+                if filtered {
+                    trace!("\t\t|  |  |  |  REQUEST FILTERED");
+                    decision.action = Action::Filtered;
+                    Router::decide(label, ops, decisions, counters, decision);
+                    return Ok(());
+                }
+                // what's prefetched is only cached
+                if let Some(prefetcher) = prefetcher.filter(|_| role.caches()) {
+                    Router::prefetch(&mut prefetcher.lock().unwrap(), &hbfi, &this_link, r2b_tx.clone(), rs, pit, bayes, choke, now)?;
+                }
+                let cached = if role.caches() { rs.lookup(&hbfi, nw.freshness(), now) } else { None };
+                match cached {
+                    Some(nw) => {
+                        trace!("\t\t|  |  |  |  RESPONSE PACKET FOUND");
+                        decision.action = Action::FoundInCache;
+                        Router::decide(label, ops, decisions, counters, decision);
+                        ops.found_response_upstream(label.clone());
+                        let lp = LinkPacket::new(this_link.reply_to()?, nw.clone());
+                        let ilp = InterLinkPacket::new(this_link.clone(), lp);
+                        r2b_tx.send(ilp)?;
+                        return Ok(());
+                    }
+                    None => {
+                        // the producer turned the name down a moment ago
+                        let nacked = if role.caches() { nacks.get(&hbfi, now) } else { None };
+                        if let Some(nw) = nacked {
+                            trace!("\t\t|  |  |  |  NACK FOUND");
+                            decision.action = Action::FoundNack;
+                            Router::decide(label, ops, decisions, counters, decision);
                             let lp = LinkPacket::new(this_link.reply_to()?, nw.clone());
                             let ilp = InterLinkPacket::new(this_link.clone(), lp);
                             r2b_tx.send(ilp)?;
                             return Ok(());
                         }
-                        None => {
-                            trace!("\t\t|  |  |  |  FORWARD REQUEST UPSTREAM");
-                            ops.forward_request_upstream(label.clone());
-                            this_bloom.create_pending_request(hbfi.clone());
-                            let link_weights = bayes.classify(&hbfi);
-                            bayes.train(&hbfi, choke);
-                            if link_weights[0].linkid == *choke {
-                                //warn!("{}, {:?}", link_weights[0].weight, link_weights[0].linkid);
-                                let litmus_weight = (link_weights[0].weight * 100.00) as u64;
-                                match litmus_weight {
-                                    0..=35 => {
-                                        warn!("Defcon 4: Do something")
-                                    },
-                                    36..=59 => {
-                                        warn!("Defcon 3: Do something")
-                                        // packets need a nonce and it needs to be signed. So as to avert the
-                                        // scenario whereby an attacker replays requests thus shutting down
-                                        // the flow of legitimate information.
-                                    },
-                                    60..=89 => {
-                                        warn!("Defcon 2: Do something")
-                                    },
-                                    90..=u64::MAX => {
-                                        warn!("Defcon 1: Deep Sixed packet: {:?}", hbfi);
-                                        return Ok(())
-                                    },
-                                }
+                        let ilp = match nw.decrement_hop_limit() {
+                            Some(nw) => ilp.change_narrow_waist(nw),
+                            None => {
+                                trace!("\t\t|  |  |  |  HOP LIMIT REACHED");
+                                decision.action = Action::DroppedHopLimit;
+                                Router::decide(label, ops, decisions, counters, decision);
+                                return Ok(());
                             }
-                            let mut forwarded = false;
-                            for LinkWeight { linkid: that_link, weight} in link_weights {
-                                //warn!("{}, {:?}", weight, that_link);
-                                if that_link == *choke {
-                                    continue;
-                                }
-                                if that_link.link_pid()? == this_link.link_pid()? {
-                                    continue;
+                        };
+                        // what's left of the lifetime goes upstream with the
+                        // Request, the next broker counts it down from there
+                        let ilp = match expiry.map(|expiry| expiry.saturating_duration_since(Instant::now())) {
+                            Some(remaining) if remaining == Duration::from_millis(0) => {
+                                trace!("\t\t|  |  |  |  EXPIRED REQUEST DROPPED");
+                                decision.action = Action::DroppedExpired;
+                                Router::decide(label, ops, decisions, counters, decision);
+                                return Ok(());
+                            },
+                            Some(remaining) => ilp.change_narrow_waist(ilp.narrow_waist().with_lifetime(remaining)),
+                            None => ilp,
+                        };
+                        let mut link_weights = bayes.classify(&hbfi);
+                        // links the producer announced itself on outrank the
+                        // rest, the nearer the producer the more so, and a
+                        // neighbour which probably has the Response cached is
+                        // nearer still than the producer
+                        let routes = announcements.lock().unwrap().routes(&hbfi, now);
+                        let holders = digests.lock().unwrap().holders(&hbfi, now);
+                        if !routes.is_empty() || !holders.is_empty() {
+                            for lw in link_weights.iter_mut() {
+                                if let Some((_, distance)) = routes.iter().find(|(linkid, _)| *linkid == lw.linkid) {
+                                    lw.weight += constants::ANNOUNCE_WEIGHT / (*distance).max(1) as f64;
                                 }
-                                if let Some(that_bloom) = blooms.get_mut(&that_link) {
-                                    if that_bloom.contains_pending_request(hbfi.clone()) {
-                                        continue;
-                                    }
-                                    if (weight < 0.00) && (forwarded == false) {
-                                        that_bloom.create_forwarded_request(hbfi.clone());
-                                        r2b_tx.send(ilp.change_destination(that_link))?;
-                                        continue;
-                                    }
-                                    that_bloom.create_forwarded_request(hbfi.clone());
-                                    r2b_tx.send(ilp.change_destination(that_link))?;
-                                    forwarded = true;
+                                if holders.contains(&lw.linkid) {
+                                    lw.weight += constants::DIGEST_WEIGHT;
                                 }
                             }
+                            link_weights.sort_by(|a, b| b.weight.total_cmp(&a.weight));
+                        }
+                        decision.candidates = link_weights
+                            .iter()
+                            .map(|lw| Candidate { linkid: lw.linkid.clone(), weight: lw.weight, skipped: None })
+                            .collect();
+                        bayes.train(&hbfi, choke);
+                        // a link replaying requests raises the Defcon level as
+                        // much as the choke link outranking the real links does
+                        let mut defcon: Option<Defcon> = None;
+                        if link_weights[0].linkid == *choke {
+                            //warn!("{}, {:?}", link_weights[0].weight, link_weights[0].linkid);
+                            defcon = Some(Defcon::from_litmus((link_weights[0].weight * 100.00) as u64));
+                        }
+                        let litmus = replays.lock().unwrap().litmus(&this_link);
+                        if litmus > 0 {
+                            let replay_defcon = Router::replay_defcon(litmus);
+                            defcon = Some(defcon.map_or(replay_defcon, |defcon| defcon.min(replay_defcon)));
+                        }
+                        if let Some(defcon) = defcon {
+                            trace!("\t\t|  |  |  |  {} ON {:?}", defcon, this_link);
+                            if !mitigator.lock().unwrap().mitigate(defcon, &nw, &this_link, bayes, label, ops, now) {
+                                decision.action = Action::Mitigated(defcon);
+                                Router::decide(label, ops, decisions, counters, decision);
+                                return Ok(())
+                            }
+                        }
+                        trace!("\t\t|  |  |  |  FORWARD REQUEST UPSTREAM");
+                        ops.forward_request_upstream(label.clone());
+                        pit.create_pending_request(&hbfi, &this_link, now, lifetime);
+                        let mut forwarded = false;
+                        let mut forwarded_to = vec![];
+                        for (i, LinkWeight { linkid: that_link, weight}) in link_weights.into_iter().enumerate() {
+                            //warn!("{}, {:?}", weight, that_link);
+                            if that_link == *choke {
+                                decision.candidates[i].skipped = Some(Skipped::Choke);
+                                continue;
+                            }
+                            if that_link.link_pid()? == this_link.link_pid()? {
+                                decision.candidates[i].skipped = Some(Skipped::SameLink);
+                                continue;
+                            }
+                            if !filter.lock().unwrap().carries(&hbfi, &that_link) {
+                                decision.candidates[i].skipped = Some(Skipped::Filtered);
+                                continue;
+                            }
+                            if !role.forwards(&upstream.lock().unwrap(), &this_link, &that_link) {
+                                decision.candidates[i].skipped = Some(Skipped::Role);
+                                continue;
+                            }
+                            if pit.contains_pending_request(&hbfi, &that_link, now) {
+                                decision.candidates[i].skipped = Some(Skipped::Pending);
+                                continue;
+                            }
+                            forwarded_to.push(that_link.clone());
+                            if (weight < 0.00) && (forwarded == false) {
+                                pit.create_forwarded_request(&hbfi, &that_link);
+                                r2b_tx.send(ilp.change_destination(that_link))?;
+                                continue;
+                            }
+                            pit.create_forwarded_request(&hbfi, &that_link);
+                            r2b_tx.send(ilp.change_destination(that_link))?;
+                            forwarded = true;
                         }
+                        if !forwarded_to.is_empty() {
+                            decision.action = Action::Forwarded(forwarded_to);
+                        }
+                        Router::decide(label, ops, decisions, counters, decision);
                     }
                 }
-                NarrowWaistPacket::Response { hbfi, .. } => {
-                    if this_bloom.contains_forwarded_request(hbfi.clone()) {
+            }
+            NarrowWaistPacket::Response { hbfi, .. } => {
+                if pit.contains_forwarded_request(&hbfi, &this_link, now) {
+                    let (admitted, cached) = {
+                        let filter = filter.lock().unwrap();
+                        (filter.admits(&hbfi), filter.caches(&hbfi))
+                    };
+                    if !admitted {
+                        trace!("\t\t|  |  |  |  RESPONSE FILTERED");
+                        return Ok(());
+                    }
+                    if cached && role.caches() {
                         rs.insert(nw);
-                        bayes.super_train(&hbfi, &this_link);
-                        for (that_link, that_bloom) in blooms.iter_mut() {
+                    }
+                    bayes.super_train(&hbfi, &this_link);
+                    if let Some(pending) = pit.satisfy(&hbfi) {
+                        for that_link in pending.downstream {
+                            // prefetched, so only cached
+                            if that_link == *choke {
+                                continue;
+                            }
                             if that_link.link_pid()? == this_link.link_pid()? {
                                 continue;
                             }
-                            if that_bloom.contains_pending_request(hbfi.clone()) {
-                                trace!("\t\t|  |  |  |  FORWARD RESPONSE DOWNSTREAM");
-                                ops.forward_response_downstream(label.clone());
-                                r2b_tx.send(ilp.change_destination(that_link.clone()))?;
+                            if !filter.lock().unwrap().carries(&hbfi, &that_link) {
+                                continue;
                             }
+                            trace!("\t\t|  |  |  |  FORWARD RESPONSE DOWNSTREAM");
+                            ops.forward_response_downstream(label.clone());
+                            r2b_tx.send(ilp.change_destination(that_link))?;
                         }
                     }
                 }
             }
+            // A Nack stands in for the Response, so it goes back the same
+            // way and satisfies the pending Request.
+            NarrowWaistPacket::Nack { hbfi, reason, .. } => {
+                if !pit.contains_forwarded_request(&hbfi, &this_link, now) {
+                    return Ok(());
+                }
+                match nw.verify() {
+                    Ok(true) => {},
+                    _ => {
+                        warn!("Nack with a bad signature dropped: {:?} on {:?}", hbfi, this_link);
+                        counters.lock().unwrap().decode_failure();
+                        return Ok(());
+                    },
+                }
+                if !filter.lock().unwrap().admits(&hbfi) {
+                    trace!("\t\t|  |  |  |  NACK FILTERED");
+                    return Ok(());
+                }
+                if role.caches() {
+                    nacks.insert(nw, now);
+                }
+                if let Some(prefetcher) = prefetcher.filter(|_| reason == NackReason::UnknownName) {
+                    prefetcher.lock().unwrap().nacked(&hbfi);
+                }
+                if let Some(pending) = pit.satisfy(&hbfi) {
+                    for that_link in pending.downstream {
+                        if that_link == *choke {
+                            continue;
+                        }
+                        if that_link.link_pid()? == this_link.link_pid()? {
+                            continue;
+                        }
+                        if !filter.lock().unwrap().carries(&hbfi, &that_link) {
+                            continue;
+                        }
+                        trace!("\t\t|  |  |  |  FORWARD NACK DOWNSTREAM");
+                        r2b_tx.send(ilp.change_destination(that_link))?;
+                    }
+                }
+            }
+            NarrowWaistPacket::Announce { hbfi, nonce, .. } => {
+                match nw.verify() {
+                    Ok(true) => {},
+                    _ => {
+                        warn!("Announce with a bad signature dropped: {:?} on {:?}", hbfi, this_link);
+                        counters.lock().unwrap().decode_failure();
+                        return Ok(());
+                    },
+                }
+                // no routes toward producers the broker won't carry
+                if !filter.lock().unwrap().admits(&hbfi) {
+                    trace!("\t\t|  |  |  |  ANNOUNCE FILTERED");
+                    return Ok(());
+                }
+                let nw = match nw.increment_distance() {
+                    Some(nw) => nw,
+                    None => {
+                        trace!("\t\t|  |  |  |  ANNOUNCE DISTANCE REACHED");
+                        return Ok(());
+                    }
+                };
+                let distance = nw.distance().unwrap_or(constants::ANNOUNCE_MAX_DISTANCE);
+                if !announcements.lock().unwrap().learn(&hbfi, &nonce, &this_link, distance, now) {
+                    return Ok(());
+                }
+                let ilp = ilp.change_narrow_waist(nw);
+                for that_link in bayes.linkids() {
+                    if that_link == *choke {
+                        continue;
+                    }
+                    if that_link.link_pid()? == this_link.link_pid()? {
+                        continue;
+                    }
+                    trace!("\t\t|  |  |  |  FORWARD ANNOUNCE");
+                    r2b_tx.send(ilp.change_destination(that_link))?;
+                }
+            }
+            NarrowWaistPacket::Digest { hbfi, digest, .. } => {
+                match nw.verify() {
+                    Ok(true) => {},
+                    _ => {
+                        warn!("Cache digest with a bad signature dropped: {:?} on {:?}", hbfi, this_link);
+                        counters.lock().unwrap().decode_failure();
+                        return Ok(());
+                    },
+                }
+                if !digests.lock().unwrap().learn(&this_link, &hbfi.response_pid, digest, now) {
+                    warn!("Cache digest not signed by the neighbour dropped: {:?} on {:?}", hbfi, this_link);
+                    return Ok(());
+                }
+                trace!("\t\t|  |  |  |  CACHE DIGEST FROM {:?}", this_link);
+            }
+            NarrowWaistPacket::Cancel { hbfi, .. } => {
+                if let Some(pending) = pit.cancel(&hbfi, &this_link) {
+                    trace!("\t\t|  |  |  |  REQUEST CANCELLED");
+                    for that_link in pending.upstream {
+                        if that_link == *choke {
+                            continue;
+                        }
+                        trace!("\t\t|  |  |  |  FORWARD CANCEL UPSTREAM");
+                        r2b_tx.send(ilp.change_destination(that_link))?;
+                    }
+                }
+            }
+            NarrowWaistPacket::Credit { hbfi, requests, responses, .. } => {
+                match nw.verify() {
+                    Ok(true) => {},
+                    _ => {
+                        warn!("Credit grant with a bad signature dropped: {:?} on {:?}", hbfi, this_link);
+                        counters.lock().unwrap().decode_failure();
+                        return Ok(());
+                    },
+                }
+                if !credits.lock().unwrap().grant(&this_link, &hbfi.response_pid, requests, responses) {
+                    warn!("Credit grant not signed by the neighbour dropped: {:?} on {:?}", hbfi, this_link);
+                    return Ok(());
+                }
+                trace!("\t\t|  |  |  |  CREDIT FROM {:?}", this_link);
+            }
         }
         Ok::<(), anyhow::Error>(())
     }
+    // Requests the frames a sequential reader will want next from the best
+    // link Bayes knows of, as far as the prefetch budget allows.
+    fn prefetch(
+        prefetcher: &mut Prefetcher,
+        hbfi: &HBFI,
+        this_link: &LinkId,
+        r2b_tx: Sender<InterLinkPacket>,
+        rs: &ResponseStore,
+        pit: &mut PendingTable,
+        bayes: &mut Bayes,
+        choke: &LinkId,
+        now: Instant,
+    ) -> Result<()> {
+        for hbfi in prefetcher.observe(hbfi, now) {
+            if rs.contains(&hbfi) || pit.get(&hbfi).is_some() {
+                continue;
+            }
+            let that_link = bayes.classify(&hbfi)
+                .into_iter()
+                .map(|lw| lw.linkid)
+                .find(|linkid| linkid != choke && linkid.link_pid().ok() != this_link.link_pid().ok());
+            let that_link = match that_link {
+                Some(that_link) => that_link,
+                None => break,
+            };
+            if !prefetcher.admit(now) {
+                trace!("\t\t|  |  |  |  PREFETCH BUDGET SPENT");
+                break;
+            }
+            trace!("\t\t|  |  |  |  PREFETCH REQUEST UPSTREAM");
+            let nw = NarrowWaistPacket::request(hbfi.clone(), RequestOptions::default())?;
+            let lp = LinkPacket::new(that_link.reply_to()?, nw);
+            // the choke link stands in for the broker itself downstream
+            pit.create_pending_request(&hbfi, choke, now, None);
+            pit.create_forwarded_request(&hbfi, &that_link);
+            r2b_tx.send(InterLinkPacket::new(that_link, lp))?;
+        }
+        Ok(())
+    }
+    // A link which replayed Requests in the current window is rate limited
+    // at Defcon 3 at least, however few of its Requests were replays.
+    fn replay_defcon(litmus: u64) -> Defcon {
+        Defcon::from_litmus(litmus).min(Defcon::Three)
+    }
+    fn decide(label: &ArrayString<{constants::LABEL_SIZE}>, ops: &Operations, decisions: &Mutex<Decisions>, counters: &Mutex<Counters>, decision: Decision) {
+        ops.decision(label.clone(), &decision);
+        counters.lock().unwrap().request(&decision.action);
+        decisions.lock().unwrap().record(decision);
+    }
 }
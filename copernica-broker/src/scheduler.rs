@@ -0,0 +1,270 @@
+use {
+    crate::qos::{DropPolicy, QosClass},
+    copernica_packets::{InterLinkPacket, LinkId},
+    copernica_common::constants::QOS_QUEUE_DEPTH,
+    std::collections::{HashMap, HashSet, VecDeque},
+};
+// What became of a packet handed to a link.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sent {
+    Sent,
+    // the link's channel is full, try again later
+    Full,
+    // the link can't take this kind of packet yet, the ones behind it may go
+    Held,
+    // the link has gone away
+    Gone,
+}
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QueueStats {
+    pub depth: [usize; 3],
+    pub enqueued: u64,
+    pub sent: u64,
+    pub dropped: u64,
+}
+impl QueueStats {
+    pub fn depth(&self, class: QosClass) -> usize {
+        self.depth[class.index()]
+    }
+    pub fn total_depth(&self) -> usize {
+        self.depth.iter().sum()
+    }
+}
+#[derive(Clone, Default)]
+struct Queues {
+    classes: [VecDeque<InterLinkPacket>; 3],
+    stats: QueueStats,
+}
+// Queues the router's packets per link and class. Links take turns and each
+// turn sends up to a class's weight of packets, highest class first, so no
+// link or class is starved and a full link never holds up the others. A full
+// queue drops a packet according to the drop policy instead of blocking.
+#[derive(Clone)]
+pub struct Scheduler {
+    depth: usize,
+    drop: DropPolicy,
+    queues: HashMap<LinkId, Queues>,
+    order: VecDeque<LinkId>,
+}
+impl Scheduler {
+    pub fn new() -> Self {
+        Self {
+            depth: QOS_QUEUE_DEPTH,
+            drop: DropPolicy::Newest,
+            queues: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+    pub fn set_policy(&mut self, depth: usize, drop: DropPolicy) {
+        self.depth = depth;
+        self.drop = drop;
+    }
+    pub fn is_empty(&self) -> bool {
+        self.queues
+            .values()
+            .all(|queues| queues.classes.iter().all(|queue| queue.is_empty()))
+    }
+    // Returns false if a packet had to be dropped.
+    pub fn enqueue(&mut self, ilp: InterLinkPacket, class: QosClass) -> bool {
+        let link_id = ilp.link_id();
+        if !self.queues.contains_key(&link_id) {
+            self.order.push_back(link_id.clone());
+        }
+        let queues = self.queues.entry(link_id).or_default();
+        let queue = &mut queues.classes[class.index()];
+        queues.stats.enqueued += 1;
+        if queue.len() >= self.depth {
+            queues.stats.dropped += 1;
+            match self.drop {
+                DropPolicy::Newest => return false,
+                DropPolicy::Oldest => {
+                    if queue.pop_front().is_none() {
+                        return false
+                    }
+                },
+            }
+            queue.push_back(ilp);
+            return false
+        }
+        queue.push_back(ilp);
+        true
+    }
+    // Hands packets to `send` until every link is empty or full, returning
+    // how many were sent.
+    pub fn drain<F: FnMut(&InterLinkPacket) -> Sent>(&mut self, mut send: F) -> usize {
+        let mut sent = 0;
+        let mut full: HashSet<LinkId> = HashSet::new();
+        loop {
+            let mut progress = 0;
+            for link_id in self.order.iter() {
+                if full.contains(link_id) {
+                    continue;
+                }
+                let queues = match self.queues.get_mut(link_id) {
+                    Some(queues) => queues,
+                    None => continue,
+                };
+                'classes: for class in QosClass::ALL.iter() {
+                    let queue = &mut queues.classes[class.index()];
+                    // held packets stay where they are, in order
+                    let mut next = 0;
+                    let mut turn = 0;
+                    while turn < class.weight() {
+                        let outcome = match queue.get(next) {
+                            Some(ilp) => send(ilp),
+                            None => break,
+                        };
+                        match outcome {
+                            Sent::Sent => {
+                                queue.remove(next);
+                                queues.stats.sent += 1;
+                                sent += 1;
+                                progress += 1;
+                                turn += 1;
+                            },
+                            Sent::Gone => {
+                                queue.remove(next);
+                                queues.stats.dropped += 1;
+                                progress += 1;
+                                turn += 1;
+                            },
+                            Sent::Held => next += 1,
+                            Sent::Full => {
+                                full.insert(link_id.clone());
+                                break 'classes;
+                            },
+                        }
+                    }
+                }
+            }
+            if progress == 0 {
+                break
+            }
+        }
+        // the next drain starts with the next link
+        if let Some(link_id) = self.order.pop_front() {
+            self.order.push_back(link_id);
+        }
+        sent
+    }
+    // Drops whatever is queued for a link that has gone away.
+    pub fn remove_link(&mut self, link_id: &LinkId) {
+        let lookup_id = link_id.lookup_id().ok();
+        self.queues.retain(|other, _| other.lookup_id().ok() != lookup_id);
+        self.order.retain(|other| other.lookup_id().ok() != lookup_id);
+    }
+    pub fn stats(&self) -> Vec<(LinkId, QueueStats)> {
+        self.order
+            .iter()
+            .filter_map(|link_id| self.queues.get(link_id).map(|queues| {
+                let mut stats = queues.stats.clone();
+                for class in QosClass::ALL.iter() {
+                    stats.depth[class.index()] = queues.classes[class.index()].len();
+                }
+                (link_id.clone(), stats)
+            }))
+            .collect()
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use copernica_packets::{HBFI, LinkPacket, NarrowWaistPacket, PrivateIdentityInterface, PublicIdentityInterface, ReplyTo, RequestOptions};
+
+    fn ilp(link_id: &LinkId, frm: u64) -> InterLinkPacket {
+        let response_sid = PrivateIdentityInterface::new_key();
+        let hbfi = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "app", "m0d", "fun", "arg").unwrap();
+        let nw = NarrowWaistPacket::request(hbfi.offset(frm), RequestOptions::default()).unwrap();
+        InterLinkPacket::new(link_id.clone(), LinkPacket::new(ReplyTo::Mpsc, nw))
+    }
+    fn link() -> LinkId {
+        LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::Absent, ReplyTo::Mpsc)
+    }
+    #[test]
+    fn test_classes_are_weighted_and_full_links_dont_block() {
+        let link0 = link();
+        let link1 = link();
+        let mut scheduler = Scheduler::new();
+        for frm in 0..4 {
+            scheduler.enqueue(ilp(&link0, frm), QosClass::Background);
+            scheduler.enqueue(ilp(&link0, frm), QosClass::Interactive);
+        }
+        scheduler.enqueue(ilp(&link1, 0), QosClass::Bulk);
+        let mut link0_sent = vec![];
+        let mut link1_sent = 0;
+        // link0 takes 5 packets before it's full, link1 still gets its turn
+        let sent = scheduler.drain(|ilp| {
+            if ilp.link_id() == link1 {
+                link1_sent += 1;
+                return Sent::Sent
+            }
+            if link0_sent.len() == 5 {
+                return Sent::Full
+            }
+            link0_sent.push(ilp.narrow_waist().hbfi().frm);
+            Sent::Sent
+        });
+        assert_eq!(sent, 6);
+        assert_eq!(link1_sent, 1);
+        // 4 interactive then 1 background in the first round
+        assert_eq!(link0_sent, vec![0, 1, 2, 3, 0]);
+        let stats = scheduler.stats();
+        let (_, link0_stats) = stats.iter().find(|(link_id, _)| *link_id == link0).unwrap();
+        assert_eq!(link0_stats.depth(QosClass::Background), 3);
+        assert_eq!(link0_stats.total_depth(), 3);
+    }
+    #[test]
+    fn test_held_packets_dont_block_the_rest() {
+        let link0 = link();
+        let mut scheduler = Scheduler::new();
+        for frm in 0..4 {
+            scheduler.enqueue(ilp(&link0, frm), QosClass::Bulk);
+        }
+        // frames 0 and 2 are held, as if their kind ran out of credit
+        let mut frms = vec![];
+        let sent = scheduler.drain(|ilp| {
+            let frm = ilp.narrow_waist().hbfi().frm;
+            if frm % 2 == 0 {
+                return Sent::Held
+            }
+            frms.push(frm);
+            Sent::Sent
+        });
+        assert_eq!(sent, 2);
+        assert_eq!(frms, vec![1, 3]);
+        let mut frms = vec![];
+        scheduler.drain(|ilp| {
+            frms.push(ilp.narrow_waist().hbfi().frm);
+            Sent::Sent
+        });
+        assert_eq!(frms, vec![0, 2]);
+        assert!(scheduler.is_empty());
+    }
+    #[test]
+    fn test_full_queue_drops_by_policy() {
+        let link0 = link();
+        let mut scheduler = Scheduler::new();
+        scheduler.set_policy(2, DropPolicy::Oldest);
+        for frm in 0..3 {
+            scheduler.enqueue(ilp(&link0, frm), QosClass::Bulk);
+        }
+        let mut frms = vec![];
+        scheduler.drain(|ilp| {
+            frms.push(ilp.narrow_waist().hbfi().frm);
+            Sent::Sent
+        });
+        assert_eq!(frms, vec![1, 2]);
+        scheduler.set_policy(2, DropPolicy::Newest);
+        for frm in 0..3 {
+            scheduler.enqueue(ilp(&link0, frm), QosClass::Bulk);
+        }
+        let mut frms = vec![];
+        scheduler.drain(|ilp| {
+            frms.push(ilp.narrow_waist().hbfi().frm);
+            Sent::Sent
+        });
+        assert_eq!(frms, vec![0, 1]);
+        assert_eq!(scheduler.stats()[0].1.dropped, 2);
+        assert!(scheduler.is_empty());
+    }
+}
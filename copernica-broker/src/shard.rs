@@ -0,0 +1,97 @@
+use {
+    crate::{
+        response_store::{ResponseStore, CacheStats},
+        pending_table::{PendingTable},
+        snapshot::{Snapshot},
+        Bayes,
+    },
+    copernica_packets::{LinkId, HBFI, HBFIOnlyKeys},
+    std::{
+        collections::{hash_map::DefaultHasher, HashSet},
+        hash::{Hash, Hasher},
+        sync::{Arc, Mutex},
+    },
+};
+// A router thread's share of the broker's state. Packets go to a shard by a
+// hash of their HBFI's requester and producer keys, so every packet for a
+// name is handled by the same thread in the order it arrived. Whoever holds
+// more than one of its locks takes them in the order rs, bayes, pit.
+#[derive(Clone)]
+pub struct Shard {
+    pub rs: Arc<Mutex<ResponseStore>>,
+    pub bayes: Arc<Mutex<Bayes>>,
+    pub pit: Arc<Mutex<PendingTable>>,
+}
+impl Shard {
+    pub fn new(rs: ResponseStore, bayes: Bayes) -> Self {
+        Self {
+            rs: Arc::new(Mutex::new(rs)),
+            bayes: Arc::new(Mutex::new(bayes)),
+            pit: Arc::new(Mutex::new(PendingTable::new())),
+        }
+    }
+    pub fn of(hbfi: &HBFI, shards: usize) -> usize {
+        let mut hasher = DefaultHasher::new();
+        HBFIOnlyKeys(hbfi.clone()).hash(&mut hasher);
+        (hasher.finish() % shards.max(1) as u64) as usize
+    }
+    // The content store stats of every shard added together.
+    pub fn cache_stats(shards: &[Shard]) -> CacheStats {
+        let mut total = shards[0].rs.lock().unwrap().stats();
+        for shard in &shards[1..] {
+            let stats = shard.rs.lock().unwrap().stats();
+            total.capacity += stats.capacity;
+            total.bytes += stats.bytes;
+            total.entries += stats.entries;
+            total.hits += stats.hits;
+            total.misses += stats.misses;
+            total.insertions += stats.insertions;
+            total.evictions += stats.evictions;
+            total.rejections += stats.rejections;
+        }
+        total
+    }
+    pub fn snapshot(shards: &[Shard]) -> Snapshot {
+        let snapshots = shards
+            .iter()
+            .map(|shard| {
+                let rs = shard.rs.lock().unwrap();
+                let bayes = shard.bayes.lock().unwrap();
+                Snapshot::take(&bayes, &rs)
+            })
+            .collect();
+        Snapshot::merge(snapshots)
+    }
+    pub fn restore(shards: &[Shard], snapshot: &Snapshot, links: &HashSet<LinkId>) {
+        for (index, shard) in shards.iter().enumerate() {
+            let mut rs = shard.rs.lock().unwrap();
+            let mut bayes = shard.bayes.lock().unwrap();
+            snapshot.shard(index, shards.len()).restore(links, &mut bayes, &mut rs);
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use copernica_packets::{NarrowWaistPacket, PrivateIdentityInterface, PublicIdentityInterface};
+
+    #[test]
+    fn test_names_keep_to_their_shard() {
+        let response_sid = PrivateIdentityInterface::new_key();
+        let hbfi = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "app", "m0d", "fun", "arg").unwrap();
+        let shard = Shard::of(&hbfi, 4);
+        for frm in 0..16 {
+            assert_eq!(Shard::of(&hbfi.clone().offset(frm), 4), shard);
+        }
+        assert_eq!(Shard::of(&hbfi, 1), 0);
+        let shards: Vec<Shard> = (0..4).map(|_| Shard::new(ResponseStore::default(), Bayes::new())).collect();
+        shards[shard].rs.lock().unwrap().insert(NarrowWaistPacket::response(response_sid, hbfi.clone(), b"pong".to_vec()).unwrap());
+        let snapshot = Shard::snapshot(&shards);
+        let restored: Vec<Shard> = (0..4).map(|_| Shard::new(ResponseStore::default(), Bayes::new())).collect();
+        Shard::restore(&restored, &snapshot, &HashSet::new());
+        for (index, shard) in restored.iter().enumerate() {
+            assert_eq!(shard.rs.lock().unwrap().contains(&hbfi), index == Shard::of(&hbfi, 4));
+        }
+        assert_eq!(Shard::cache_stats(&restored).entries, 1);
+    }
+}
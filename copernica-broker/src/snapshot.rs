@@ -0,0 +1,265 @@
+use {
+    crate::{
+        response_store::{ResponseStore},
+        shard::{Shard},
+        Bayes,
+    },
+    copernica_packets::{LinkId, HBFI, NarrowWaistPacket, PublicIdentity},
+    copernica_common::{
+        constants::{ID_SIZE, CC_SIZE, SNAPSHOT_VERSION},
+        u16_to_u8, u8_to_u16, u64_to_u8, u8_to_u64,
+    },
+    anyhow::{anyhow, Result},
+    std::{
+        collections::{HashMap, HashSet},
+        fs,
+        path::Path,
+    },
+};
+const SNAPSHOT_MAGIC: &[u8; 4] = b"COPB";
+const CHOKE_TAG: u8 = 0;
+const LINK_TAG: u8 = 1;
+// Links are recorded by the public identity of their link_sid, which is the
+// only part of a LinkId that survives a restart. None is the choke link.
+type LinkKey = Option<PublicIdentity>;
+// What a broker has learnt about the network: the Bayes link and BFI counts
+// and the cached Responses. Pending Requests are short lived and aren't kept.
+//
+// magic | version u16 | link count u64 | (link, count)*
+//       | bfi count u64 | (hbfi len u16, hbfi, link count u64, (link, count)*)*
+//       | response count u64 | (response len u16, response)*
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub links: Vec<(LinkKey, f64)>,
+    pub bfis: Vec<(HBFI, Vec<(LinkKey, f64)>)>,
+    pub responses: Vec<NarrowWaistPacket>,
+}
+impl Snapshot {
+    pub fn take(bayes: &Bayes, rs: &ResponseStore) -> Self {
+        let links = bayes.link_counts()
+            .into_iter()
+            .map(|(link_id, count)| (link_id.link_pid().ok(), count))
+            .collect();
+        let bfis = bayes.bfi_counts()
+            .into_iter()
+            .map(|(hbfi, counts)| {
+                let counts = counts
+                    .into_iter()
+                    .map(|(link_id, count)| (link_id.link_pid().ok(), count))
+                    .collect();
+                (hbfi, counts)
+            })
+            .collect();
+        let responses = rs.iter().cloned().collect();
+        Self { links, bfis, responses }
+    }
+    // Counts of links which aren't peered with the broker any more are dropped.
+    pub fn restore(&self, links: &HashSet<LinkId>, bayes: &mut Bayes, rs: &mut ResponseStore) {
+        let mut by_pid: HashMap<LinkKey, LinkId> = HashMap::new();
+        by_pid.insert(None, LinkId::choke());
+        for link_id in links {
+            if let Ok(link_pid) = link_id.link_pid() {
+                by_pid.insert(Some(link_pid), link_id.clone());
+            }
+        }
+        for (key, count) in &self.links {
+            if let Some(link_id) = by_pid.get(key) {
+                bayes.restore_link(link_id, *count);
+            }
+        }
+        for (hbfi, counts) in &self.bfis {
+            for (key, count) in counts {
+                if let Some(link_id) = by_pid.get(key) {
+                    bayes.restore_bfi(hbfi, link_id, *count);
+                }
+            }
+        }
+        for nw in &self.responses {
+            rs.insert(nw.clone());
+        }
+    }
+    // One snapshot of every router thread's share, link counts are summed.
+    pub fn merge(snapshots: Vec<Snapshot>) -> Self {
+        let mut links: Vec<(LinkKey, f64)> = vec![];
+        let mut bfis = vec![];
+        let mut responses = vec![];
+        for snapshot in snapshots {
+            for (key, count) in snapshot.links {
+                match links.iter_mut().find(|(k, _)| *k == key) {
+                    Some((_, total)) => *total += count,
+                    None => links.push((key, count)),
+                }
+            }
+            bfis.extend(snapshot.bfis);
+            responses.extend(snapshot.responses);
+        }
+        Self { links, bfis, responses }
+    }
+    // The part of the snapshot belonging to one of `shards` router threads,
+    // each is given an even share of the link counts.
+    pub fn shard(&self, index: usize, shards: usize) -> Self {
+        let links = self.links
+            .iter()
+            .map(|(key, count)| (key.clone(), count / shards.max(1) as f64))
+            .collect();
+        let bfis = self.bfis
+            .iter()
+            .filter(|(hbfi, _)| Shard::of(hbfi, shards) == index)
+            .cloned()
+            .collect();
+        let responses = self.responses
+            .iter()
+            .filter(|nw| Shard::of(&nw.hbfi(), shards) == index)
+            .cloned()
+            .collect();
+        Self { links, bfis, responses }
+    }
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut buf: Vec<u8> = vec![];
+        buf.extend_from_slice(SNAPSHOT_MAGIC);
+        buf.extend_from_slice(&u16_to_u8(SNAPSHOT_VERSION));
+        buf.extend_from_slice(&u64_to_u8(self.links.len() as u64));
+        for (key, count) in &self.links {
+            write_count(&mut buf, key, *count);
+        }
+        buf.extend_from_slice(&u64_to_u8(self.bfis.len() as u64));
+        for (hbfi, counts) in &self.bfis {
+            let hbfi = hbfi.as_bytes();
+            buf.extend_from_slice(&u16_to_u8(hbfi.len() as u16));
+            buf.extend_from_slice(&hbfi);
+            buf.extend_from_slice(&u64_to_u8(counts.len() as u64));
+            for (key, count) in counts {
+                write_count(&mut buf, key, *count);
+            }
+        }
+        buf.extend_from_slice(&u64_to_u8(self.responses.len() as u64));
+        for nw in &self.responses {
+            let nw = nw.as_bytes();
+            buf.extend_from_slice(&u16_to_u8(nw.len() as u16));
+            buf.extend_from_slice(&nw);
+        }
+        buf
+    }
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut reader = Reader { data, at: 0 };
+        if reader.take(SNAPSHOT_MAGIC.len())? != SNAPSHOT_MAGIC {
+            return Err(anyhow!("Not a broker snapshot"))
+        }
+        let version = reader.u16()?;
+        if version != SNAPSHOT_VERSION {
+            return Err(anyhow!("Broker snapshot version {} is unsupported, expected {}", version, SNAPSHOT_VERSION))
+        }
+        let mut links = vec![];
+        for _ in 0..reader.u64()? {
+            links.push(reader.count()?);
+        }
+        let mut bfis = vec![];
+        for _ in 0..reader.u64()? {
+            let len = reader.u16()? as usize;
+            let hbfi = HBFI::from_bytes(reader.take(len)?)?;
+            let mut counts = vec![];
+            for _ in 0..reader.u64()? {
+                counts.push(reader.count()?);
+            }
+            bfis.push((hbfi, counts));
+        }
+        let mut responses = vec![];
+        for _ in 0..reader.u64()? {
+            let len = reader.u16()? as usize;
+            responses.push(NarrowWaistPacket::from_bytes(reader.take(len)?)?);
+        }
+        Ok(Self { links, bfis, responses })
+    }
+    // Writes to a temporary file first so a crash mid write leaves the last snapshot intact.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, self.as_bytes())?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+}
+fn write_count(buf: &mut Vec<u8>, key: &LinkKey, count: f64) {
+    match key {
+        None => buf.push(CHOKE_TAG),
+        Some(link_pid) => {
+            buf.push(LINK_TAG);
+            buf.extend_from_slice(link_pid.key().as_ref());
+            buf.extend_from_slice(link_pid.chain_code().as_ref());
+        },
+    }
+    buf.extend_from_slice(&u64_to_u8(count.to_bits()));
+}
+struct Reader<'a> {
+    data: &'a [u8],
+    at: usize,
+}
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.at + len > self.data.len() {
+            return Err(anyhow!("Broker snapshot is truncated"))
+        }
+        let bytes = &self.data[self.at..self.at + len];
+        self.at += len;
+        Ok(bytes)
+    }
+    fn u16(&mut self) -> Result<u16> {
+        let mut bytes = [0u8; 2];
+        bytes.clone_from_slice(self.take(2)?);
+        Ok(u8_to_u16(bytes))
+    }
+    fn u64(&mut self) -> Result<u64> {
+        let mut bytes = [0u8; 8];
+        bytes.clone_from_slice(self.take(8)?);
+        Ok(u8_to_u64(bytes))
+    }
+    fn count(&mut self) -> Result<(LinkKey, f64)> {
+        let key = match self.take(1)?[0] {
+            CHOKE_TAG => None,
+            LINK_TAG => {
+                let mut link_pid = [0u8; ID_SIZE + CC_SIZE];
+                link_pid.clone_from_slice(self.take(ID_SIZE + CC_SIZE)?);
+                Some(PublicIdentity::from(link_pid))
+            },
+            tag => return Err(anyhow!("Unrecognised link tag {} in broker snapshot", tag)),
+        };
+        Ok((key, f64::from_bits(self.u64()?)))
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use copernica_packets::{PrivateIdentityInterface, PublicIdentityInterface, ReplyTo};
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let response_sid = PrivateIdentityInterface::new_key();
+        let hbfi = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "app", "m0d", "fun", "arg").unwrap();
+        let link_sid = PrivateIdentityInterface::new_key();
+        let link0 = LinkId::link_with_type(link_sid.clone(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+        let mut bayes = Bayes::new();
+        bayes.add_link(&link0);
+        bayes.super_train(&hbfi, &link0);
+        bayes.train(&hbfi, &LinkId::choke());
+        let mut rs = ResponseStore::default();
+        rs.insert(NarrowWaistPacket::response(response_sid, hbfi.clone(), b"pong".to_vec()).unwrap());
+        let bytes = Snapshot::take(&bayes, &rs).as_bytes();
+        let snapshot = Snapshot::from_bytes(&bytes).unwrap();
+        // the same link after a restart has a new lookup id but the same link_sid
+        let link1 = LinkId::link_with_type(link_sid, PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+        let mut links = HashSet::new();
+        links.insert(link1.clone());
+        let mut restored = Bayes::new();
+        let mut restored_rs = ResponseStore::default();
+        snapshot.restore(&links, &mut restored, &mut restored_rs);
+        let mut counts = restored.link_counts();
+        counts.sort_by(|a, b| a.1.total_cmp(&b.1));
+        assert_eq!(counts.iter().map(|(link_id, _)| link_id.clone()).collect::<Vec<LinkId>>(), vec![LinkId::choke(), link1.clone()]);
+        assert!((counts[1].1 - 5.0).abs() < 0.01);
+        assert_eq!(restored.classify(&hbfi)[0].linkid, link1);
+        assert!(restored_rs.get(&hbfi).is_some());
+        assert!(Snapshot::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
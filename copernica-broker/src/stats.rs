@@ -0,0 +1,214 @@
+use {
+    crate::{
+        explain::{Action},
+        credits::{Credits, CreditStats},
+        response_store::{CacheStats},
+        scheduler::{Scheduler, QueueStats},
+        shard::{Shard},
+    },
+    copernica_packets::{LinkId, DecodeFailures},
+    copernica_common::constants::LINK_IDLE,
+    std::{
+        collections::HashMap,
+        sync::Mutex,
+        time::{Duration, Instant},
+    },
+};
+// How many Requests met each end in the router.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RequestCounts {
+    pub forwarded: u64,
+    pub unroutable: u64,
+    pub found_in_cache: u64,
+    // answered with a producer's recent Nack
+    pub found_nack: u64,
+    pub duplicate: u64,
+    pub replay: u64,
+    pub hop_limit: u64,
+    // the requester's lifetime had run out
+    pub expired: u64,
+    pub rate_limited: u64,
+    pub filtered: u64,
+    // dropped by the Defcon mitigations
+    pub mitigated: u64,
+}
+impl RequestCounts {
+    pub fn count(&mut self, action: &Action) {
+        let counter = match action {
+            Action::Forwarded(_) => &mut self.forwarded,
+            Action::Unroutable => &mut self.unroutable,
+            Action::FoundInCache => &mut self.found_in_cache,
+            Action::FoundNack => &mut self.found_nack,
+            Action::DroppedDuplicate => &mut self.duplicate,
+            Action::DroppedReplay => &mut self.replay,
+            Action::DroppedHopLimit => &mut self.hop_limit,
+            Action::DroppedExpired => &mut self.expired,
+            Action::RateLimited => &mut self.rate_limited,
+            Action::Filtered => &mut self.filtered,
+            Action::Mitigated(_) => &mut self.mitigated,
+        };
+        *counter += 1;
+    }
+    // Every Request the router has decided on, whichever way.
+    pub fn total(&self) -> u64 {
+        self.forwarded + self.unroutable + self.found_in_cache + self.found_nack
+            + self.duplicate + self.replay + self.hop_limit + self.expired
+            + self.rate_limited + self.filtered + self.mitigated
+    }
+}
+// What the router threads count as they go, shared between them.
+#[derive(Clone, Debug, Default)]
+pub struct Counters {
+    received: HashMap<LinkId, (u64, Option<Instant>)>,
+    // counted by the links themselves
+    link_decode_failures: HashMap<LinkId, DecodeFailures>,
+    requests: RequestCounts,
+    decode_failures: u64,
+    router_errors: u64,
+    // per router thread
+    pending: HashMap<usize, usize>,
+}
+impl Counters {
+    // Hands back what the link counts the packets it couldn't decode into.
+    pub fn peer(&mut self, link_id: &LinkId) -> DecodeFailures {
+        self.received.entry(link_id.clone()).or_insert((0, None));
+        self.link_decode_failures.entry(link_id.clone()).or_default().clone()
+    }
+    pub fn unpeer(&mut self, link_id: &LinkId) {
+        let lookup_id = link_id.lookup_id().ok();
+        self.received.retain(|other, _| other.lookup_id().ok() != lookup_id);
+        self.link_decode_failures.retain(|other, _| other.lookup_id().ok() != lookup_id);
+    }
+    pub fn received(&mut self, link_id: &LinkId, now: Instant) {
+        let (received, last) = self.received.entry(link_id.clone()).or_insert((0, None));
+        *received += 1;
+        *last = Some(now);
+    }
+    pub fn request(&mut self, action: &Action) {
+        self.requests.count(action);
+    }
+    // A packet that couldn't be verified or made sense of.
+    pub fn decode_failure(&mut self) {
+        self.decode_failures += 1;
+    }
+    // A packet the router failed to handle, it couldn't send it on say.
+    pub fn router_error(&mut self) {
+        self.router_errors += 1;
+    }
+    pub fn pending(&mut self, shard: usize, len: usize) {
+        self.pending.insert(shard, len);
+    }
+}
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinkStats {
+    pub link_id: LinkId,
+    pub received: u64,
+    pub last_received: Option<Instant>,
+    // sent and dropped on the way out, and what's still queued
+    pub queue: QueueStats,
+    // what the peer lets this broker send it
+    pub credits: CreditStats,
+}
+impl LinkStats {
+    // A link is up while packets keep arriving on it.
+    pub fn is_up(&self, now: Instant) -> bool {
+        match self.last_received {
+            Some(last) => now.saturating_duration_since(last) < Duration::from_millis(LINK_IDLE),
+            None => false,
+        }
+    }
+}
+#[derive(Clone, Debug, PartialEq)]
+pub struct BrokerStats {
+    pub links: Vec<LinkStats>,
+    pub cache: CacheStats,
+    pub pending: usize,
+    pub requests: RequestCounts,
+    // the router's and those of the links it's peered with
+    pub decode_failures: u64,
+    pub router_errors: u64,
+    pub bayes_bfis: usize,
+}
+impl BrokerStats {
+    pub fn collect(shards: &[Shard], scheduler: &Mutex<Scheduler>, counters: &Mutex<Counters>, credits: &Mutex<Credits>) -> Self {
+        let cache = Shard::cache_stats(shards);
+        let bayes_bfis = shards.iter().map(|shard| shard.bayes.lock().unwrap().bfis_len()).sum();
+        let mut queues: HashMap<LinkId, QueueStats> = scheduler.lock().unwrap().stats().into_iter().collect();
+        let counters = counters.lock().unwrap();
+        let mut links: Vec<LinkStats> = counters.received
+            .iter()
+            .map(|(link_id, (received, last_received))| LinkStats {
+                link_id: link_id.clone(),
+                received: *received,
+                last_received: *last_received,
+                queue: queues.remove(link_id).unwrap_or_default(),
+                credits: CreditStats::default(),
+            })
+            .collect();
+        links.extend(queues.into_iter().map(|(link_id, queue)| LinkStats { link_id, received: 0, last_received: None, queue, credits: CreditStats::default() }));
+        let credits = credits.lock().unwrap();
+        for link in &mut links {
+            link.credits = credits.stats(&link.link_id);
+        }
+        links.sort_by_key(|link| link.link_id.lookup_id().ok());
+        let link_decode_failures: u64 = counters.link_decode_failures.values().map(|failures| failures.count()).sum();
+        Self {
+            links,
+            cache,
+            pending: counters.pending.values().sum(),
+            requests: counters.requests.clone(),
+            decode_failures: counters.decode_failures + link_decode_failures,
+            router_errors: counters.router_errors,
+            bayes_bfis,
+        }
+    }
+    pub fn link(&self, link_id: &LinkId) -> Option<&LinkStats> {
+        self.links.iter().find(|link| link.link_id == *link_id)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Bayes, response_store::ResponseStore};
+    use copernica_packets::{PrivateIdentityInterface, PublicIdentityInterface, ReplyTo};
+
+    #[test]
+    fn test_collect() {
+        let link0 = LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+        let shards = vec![Shard::new(ResponseStore::default(), Bayes::new())];
+        let scheduler = Mutex::new(Scheduler::new());
+        let counters = Mutex::new(Counters::default());
+        let link0_decode_failures = counters.lock().unwrap().peer(&link0);
+        let credits = Mutex::new(Credits::new());
+        credits.lock().unwrap().grant(&link0, &PrivateIdentityInterface::new_key().public_id(), 4, 8);
+        {
+            let mut counters = counters.lock().unwrap();
+            counters.received(&link0, Instant::now());
+            counters.received(&link0, Instant::now());
+            counters.request(&Action::Forwarded(vec![link0.clone()]));
+            counters.request(&Action::Mitigated(crate::Defcon::from_litmus(100)));
+            counters.decode_failure();
+            counters.router_error();
+            counters.pending(0, 3);
+            counters.pending(1, 2);
+            counters.pending(0, 1);
+        }
+        // link0 couldn't decode a packet either
+        link0_decode_failures.failed();
+        let stats = BrokerStats::collect(&shards, &scheduler, &counters, &credits);
+        assert_eq!(stats.link(&link0).map(|link| link.received), Some(2));
+        assert_eq!(stats.link(&link0).and_then(|link| link.credits.requests), Some(4));
+        assert!(stats.links[0].is_up(Instant::now()));
+        assert!(!stats.links[0].is_up(Instant::now() + Duration::from_millis(LINK_IDLE)));
+        assert_eq!(stats.requests.forwarded, 1);
+        assert_eq!(stats.requests.mitigated, 1);
+        assert_eq!(stats.decode_failures, 2);
+        assert_eq!(stats.router_errors, 1);
+        assert_eq!(stats.pending, 3);
+        assert_eq!(stats.cache.entries, 0);
+        // another broker peered with the same link counts its own failures
+        let other = Mutex::new(Counters::default());
+        other.lock().unwrap().peer(&link0);
+        assert_eq!(BrokerStats::collect(&shards, &scheduler, &other, &credits).decode_failures, 0);
+    }
+}
@@ -0,0 +1,51 @@
+use {
+    std::time::Instant,
+};
+// Tokens refill continuously at `rate` per second up to `burst`, each
+// admitted packet takes one token.
+#[derive(Clone, Debug)]
+pub struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    last: Instant,
+}
+impl TokenBucket {
+    pub fn new(rate: u32, burst: u32, now: Instant) -> Self {
+        Self {
+            rate: rate as f64,
+            burst: burst as f64,
+            tokens: burst as f64,
+            last: now,
+        }
+    }
+    pub fn set_rate(&mut self, rate: u32, burst: u32) {
+        self.rate = rate as f64;
+        self.burst = burst as f64;
+        self.tokens = self.tokens.min(self.burst);
+    }
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+    pub fn burst(&self) -> f64 {
+        self.burst
+    }
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        self.last = now;
+    }
+    pub fn tokens(&mut self, now: Instant) -> f64 {
+        self.refill(now);
+        self.tokens
+    }
+    pub fn try_take(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
@@ -1,8 +1,9 @@
-pub const FRAGMENT_SIZE: usize= 1024;
+pub const FRAGMENT_SIZE: usize= 1008;
 pub const REED_SOLOMON_DE_EN_CODER_SIZE: usize = 10;
 // the above two constants give us a maximum packet size of the largest packet (Cyphertext Link + Cyphertext NarrowWaistPacket)
 // of 1469 bytes. 1472 is the maximum we can go (see https://gafferongames.com/post/packet_fragmentation_and_reassembly/)
-// thus we have about 5 bytes of error correction capability and still have a nice roundish number of 1024 of data.
+// thus we have about 5 bytes of error correction capability. The fragment was 1024 bytes until Responses carried a
+// freshness period and version, the 16 bytes those take came out of the data.
 
 pub const DATA_SIZE_START: usize = FRAGMENT_SIZE-2;
 pub const DATA_SIZE_END: usize = FRAGMENT_SIZE-1;
@@ -19,6 +20,16 @@ pub const BFI_BYTE_SIZE: usize = BLOOM_FILTER_INDEX_ELEMENT_LENGTH * 2;
 pub const BFI_COUNT: usize = 6; // RES, REQ, APP, MOD, FUN, ARG
 pub const U64_SIZE: usize = 8;
 pub const FRAME_SIZE: usize = U64_SIZE;
+pub const HOP_LIMIT_SIZE: usize = 1;
+pub const REQUEST_HOP_LIMIT: u8 = 32; // the number of brokers a Request may traverse before it's dropped
+pub const LIFETIME_SIZE: usize = U64_SIZE; // in milliseconds left to wait, u64::MAX for none
+pub const DISTANCE_SIZE: usize = 1;
+pub const ANNOUNCE_MAX_DISTANCE: u8 = 16; // the number of brokers an Announce may traverse from its producer
+pub const CACHE_DIGEST_SIZE: usize = 512; // in bytes, a Bloom filter of 4096 bits
+pub const NACK_REASON_SIZE: usize = 1;
+pub const FRESHNESS_SIZE: usize = 1;
+pub const FRESH_FOR_SIZE: usize = U64_SIZE; // in milliseconds after a Response is cached, 0 for forever
+pub const VERSION_SIZE: usize = U64_SIZE;
 
 pub const REPLY_TO_INDEX_SIZE: usize = 2; //65535 different types of addressing should provide sufficient expansion
 pub const REPLY_TO_MPSC_INDEX: u16 = 0;
@@ -42,6 +53,14 @@ pub const CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_INDEX: u8 = 0;
 pub const CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_INDEX: u8 = 1;
 pub const CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_INDEX: u8 = 2;
 pub const CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_INDEX: u8 = 3;
+pub const SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_INDEX: u8 = 4;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_ANNOUNCE_INDEX: u8 = 5;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_DIGEST_INDEX: u8 = 6;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_INDEX: u8 = 7;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_CANCEL_INDEX: u8 = 8;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_CANCEL_INDEX: u8 = 9;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_NACK_INDEX: u8 = 10;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_NACK_INDEX: u8 = 11;
 
 pub const CYPHERTEXT_LINK_TX_PK_START: usize = 0;
 pub const CYPHERTEXT_LINK_TX_PK_END: usize = CYPHERTEXT_LINK_TX_PK_START + ID_SIZE + CC_SIZE;
@@ -65,33 +84,123 @@ pub const CYPHERTEXT_RESPONSE_DATA_SIZE: usize = FRAGMENT_SIZE + TAG_SIZE;
 pub const CLEARTEXT_HBFI_SIZE: usize = ((BLOOM_FILTER_INDEX_ELEMENT_LENGTH * 2) * BFI_COUNT) + FRAME_SIZE + ID_SIZE + CC_SIZE;
 pub const CLEARTEXT_RESPONSE_DATA_SIZE: usize = FRAGMENT_SIZE;
 
-pub const CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_NONCE_START: usize =   1;
-pub const CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_NONCE_END: usize =     CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_NONCE_START   + NONCE_SIZE;
-pub const CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_HBFI_START: usize =    CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_NONCE_END;
-pub const CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_HBFI_END: usize =      CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_NONCE_END     + CLEARTEXT_HBFI_SIZE;
-
-pub const CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_NONCE_START: usize =  1;
-pub const CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_NONCE_END: usize =    CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_NONCE_START  + NONCE_SIZE;
-pub const CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_HBFI_START: usize =   CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_NONCE_END;
-pub const CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_HBFI_END: usize =     CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_NONCE_END    + CYPHERTEXT_HBFI_SIZE;
-
-pub const CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_SIG_START: usize =    1;
-pub const CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_SIG_END: usize =      CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_SIG_START    + SIG_SIZE;
-pub const CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_NONCE_START: usize =  CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_SIG_END;
-pub const CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_NONCE_END: usize =    CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_SIG_END      + NONCE_SIZE;
-pub const CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_HBFI_START: usize =   CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_NONCE_END;
-pub const CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_HBFI_END: usize =     CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_HBFI_START   + CLEARTEXT_HBFI_SIZE;
-pub const CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_DATA_START: usize =   CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_HBFI_END;
-pub const CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_DATA_END: usize =     CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_DATA_START   + CLEARTEXT_RESPONSE_DATA_SIZE;
-
-pub const CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_SIG_START: usize =   1;
-pub const CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_SIG_END: usize =     CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_SIG_START   + SIG_SIZE;
-pub const CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_NONCE_START: usize = CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_SIG_END;
-pub const CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_NONCE_END: usize =   CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_NONCE_START + NONCE_SIZE;
-pub const CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_HBFI_START: usize =  CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_NONCE_END;
-pub const CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_HBFI_END: usize =    CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_HBFI_START  + CYPHERTEXT_HBFI_SIZE;
-pub const CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_DATA_START: usize =  CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_HBFI_END;
-pub const CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_DATA_END: usize =    CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_DATA_START  + CYPHERTEXT_RESPONSE_DATA_SIZE;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_NONCE_START: usize =     1;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_NONCE_END: usize =       CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_NONCE_START     + NONCE_SIZE;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_HOP_LIMIT_START: usize = CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_NONCE_END;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_HOP_LIMIT_END: usize =   CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_HOP_LIMIT_START + HOP_LIMIT_SIZE;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_LIFETIME_START: usize =  CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_HOP_LIMIT_END;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_LIFETIME_END: usize =    CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_LIFETIME_START  + LIFETIME_SIZE;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_FRESHNESS_START: usize = CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_LIFETIME_END;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_FRESHNESS_END: usize =   CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_FRESHNESS_START + FRESHNESS_SIZE;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_HBFI_START: usize =      CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_FRESHNESS_END;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_HBFI_END: usize =        CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_HBFI_START      + CLEARTEXT_HBFI_SIZE;
+
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_NONCE_START: usize =     1;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_NONCE_END: usize =       CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_NONCE_START     + NONCE_SIZE;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_HOP_LIMIT_START: usize = CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_NONCE_END;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_HOP_LIMIT_END: usize =   CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_HOP_LIMIT_START + HOP_LIMIT_SIZE;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_LIFETIME_START: usize =  CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_HOP_LIMIT_END;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_LIFETIME_END: usize =    CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_LIFETIME_START  + LIFETIME_SIZE;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_FRESHNESS_START: usize = CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_LIFETIME_END;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_FRESHNESS_END: usize =   CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_FRESHNESS_START + FRESHNESS_SIZE;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_HBFI_START: usize =      CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_FRESHNESS_END;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_HBFI_END: usize =        CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_HBFI_START      + CYPHERTEXT_HBFI_SIZE;
+
+pub const SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_SIG_START: usize =       1;
+pub const SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_SIG_END: usize =         SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_SIG_START       + SIG_SIZE;
+pub const SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_NONCE_START: usize =     SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_SIG_END;
+pub const SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_NONCE_END: usize =       SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_NONCE_START     + NONCE_SIZE;
+pub const SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_HOP_LIMIT_START: usize = SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_NONCE_END;
+pub const SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_HOP_LIMIT_END: usize =   SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_HOP_LIMIT_START + HOP_LIMIT_SIZE;
+pub const SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_LIFETIME_START: usize =  SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_HOP_LIMIT_END;
+pub const SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_LIFETIME_END: usize =    SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_LIFETIME_START  + LIFETIME_SIZE;
+pub const SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_FRESHNESS_START: usize = SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_LIFETIME_END;
+pub const SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_FRESHNESS_END: usize =   SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_FRESHNESS_START + FRESHNESS_SIZE;
+pub const SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_HBFI_START: usize =      SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_FRESHNESS_END;
+pub const SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_HBFI_END: usize =        SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_HBFI_START      + CYPHERTEXT_HBFI_SIZE;
+
+pub const CLEARTEXT_NARROW_WAIST_PACKET_ANNOUNCE_SIG_START: usize =      1;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_ANNOUNCE_SIG_END: usize =        CLEARTEXT_NARROW_WAIST_PACKET_ANNOUNCE_SIG_START      + SIG_SIZE;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_ANNOUNCE_NONCE_START: usize =    CLEARTEXT_NARROW_WAIST_PACKET_ANNOUNCE_SIG_END;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_ANNOUNCE_NONCE_END: usize =      CLEARTEXT_NARROW_WAIST_PACKET_ANNOUNCE_NONCE_START    + NONCE_SIZE;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_ANNOUNCE_DISTANCE_START: usize = CLEARTEXT_NARROW_WAIST_PACKET_ANNOUNCE_NONCE_END;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_ANNOUNCE_DISTANCE_END: usize =   CLEARTEXT_NARROW_WAIST_PACKET_ANNOUNCE_DISTANCE_START + DISTANCE_SIZE;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_ANNOUNCE_HBFI_START: usize =     CLEARTEXT_NARROW_WAIST_PACKET_ANNOUNCE_DISTANCE_END;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_ANNOUNCE_HBFI_END: usize =       CLEARTEXT_NARROW_WAIST_PACKET_ANNOUNCE_HBFI_START     + CLEARTEXT_HBFI_SIZE;
+
+pub const CLEARTEXT_NARROW_WAIST_PACKET_DIGEST_SIG_START: usize =    1;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_DIGEST_SIG_END: usize =      CLEARTEXT_NARROW_WAIST_PACKET_DIGEST_SIG_START    + SIG_SIZE;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_DIGEST_NONCE_START: usize =  CLEARTEXT_NARROW_WAIST_PACKET_DIGEST_SIG_END;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_DIGEST_NONCE_END: usize =    CLEARTEXT_NARROW_WAIST_PACKET_DIGEST_NONCE_START  + NONCE_SIZE;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_DIGEST_HBFI_START: usize =   CLEARTEXT_NARROW_WAIST_PACKET_DIGEST_NONCE_END;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_DIGEST_HBFI_END: usize =     CLEARTEXT_NARROW_WAIST_PACKET_DIGEST_HBFI_START   + CLEARTEXT_HBFI_SIZE;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_DIGEST_DATA_START: usize =   CLEARTEXT_NARROW_WAIST_PACKET_DIGEST_HBFI_END;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_DIGEST_DATA_END: usize =     CLEARTEXT_NARROW_WAIST_PACKET_DIGEST_DATA_START   + CACHE_DIGEST_SIZE;
+
+pub const CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_SIG_START: usize =       1;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_SIG_END: usize =         CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_SIG_START       + SIG_SIZE;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_NONCE_START: usize =     CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_SIG_END;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_NONCE_END: usize =       CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_NONCE_START     + NONCE_SIZE;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_HBFI_START: usize =      CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_NONCE_END;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_HBFI_END: usize =        CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_HBFI_START      + CLEARTEXT_HBFI_SIZE;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_REQUESTS_START: usize =  CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_HBFI_END;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_REQUESTS_END: usize =    CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_REQUESTS_START  + U64_SIZE;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_RESPONSES_START: usize = CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_REQUESTS_END;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_RESPONSES_END: usize =   CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_RESPONSES_START + U64_SIZE;
+
+pub const CLEARTEXT_NARROW_WAIST_PACKET_CANCEL_NONCE_START: usize =  1;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_CANCEL_NONCE_END: usize =    CLEARTEXT_NARROW_WAIST_PACKET_CANCEL_NONCE_START  + NONCE_SIZE;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_CANCEL_HBFI_START: usize =   CLEARTEXT_NARROW_WAIST_PACKET_CANCEL_NONCE_END;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_CANCEL_HBFI_END: usize =     CLEARTEXT_NARROW_WAIST_PACKET_CANCEL_HBFI_START   + CLEARTEXT_HBFI_SIZE;
+
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_CANCEL_NONCE_START: usize = 1;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_CANCEL_NONCE_END: usize =   CYPHERTEXT_NARROW_WAIST_PACKET_CANCEL_NONCE_START + NONCE_SIZE;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_CANCEL_HBFI_START: usize =  CYPHERTEXT_NARROW_WAIST_PACKET_CANCEL_NONCE_END;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_CANCEL_HBFI_END: usize =    CYPHERTEXT_NARROW_WAIST_PACKET_CANCEL_HBFI_START  + CYPHERTEXT_HBFI_SIZE;
+
+pub const CLEARTEXT_NARROW_WAIST_PACKET_NACK_SIG_START: usize =     1;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_NACK_SIG_END: usize =       CLEARTEXT_NARROW_WAIST_PACKET_NACK_SIG_START     + SIG_SIZE;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_NACK_NONCE_START: usize =   CLEARTEXT_NARROW_WAIST_PACKET_NACK_SIG_END;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_NACK_NONCE_END: usize =     CLEARTEXT_NARROW_WAIST_PACKET_NACK_NONCE_START   + NONCE_SIZE;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_NACK_HBFI_START: usize =    CLEARTEXT_NARROW_WAIST_PACKET_NACK_NONCE_END;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_NACK_HBFI_END: usize =      CLEARTEXT_NARROW_WAIST_PACKET_NACK_HBFI_START    + CLEARTEXT_HBFI_SIZE;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_NACK_REASON_START: usize =  CLEARTEXT_NARROW_WAIST_PACKET_NACK_HBFI_END;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_NACK_REASON_END: usize =    CLEARTEXT_NARROW_WAIST_PACKET_NACK_REASON_START  + NACK_REASON_SIZE;
+
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_NACK_SIG_START: usize =    1;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_NACK_SIG_END: usize =      CYPHERTEXT_NARROW_WAIST_PACKET_NACK_SIG_START    + SIG_SIZE;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_NACK_NONCE_START: usize =  CYPHERTEXT_NARROW_WAIST_PACKET_NACK_SIG_END;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_NACK_NONCE_END: usize =    CYPHERTEXT_NARROW_WAIST_PACKET_NACK_NONCE_START  + NONCE_SIZE;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_NACK_HBFI_START: usize =   CYPHERTEXT_NARROW_WAIST_PACKET_NACK_NONCE_END;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_NACK_HBFI_END: usize =     CYPHERTEXT_NARROW_WAIST_PACKET_NACK_HBFI_START   + CYPHERTEXT_HBFI_SIZE;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_NACK_REASON_START: usize = CYPHERTEXT_NARROW_WAIST_PACKET_NACK_HBFI_END;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_NACK_REASON_END: usize =   CYPHERTEXT_NARROW_WAIST_PACKET_NACK_REASON_START + NACK_REASON_SIZE;
+
+pub const CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_SIG_START: usize =       1;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_SIG_END: usize =         CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_SIG_START       + SIG_SIZE;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_NONCE_START: usize =     CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_SIG_END;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_NONCE_END: usize =       CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_SIG_END         + NONCE_SIZE;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_HBFI_START: usize =      CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_NONCE_END;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_HBFI_END: usize =        CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_HBFI_START      + CLEARTEXT_HBFI_SIZE;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_FRESH_FOR_START: usize = CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_HBFI_END;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_FRESH_FOR_END: usize =   CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_FRESH_FOR_START + FRESH_FOR_SIZE;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_VERSION_START: usize =   CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_FRESH_FOR_END;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_VERSION_END: usize =     CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_VERSION_START   + VERSION_SIZE;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_DATA_START: usize =      CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_VERSION_END;
+pub const CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_DATA_END: usize =        CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_DATA_START      + CLEARTEXT_RESPONSE_DATA_SIZE;
+
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_SIG_START: usize =       1;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_SIG_END: usize =         CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_SIG_START       + SIG_SIZE;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_NONCE_START: usize =     CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_SIG_END;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_NONCE_END: usize =       CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_NONCE_START     + NONCE_SIZE;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_HBFI_START: usize =      CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_NONCE_END;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_HBFI_END: usize =        CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_HBFI_START      + CYPHERTEXT_HBFI_SIZE;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_FRESH_FOR_START: usize = CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_HBFI_END;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_FRESH_FOR_END: usize =   CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_FRESH_FOR_START + FRESH_FOR_SIZE;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_VERSION_START: usize =   CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_FRESH_FOR_END;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_VERSION_END: usize =     CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_VERSION_START   + VERSION_SIZE;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_DATA_START: usize =      CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_VERSION_END;
+pub const CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_DATA_END: usize =        CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_DATA_START      + CYPHERTEXT_RESPONSE_DATA_SIZE;
 
 pub const HBFI_FRAME_START: usize = BFI_BYTE_SIZE * BFI_COUNT;
 pub const HBFI_FRAME_END: usize = HBFI_FRAME_START + FRAME_SIZE;
@@ -102,6 +211,43 @@ pub const HBFI_REQUEST_KEY_END: usize = HBFI_REQUEST_KEY_START + ID_SIZE + CC_SI
 
 pub const BOUNDED_BUFFER_SIZE: usize = 25;
 pub const RESPONSE_STORE_SIZE: usize = 250;
-pub const PACKET_PHEROMONE_TRAIL_COUNT: usize = 500;
+pub const RESPONSE_STORE_CAPACITY: usize = RESPONSE_STORE_SIZE * CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_DATA_END; // in bytes, room for RESPONSE_STORE_SIZE of the largest Response
+pub const EDGE_RESPONSE_STORE_CAPACITY: usize = 4 * RESPONSE_STORE_CAPACITY; // in bytes, edge brokers cache aggressively
+pub const TINY_LFU_SKETCH_WIDTH: usize = 1024;
+pub const TINY_LFU_SKETCH_DEPTH: usize = 4;
+pub const PENDING_TABLE_SIZE: usize = 500;
+pub const PENDING_REQUEST_LIFETIME: u64 = 4000; // in milliseconds
+pub const PENDING_TABLE_SWEEP_INTERVAL: u64 = 100; // in milliseconds
+pub const REPLAY_CACHE_SIZE: usize = 4096;
+pub const REPLAY_WINDOW: u64 = 10000; // in milliseconds
+pub const BAYES_PENALTY_FACTOR: f64 = 0.1;
+pub const BAYES_HALF_LIFE: u64 = 600000; // in milliseconds
+pub const BAYES_BFIS_SIZE: usize = 10000;
+pub const BAYES_FORGET_THRESHOLD: f64 = 0.01;
+pub const DECISION_LOG_SIZE: usize = 1000;
+pub const NACK_LIFETIME: u64 = 1000; // in milliseconds, how long a broker answers Requests with a producer's Nack
+pub const ANNOUNCE_LIFETIME: u64 = 60000; // in milliseconds
+pub const ANNOUNCE_WEIGHT: f64 = 1.0; // added to a link's Bayes weight, divided by the announced distance
+pub const CACHE_DIGEST_INTERVAL: u64 = 1000; // in milliseconds
+pub const CACHE_DIGEST_ENTRIES: usize = 512; // the most recently used Responses digested, more and the 4096 bit filter passes 2% false positives
+pub const DIGEST_WEIGHT: f64 = 2.0; // added to a link's Bayes weight when its neighbour's digest holds the HBFI
+pub const CACHE_DIGEST_LIFETIME: u64 = 5000; // in milliseconds, a neighbour's digest is ignored once this old
+pub const PREFETCH_DEPTH: u64 = 4; // frames requested ahead of a sequential reader
+pub const PREFETCH_POPULARITY: u32 = 2; // Requests for an object before it's prefetched
+pub const PREFETCH_BANDWIDTH: u32 = 65536; // in bytes per second of prefetched Responses
+pub const PREFETCH_STREAMS: usize = 1024;
+pub const PREFETCH_IDLE: u64 = 30000; // in milliseconds, an object's stream is forgotten once idle this long
+pub const QOS_QUEUE_DEPTH: usize = 64; // packets queued per link per class
+pub const QOS_INTERACTIVE_WEIGHT: usize = 4;
+pub const QOS_BULK_WEIGHT: usize = 2;
+pub const QOS_BACKGROUND_WEIGHT: usize = 1;
+pub const LINK_IDLE: u64 = 30000; // in milliseconds, a link is reported down once nothing has arrived on it for this long
+pub const LINK_POLL_INTERVAL: u64 = 500; // in milliseconds, how often an idle link checks whether it was unpeered
+pub const FLOW_CONTROL_REQUEST_CREDITS: u64 = 64; // Requests a peer may send before it's granted more
+pub const FLOW_CONTROL_RESPONSE_CREDITS: u64 = 256; // Responses a peer may send before it's granted more
+pub const FLOW_CONTROL_GRANT_INTERVAL: u64 = 500; // in milliseconds, how often credits are granted even if none were used
+pub const SNAPSHOT_VERSION: u16 = 3;
+pub const SNAPSHOT_INTERVAL: u64 = 60000; // in milliseconds
 
 pub const LABEL_SIZE: usize = 100;
+pub const EXPLANATION_SIZE: usize = 1024;
@@ -1,8 +1,8 @@
 use {
     crossbeam_channel::{Sender},
-    core::fmt::{self},
+    core::fmt::{self, Write},
     arrayvec::ArrayString,
-    crate::constants::LABEL_SIZE,
+    crate::constants::{LABEL_SIZE, EXPLANATION_SIZE},
 };
 #[derive(Clone, Debug)]
 pub enum Operations {
@@ -127,6 +127,29 @@ impl Operations {
             Operations::Off => {}
         }
     }
+    pub fn alert(&self, label: ArrayString<LABEL_SIZE>, defcon: u8) {
+        match self {
+            Operations::On { tx, .. } => {
+                match tx.send(LogEntry::alert(&label, defcon)) {
+                    Ok(_) => {},
+                    Err(_) => {},
+                }
+            },
+            Operations::Off => {}
+        }
+    }
+    // Explains a routing decision, only formatted when turned on.
+    pub fn decision(&self, label: ArrayString<LABEL_SIZE>, explanation: &dyn fmt::Display) {
+        match self {
+            Operations::On { tx, .. } => {
+                match tx.send(LogEntry::decision(&label, explanation)) {
+                    Ok(_) => {},
+                    Err(_) => {},
+                }
+            },
+            Operations::Off => {}
+        }
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
@@ -138,6 +161,8 @@ pub enum LogEntry {
     ResponseArrivedDownstream(ArrayString<LABEL_SIZE>),
     ForwardResponseDownstream(ArrayString<LABEL_SIZE>),
     ForwardRequestUpstream(ArrayString<LABEL_SIZE>),
+    Alert(ArrayString<LABEL_SIZE>),
+    Decision(ArrayString<EXPLANATION_SIZE>),
 }
 impl LogEntry {
     pub fn end() -> Self {
@@ -185,6 +210,23 @@ impl LogEntry {
         label.push_str(".clone()),");
         LogEntry::ForwardResponseDownstream(label)
     }
+    pub fn alert(l: &str, defcon: u8) -> Self {
+        let mut label = ArrayString::<LABEL_SIZE>::new();
+        label.push_str("expected_behaviour.insert(LogEntry::alert(");
+        label.push_str(&l);
+        label.push_str(".clone(), ");
+        label.push(char::from(b'0' + defcon % 10));
+        label.push_str("),");
+        LogEntry::Alert(label)
+    }
+    // Explanations longer than EXPLANATION_SIZE are cut short.
+    pub fn decision(l: &str, explanation: &dyn fmt::Display) -> Self {
+        let mut label = ArrayString::<EXPLANATION_SIZE>::new();
+        label.push_str(&l);
+        label.push_str(": ");
+        let _ = write!(label, "{}", explanation);
+        LogEntry::Decision(label)
+    }
 }
 impl fmt::Display for LogEntry {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -196,6 +238,8 @@ impl fmt::Display for LogEntry {
             LogEntry::ResponseArrivedDownstream(label) => { label },
             LogEntry::ForwardResponseDownstream(label) => { label },
             LogEntry::ForwardRequestUpstream(label) => { label },
+            LogEntry::Alert(label) => { label },
+            LogEntry::Decision(label) => { return write!(f, "{}", label) },
             LogEntry::End => {
                 label.push_str("end");
                 &label
@@ -17,7 +17,7 @@ pub use {
 };
 use {
     copernica_packets::{
-        InterLinkPacket, LinkId, LinkPacket, PublicIdentity,
+        InterLinkPacket, LinkId, LinkPacket, PublicIdentity, DecodeFailures,
     },
     copernica_common::{ Operations, constants::{REED_SOLOMON_DE_EN_CODER_SIZE, LABEL_SIZE} },
     crossbeam_channel::{Receiver, Sender},
@@ -26,7 +26,7 @@ use {
     arrayvec::ArrayString,
     //log::debug,
 };
-pub fn decode(msg: Vec<u8>, link_id: LinkId) -> Result<(PublicIdentity, LinkPacket)> {
+pub fn decode(msg: Vec<u8>, link_id: LinkId, decode_failures: &DecodeFailures) -> Result<(PublicIdentity, LinkPacket)> {
     let dec = Decoder::new(REED_SOLOMON_DE_EN_CODER_SIZE);
     let mut buffers: Vec<Buffer> = vec![];
     for chunk in msg.chunks(255) {
@@ -37,14 +37,20 @@ pub fn decode(msg: Vec<u8>, link_id: LinkId) -> Result<(PublicIdentity, LinkPack
         let buf = match dec.correct(&buffer, None) {
             Ok(b) => b,
             Err(e) => {
+                decode_failures.failed();
                 return Err(anyhow!("Packet corrupted beyond recovery, dropping it (error: {:?})", e));
             },
         };
         reconstituted.push(buf);
     }
     let reconstituted: Vec<u8> = reconstituted.iter().map(|d| d.data()).collect::<Vec<_>>().concat();
-    let (public_id0, lp0) = LinkPacket::from_bytes(&reconstituted, link_id.clone())?;
-    Ok((public_id0, lp0))
+    match LinkPacket::from_bytes(&reconstituted, link_id.clone()) {
+        Ok((public_id0, lp0)) => Ok((public_id0, lp0)),
+        Err(e) => {
+            decode_failures.failed();
+            Err(e)
+        },
+    }
 }
 pub fn encode(lp: LinkPacket, link_id: LinkId) -> Result<Vec<u8>> {
     let mut merged = vec![];
@@ -60,4 +66,7 @@ pub fn encode(lp: LinkPacket, link_id: LinkId) -> Result<Vec<u8>> {
 pub trait Link {
     fn run(&mut self) -> Result<()>;
     fn new(link: LinkId, ops: (ArrayString<LABEL_SIZE>, Operations), router_in_and_out: ( Sender<InterLinkPacket> , Receiver<InterLinkPacket>)) -> Result<Self> where Self: Sized;
+    // Counts the packets the link couldn't decode into the broker's handle
+    // from Broker::peer, call it before run.
+    fn count_decode_failures(&mut self, decode_failures: DecodeFailures);
 }
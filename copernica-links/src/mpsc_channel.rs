@@ -1,7 +1,7 @@
 use {
     crate::{Link, decode, encode},
     copernica_packets::{
-        InterLinkPacket, LinkId, ReplyTo
+        InterLinkPacket, LinkId, ReplyTo, DecodeFailures,
     },
     copernica_common::{ Operations, constants::{LABEL_SIZE, BOUNDED_BUFFER_SIZE} },
     anyhow::{anyhow, Result},
@@ -17,6 +17,7 @@ pub struct MpscChannel {
     // t = tansport; c = copernic; 0 = this instance of t; 1 = the pair of same type
     l2bs_tx: Sender<InterLinkPacket>,
     bs2l_rx: Receiver<InterLinkPacket>,
+    decode_failures: DecodeFailures,
     l2l0_tx: Sender<Vec<u8>>,        // give
     l2l0_rx: Receiver<Vec<u8>>,      // keep
     l2l1_tx: Option<Vec<Sender<Vec<u8>>>>,
@@ -50,6 +51,7 @@ impl Link for MpscChannel {
                         ops,
                         l2bs_tx,
                         bs2l_rx,
+                        decode_failures: DecodeFailures::new(),
                         l2l0_tx,
                         l2l0_rx,
                         l2l1_tx: None,
@@ -58,11 +60,15 @@ impl Link for MpscChannel {
             _ => return Err(anyhow!("MpscChannel Link expects a LinkId of type LinkId::Mpsc")),
         }
     }
+    fn count_decode_failures(&mut self, decode_failures: DecodeFailures) {
+        self.decode_failures = decode_failures;
+    }
     #[allow(unreachable_code)]
     fn run(&mut self) -> Result<()> {
         let this_link = self.link_id.clone();
         trace!("Started {:?}:", this_link);
         let l2l0_rx = self.l2l0_rx.clone();
+        let decode_failures = self.decode_failures.clone();
         let l2bs_tx = self.l2bs_tx.clone();
         let ops = self.ops.clone();
         let label = self.label.clone();
@@ -72,7 +78,7 @@ impl Link for MpscChannel {
                     loop {
                         match l2l0_rx.recv() {
                             Ok(msg) => {
-                                match decode(msg, this_link.clone()) {
+                                match decode(msg, this_link.clone(), &decode_failures) {
                                     Ok((_lnk_tx_pid, lp)) => {
                                         let link_id = LinkId::new(this_link.lookup_id()?, this_link.link_sid()?, this_link.remote_link_pid()?, lp.reply_to());
                                         let ilp = InterLinkPacket::new(link_id, lp.clone());
@@ -116,7 +122,8 @@ impl Link for MpscChannel {
                                 }
                             }
                         },
-                        Err(error) => error!("{:?}: {}", this_link, error),
+                        // the broker or protocol unpeered the link
+                        Err(_) => break,
                     }
                 }
                 Ok::<(), anyhow::Error>(())
@@ -1,14 +1,17 @@
 use {
     crate::{Link, encode, decode},
-    copernica_packets::{ InterLinkPacket, LinkId, ReplyTo },
+    copernica_packets::{ InterLinkPacket, LinkId, ReplyTo, DecodeFailures },
     copernica_common::{ Operations, constants },
     anyhow::{anyhow, Result},
     crossbeam_channel::{Receiver, Sender},
     futures_lite::{future},
+    async_io::{Timer},
     log::{error, trace},
     arrayvec::ArrayString,
     std::{
       net::{SocketAddr, UdpSocket},
+      sync::{Arc, atomic::{AtomicBool, Ordering}},
+      time::{Duration},
     },
 };
 #[allow(dead_code)]
@@ -18,6 +21,7 @@ pub struct UdpIpV4 {
     ops: Operations,
     l2bs_tx: Sender<InterLinkPacket>,
     bs2l_rx: Receiver<InterLinkPacket>,
+    decode_failures: DecodeFailures,
 }
 impl Link for UdpIpV4 {
     fn new(link_id: LinkId
@@ -28,27 +32,39 @@ impl Link for UdpIpV4 {
         trace!("LISTEN ON {:?}:", link_id);
         ops.register_link(label.clone());
         match link_id.reply_to()? {
-            ReplyTo::UdpIpV4(_) => return Ok(UdpIpV4 { label, link_id, ops, l2bs_tx, bs2l_rx }),
+            ReplyTo::UdpIpV4(_) => return Ok(UdpIpV4 { label, link_id, ops, l2bs_tx, bs2l_rx, decode_failures: DecodeFailures::new() }),
             _ => return Err(anyhow!("UdpIpV4 Link expects a LinkId of type Link.ReplyTo::UdpIpV4(...)")),
         }
     }
+    fn count_decode_failures(&mut self, decode_failures: DecodeFailures) {
+        self.decode_failures = decode_failures;
+    }
     #[allow(unreachable_code)]
     fn run(&mut self) -> Result<()> {
+        // cleared once the broker or protocol drops the link, freeing its socket
+        let running = Arc::new(AtomicBool::new(true));
+        let poll = Duration::from_millis(constants::LINK_POLL_INTERVAL);
         let this_link = self.link_id.clone();
         let l2bs_tx = self.l2bs_tx.clone();
+        let decode_failures = self.decode_failures.clone();
         let ops = self.ops.clone();
         let label = self.label.clone();
+        let receiving = running.clone();
         std::thread::spawn(move || {
             match this_link.reply_to()? {
                 ReplyTo::UdpIpV4(addr) => {
                     match async_io::Async::<UdpSocket>::bind(addr) {
                         Ok(socket) => {
-                            loop {
+                            while receiving.load(Ordering::SeqCst) {
                                 let mut buf = vec![0u8; 1500];
-                                let data = future::block_on(async{ socket.recv_from(&mut buf).await });
+                                let data = future::block_on(future::or(
+                                    async { socket.recv_from(&mut buf).await.map(Some) },
+                                    async { Timer::after(poll).await; Ok(None) },
+                                ));
                                 match data {
-                                    Ok((n, _peer)) => {
-                                        match decode(buf[..n].to_vec(), this_link.clone()) {
+                                    Ok(None) => {},
+                                    Ok(Some((n, _peer))) => {
+                                        match decode(buf[..n].to_vec(), this_link.clone(), &decode_failures) {
                                             Ok((_lnk_tx_pid, lp)) => {
                                                 trace!("\t\t\t|  |  link-to-broker-or-protocol");
                                                 trace!("\t\t\t|  |  {}", this_link.lookup_id()?);
@@ -101,7 +117,11 @@ impl Link for UdpIpV4 {
                                     _ => {},
                                 }
                             },
-                            Err(error) => error!("{:?}: {}", this_link, error),
+                            Err(_) => {
+                                trace!("\t\t\t|  |  {} unpeered", this_link.lookup_id()?);
+                                running.store(false, Ordering::SeqCst);
+                                break
+                            },
                         }
                     }
                 },
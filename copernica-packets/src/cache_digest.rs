@@ -0,0 +1,65 @@
+use {
+    crate::{HBFI},
+    copernica_common::constants::{CACHE_DIGEST_SIZE, BLOOM_FILTER_INDEX_ELEMENT_LENGTH},
+    anyhow::{anyhow, Result},
+    std::fmt,
+};
+const CACHE_DIGEST_BITS: u64 = (CACHE_DIGEST_SIZE * 8) as u64;
+// A Bloom filter of the HBFIs a broker holds in its ResponseStore. Each
+// element of the HBFI's BFIs is already a hash, so the n'th bit position is
+// the n'th element of every BFI folded together with the frame.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct CacheDigest(pub [u8; CACHE_DIGEST_SIZE]);
+impl CacheDigest {
+    pub fn new() -> Self {
+        Self([0; CACHE_DIGEST_SIZE])
+    }
+    fn positions(hbfi: &HBFI) -> [u64; BLOOM_FILTER_INDEX_ELEMENT_LENGTH] {
+        let mut positions = [0u64; BLOOM_FILTER_INDEX_ELEMENT_LENGTH];
+        for (n, position) in positions.iter_mut().enumerate() {
+            let mut h: u64 = hbfi.frm.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+            for bfi in [&hbfi.req, &hbfi.res, &hbfi.app, &hbfi.m0d, &hbfi.fun, &hbfi.arg].iter() {
+                h = (h.rotate_left(16) ^ bfi.0[n] as u64).wrapping_mul(0x0100_0000_01b3);
+            }
+            *position = h % CACHE_DIGEST_BITS;
+        }
+        positions
+    }
+    pub fn insert(&mut self, hbfi: &HBFI) {
+        for position in CacheDigest::positions(hbfi).iter() {
+            self.0[(*position / 8) as usize] |= 1 << (*position % 8);
+        }
+    }
+    // False positives are possible, false negatives aren't.
+    pub fn contains(&self, hbfi: &HBFI) -> bool {
+        CacheDigest::positions(hbfi)
+            .iter()
+            .all(|position| self.0[(*position / 8) as usize] & (1 << (*position % 8)) != 0)
+    }
+    // Holds the HBFIs of both digests.
+    pub fn union(&mut self, other: &CacheDigest) {
+        for (byte, other) in self.0.iter_mut().zip(other.0.iter()) {
+            *byte |= *other;
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|byte| *byte == 0)
+    }
+    pub fn as_bytes(&self) -> Vec<u8> {
+        self.0.to_vec()
+    }
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        if data.len() != CACHE_DIGEST_SIZE {
+            return Err(anyhow!("Length of data used to reconstruct a CacheDigest is unrecognised"))
+        }
+        let mut digest = [0u8; CACHE_DIGEST_SIZE];
+        digest.clone_from_slice(data);
+        Ok(Self(digest))
+    }
+}
+impl fmt::Debug for CacheDigest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let set: u32 = self.0.iter().map(|byte| byte.count_ones()).sum();
+        write!(f, "CacheDigest {} of {} bits set", set, CACHE_DIGEST_BITS)
+    }
+}
@@ -0,0 +1,19 @@
+use {
+    std::sync::{Arc, atomic::{AtomicU64, Ordering}},
+};
+// The packets a link received and couldn't decode. A broker hands one to
+// each link it peers with and reads it back, as a link has no other way to
+// tell it about a packet it dropped.
+#[derive(Clone, Debug, Default)]
+pub struct DecodeFailures(Arc<AtomicU64>);
+impl DecodeFailures {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn failed(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn count(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
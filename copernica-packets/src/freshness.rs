@@ -0,0 +1,45 @@
+use {
+    anyhow::{anyhow, Result},
+    std::fmt,
+};
+// How fresh a Response a Request will take from a broker's cache.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Freshness {
+    // any cached Response, a stale one only until the broker's sweep evicts it
+    Any,
+    // only a cached Response still within its freshness period
+    MustBeFresh,
+    // never a cached Response, the Request goes on to the producer
+    Latest,
+}
+impl Default for Freshness {
+    fn default() -> Self {
+        Freshness::Any
+    }
+}
+impl Freshness {
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            Freshness::Any => 0,
+            Freshness::MustBeFresh => 1,
+            Freshness::Latest => 2,
+        }
+    }
+    pub fn from_u8(freshness: u8) -> Result<Self> {
+        match freshness {
+            0 => Ok(Freshness::Any),
+            1 => Ok(Freshness::MustBeFresh),
+            2 => Ok(Freshness::Latest),
+            _ => Err(anyhow!("Freshness {} is unrecognized", freshness)),
+        }
+    }
+}
+impl fmt::Display for Freshness {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Freshness::Any => write!(f, "any"),
+            Freshness::MustBeFresh => write!(f, "must be fresh"),
+            Freshness::Latest => write!(f, "latest"),
+        }
+    }
+}
@@ -2,9 +2,16 @@ use {
     crate::{ PublicIdentity, PublicIdentityInterface },
     copernica_common::{constants::*, u8_to_u64, u16_to_u8, u64_to_u8 },
     anyhow::{Result, anyhow},
-    std::fmt,
-    core::hash::{Hash}
+    std::{
+        cmp::Ordering,
+        fmt,
+        str::FromStr,
+    },
+    core::hash::{Hash, Hasher}
 };
+const URI_SCHEME: &str = "cop://";
+const URI_REQUEST_PID: &str = "?for=";
+const URI_BFI: &str = "bfi:";
 pub fn bloom_filter_index(
     s: &str,
 ) -> Result<BFI> {
@@ -76,6 +83,30 @@ impl BFIS {
         Self(bfis)
     }
 }
+// The app, m0d, fun and arg names an HBFI was made from, when known. Only
+// the BFIs go on the wire so an HBFI read off a link has none, and the names
+// never take part in comparing or hashing HBFIs.
+#[derive(Clone, Default)]
+struct Names([Option<String>; 4]);
+impl PartialEq for Names {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+impl Eq for Names {}
+impl PartialOrd for Names {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Names {
+    fn cmp(&self, _other: &Self) -> Ordering {
+        Ordering::Equal
+    }
+}
+impl Hash for Names {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
 #[derive(Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct HBFI {
     // Hierarchical Bloom Filter Index
@@ -87,7 +118,8 @@ pub struct HBFI {
     pub m0d: BFI, // Module
     pub fun: BFI, // Function
     pub arg: BFI, // Argument
-    pub frm: u64, // Frame Count: current 1024 byte chunk of data in a range.
+    pub frm: u64, // Frame Count: current FRAGMENT_SIZE byte chunk of data in a range.
+    names: Names,
 }
 impl HBFI {
     pub fn new(request_pid: PublicIdentityInterface
@@ -107,6 +139,7 @@ impl HBFI {
             fun: bloom_filter_index(fun)?,
             arg: bloom_filter_index(arg)?,
             frm: 0,
+            names: Names([Some(app.into()), Some(m0d.into()), Some(fun.into()), Some(arg.into())]),
         })
     }
     pub fn to_bfis(&self) -> BFIS {
@@ -132,6 +165,7 @@ impl HBFI {
             , fun: self.fun.clone()
             , arg: self.arg.clone()
             , frm: self.frm.clone()
+            , names: self.names.clone()
         })
     }
     pub fn cleartext_repr(&self) -> Result<Self> {
@@ -145,6 +179,7 @@ impl HBFI {
             , fun: self.fun.clone()
             , arg: self.arg.clone()
             , frm: self.frm.clone()
+            , names: self.names.clone()
         })
     }
     pub fn from_bytes(data: &[u8]) -> Result<Self> {
@@ -173,7 +208,8 @@ impl HBFI {
                       , m0d: bfis[3].clone()
                       , fun: bfis[4].clone()
                       , arg: bfis[5].clone()
-                      , frm})
+                      , frm
+                      , names: Names::default()})
           },
           CLEARTEXT_HBFI_SIZE => {
               let mut bfis: Vec<BFI> = Vec::with_capacity(BFI_COUNT);
@@ -197,7 +233,8 @@ impl HBFI {
                       , m0d: bfis[3].clone()
                       , fun: bfis[4].clone()
                       , arg: bfis[5].clone()
-                      , frm})
+                      , frm
+                      , names: Names::default()})
           }
             _ => Err(anyhow!("Length of data used to reconstruct a HBFI is unrecognised")),
         }
@@ -239,18 +276,110 @@ impl HBFI {
 impl fmt::Debug for HBFI {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match &*self {
-            HBFI { request_pid, response_pid, res, req, app, m0d, fun, arg, frm } =>
+            HBFI { request_pid, response_pid, res, req, app, m0d, fun, arg, frm, .. } =>
             write!(f, "req_pid:{:?},res_pid:{:?},req:{:?},res:{:?},app:{:?},m0d:{:?},fun:{:?},arg:{:?},frm:{:?}", request_pid, response_pid, req, res, app, m0d, fun, arg, frm),
         }
     }
 }
+// The canonical URI of an HBFI, cop://<res>/<app>/<m0d>/<fun>/<arg>#<frm>?for=<req>
+// with the frame left out when it's 0 and the requester when it's Absent.
+// Names are percent-encoded and a name that isn't known is written as its
+// BFI, bfi:<16 hex digits>, which parses back to the same HBFI.
 impl fmt::Display for HBFI {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match &*self {
-            HBFI { request_pid, response_pid, res, req, app, m0d, fun, arg, frm } =>
-            write!(f, "req_pid:{:?},res_pid:{:?},req:{:?},res:{:?},app:{:?},m0d:{:?},fun:{:?},arg:{:?},frm:{:?}", request_pid, response_pid, req, res, app, m0d, fun, arg, frm),
+        write!(f, "{}{}", URI_SCHEME, self.response_pid)?;
+        let bfis = [&self.app, &self.m0d, &self.fun, &self.arg];
+        for (name, bfi) in self.names.0.iter().zip(bfis.iter()) {
+            match name {
+                Some(name) => write!(f, "/{}", encode_name(name))?,
+                None => write!(f, "/{}{:04x}{:04x}{:04x}{:04x}", URI_BFI, bfi.0[0], bfi.0[1], bfi.0[2], bfi.0[3])?,
+            }
+        }
+        if self.frm != 0 {
+            write!(f, "#{}", self.frm)?;
+        }
+        if let PublicIdentityInterface::Present { public_identity } = &self.request_pid {
+            write!(f, "{}{}", URI_REQUEST_PID, public_identity)?;
+        }
+        Ok(())
+    }
+}
+impl FromStr for HBFI {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let rest = s.strip_prefix(URI_SCHEME)
+            .ok_or_else(|| anyhow!("HBFI URI {} doesn't start with {}", s, URI_SCHEME))?;
+        let (rest, request_pid) = match rest.find(URI_REQUEST_PID) {
+            Some(i) => (&rest[..i], PublicIdentityInterface::new(PublicIdentity::from_str(&rest[i + URI_REQUEST_PID.len()..])?)),
+            None => (rest, PublicIdentityInterface::Absent),
+        };
+        let (rest, frm) = match rest.find('#') {
+            Some(i) => (&rest[..i], rest[i + 1..].parse()?),
+            None => (rest, 0),
+        };
+        let segments: Vec<&str> = rest.split('/').collect();
+        if segments.len() != 5 {
+            return Err(anyhow!("HBFI URI {} should name a res, app, m0d, fun and arg", s))
+        }
+        let response_pid = PublicIdentity::from_str(segments[0])?;
+        let (app_name, app) = decode_segment(segments[1])?;
+        let (m0d_name, m0d) = decode_segment(segments[2])?;
+        let (fun_name, fun) = decode_segment(segments[3])?;
+        let (arg_name, arg) = decode_segment(segments[4])?;
+        Ok(HBFI {
+            req: request_pid.bloom_filter_index()?,
+            res: bloom_filter_index(&format!("{}", response_pid))?,
+            request_pid,
+            response_pid,
+            app,
+            m0d,
+            fun,
+            arg,
+            frm,
+            names: Names([app_name, m0d_name, fun_name, arg_name]),
+        })
+    }
+}
+// Everything but RFC 3986's unreserved characters is percent-encoded, so a
+// name can't be mistaken for a BFI or for the URI's separators.
+fn encode_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => out.push(byte as char),
+            _ => out.push_str(&format!("%{:02X}", byte)),
         }
     }
+    out
+}
+fn decode_segment(segment: &str) -> Result<(Option<String>, BFI)> {
+    if let Some(hex) = segment.strip_prefix(URI_BFI) {
+        if hex.len() != BLOOM_FILTER_INDEX_ELEMENT_LENGTH * 4 || !hex.is_ascii() {
+            return Err(anyhow!("{} isn't a BFI", segment))
+        }
+        let mut bfi = BFI::new();
+        for (i, element) in bfi.0.iter_mut().enumerate() {
+            *element = u16::from_str_radix(&hex[i * 4..i * 4 + 4], 16)?;
+        }
+        return Ok((None, bfi))
+    }
+    let mut bytes: Vec<u8> = Vec::with_capacity(segment.len());
+    let mut rest = segment.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' && tail.len() >= 2 {
+            let hex = std::str::from_utf8(&tail[..2])?;
+            bytes.push(u8::from_str_radix(hex, 16)?);
+            rest = &tail[2..];
+        } else if byte == b'%' {
+            return Err(anyhow!("{} ends in an incomplete percent-encoding", segment))
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    let name = String::from_utf8(bytes)?;
+    let bfi = bloom_filter_index(&name)?;
+    Ok((Some(name), bfi))
 }
 #[cfg(test)]
 mod tests {
@@ -262,4 +391,25 @@ mod tests {
         let expected = BFI([19283, 50425, 20212, 47266]);
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn test_uri_round_trip() {
+        use crate::PrivateIdentityInterface;
+        let request_pid = PrivateIdentityInterface::new_key().public_id();
+        let response_pid = PrivateIdentityInterface::new_key().public_id();
+        let hbfi = HBFI::new(PublicIdentityInterface::new(request_pid.clone()), response_pid.clone(), "app", "m0d", "fun/ny", "").unwrap().offset(7);
+        let uri = hbfi.to_string();
+        assert_eq!(uri, format!("cop://{}/app/m0d/fun%2Fny/#7?for={}", response_pid, request_pid));
+        let parsed: HBFI = uri.parse().unwrap();
+        assert_eq!(parsed, hbfi);
+        assert_eq!(parsed.to_string(), uri);
+        // read off the wire the names are lost but the HBFI is the same
+        let unnamed = HBFI::from_bytes(&hbfi.cleartext_repr().unwrap().as_bytes()).unwrap();
+        let uri = unnamed.to_string();
+        assert!(uri.starts_with(&format!("cop://{}/bfi:", response_pid)));
+        assert_eq!(uri.parse::<HBFI>().unwrap(), unnamed);
+        assert!("cop://app/m0d/fun/arg".parse::<HBFI>().is_err());
+        assert!(format!("cop://{}/app/m0d/fun/arg/more", response_pid).parse::<HBFI>().is_err());
+        assert!(format!("cop://{}/bfi:12/m0d/fun/arg", response_pid).parse::<HBFI>().is_err());
+    }
 }
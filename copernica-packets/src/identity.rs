@@ -369,6 +369,11 @@ impl PrivateIdentityInterface {
         let key = PrivateIdentity::from_seed(Seed::generate(&mut rng));
         Self { inner: PrivateIdentityState::FileSystem { key } }
     }
+    // The same seed always gives the same key, for identities kept in a file.
+    pub fn from_seed(seed: [u8; Seed::SIZE]) -> Self {
+        let key = PrivateIdentity::from_seed(Seed::from(seed));
+        Self { inner: PrivateIdentityState::FileSystem { key } }
+    }
     pub fn new_sentinel() -> Self  {
         let mut rng = rand::thread_rng();
         let key = PrivateIdentity::from_seed(Seed::generate(&mut rng));
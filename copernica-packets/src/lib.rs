@@ -19,7 +19,12 @@ mod nonce;
 mod hbfi_exclude_frame;
 mod hbfi_only_keys;
 mod link_packet;
+mod decode_failures;
 mod response_data;
+mod cache_digest;
+mod nack_reason;
+mod freshness;
+mod request_options;
 mod narrow_waist_packet;
 mod narrow_waist_packet_request_equals_response;
 mod inter_link_packet;
@@ -34,7 +39,12 @@ pub use crate::{
     hbfi_only_keys::{HBFIOnlyKeys},
     link_id::{LinkId},
     response_data::{ResponseData},
+    cache_digest::{CacheDigest},
+    nack_reason::{NackReason},
+    freshness::{Freshness},
+    request_options::{RequestOptions},
     link_packet::{LinkPacket},
+    decode_failures::{DecodeFailures},
     inter_link_packet::{InterLinkPacket},
     narrow_waist_packet::{NarrowWaistPacket},
     narrow_waist_packet_request_equals_response::{NarrowWaistPacketReqEqRes, NarrowWaistPacketReqEqResBounds},
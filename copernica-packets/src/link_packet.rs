@@ -31,6 +31,12 @@ impl LinkPacket {
             nw: self.nw.clone(),
         }
     }
+    pub fn change_narrow_waist(&self, nw: NarrowWaistPacket) -> Self {
+        LinkPacket {
+            reply_to: self.reply_to.clone(),
+            nw,
+        }
+    }
     pub fn as_bytes(&self, link_id: LinkId) -> Result<Vec<u8>> {
         let mut buf: Vec<u8> = vec![];
         let lnk_tx_pid = link_id.link_pid()?;
@@ -0,0 +1,35 @@
+use {
+    anyhow::{anyhow, Result},
+    std::fmt,
+};
+// Why a producer turned a Request down rather than answering it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum NackReason {
+    // the producer doesn't serve the name asked for
+    UnknownName,
+    // the producer serves the name but failed to produce the Response
+    Failed,
+}
+impl NackReason {
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            NackReason::UnknownName => 0,
+            NackReason::Failed => 1,
+        }
+    }
+    pub fn from_u8(reason: u8) -> Result<Self> {
+        match reason {
+            0 => Ok(NackReason::UnknownName),
+            1 => Ok(NackReason::Failed),
+            _ => Err(anyhow!("Nack reason {} is unrecognized", reason)),
+        }
+    }
+}
+impl fmt::Display for NackReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NackReason::UnknownName => write!(f, "unknown name"),
+            NackReason::Failed => write!(f, "failed"),
+        }
+    }
+}
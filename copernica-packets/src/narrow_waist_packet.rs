@@ -1,15 +1,16 @@
 use {
     crate::{
         hbfi::HBFI,
-        ResponseData, Nonce,
+        ResponseData, Nonce, CacheDigest, NackReason, Freshness, RequestOptions,
         PrivateIdentityInterface,
         PublicIdentityInterface,
         Signature,
     },
-    copernica_common::constants::*,
+    copernica_common::{constants::*, u8_to_u64, u64_to_u8},
     core::hash::{Hash},
     std::{
         fmt,
+        time::{Duration},
     },
     anyhow::{anyhow, Result},
     log::{error},
@@ -19,20 +20,243 @@ pub enum NarrowWaistPacket {
     Request {
         hbfi: HBFI,
         nonce: Nonce,
+        hop_limit: u8,
+        lifetime: Option<u64>,
+        freshness: Freshness,
+        signature: Option<Signature>,
     },
     Response {
         hbfi: HBFI,
         nonce: Nonce,
         signature: Signature,
+        fresh_for: Option<u64>,
+        version: u64,
         data: ResponseData,
     },
+    Announce {
+        hbfi: HBFI,
+        nonce: Nonce,
+        distance: u8,
+        signature: Signature,
+    },
+    Digest {
+        hbfi: HBFI,
+        nonce: Nonce,
+        signature: Signature,
+        digest: CacheDigest,
+    },
+    Credit {
+        hbfi: HBFI,
+        nonce: Nonce,
+        signature: Signature,
+        requests: u64,
+        responses: u64,
+    },
+    Cancel {
+        hbfi: HBFI,
+        nonce: Nonce,
+    },
+    Nack {
+        hbfi: HBFI,
+        nonce: Nonce,
+        signature: Signature,
+        reason: NackReason,
+    },
 }
 impl NarrowWaistPacket {
-    pub fn request(hbfi: HBFI) -> Result<Self> {
+    pub fn request(hbfi: HBFI, options: RequestOptions) -> Result<Self> {
+        let nonce: Nonce = Nonce::new();
+        let RequestOptions { hop_limit, lifetime, freshness } = options;
+        let lifetime = lifetime.map(lifetime_millis);
+        Ok(NarrowWaistPacket::Request { hbfi, nonce, hop_limit, lifetime, freshness, signature: None })
+    }
+    // Only a cyphertext Request names its requester, so only it can be signed.
+    pub fn signed_request(request_sid: PrivateIdentityInterface, hbfi: HBFI, options: RequestOptions) -> Result<Self> {
+        if hbfi.request_pid != PublicIdentityInterface::new(request_sid.public_id()) {
+            let msg = "The Request's Request Public Identity doesn't match the Public Identity used to sign the Request";
+            error!("{}", msg);
+            return Err(anyhow!(msg));
+        }
+        let nonce: Nonce = Nonce::new();
+        let RequestOptions { hop_limit, lifetime, freshness } = options;
+        let lifetime = lifetime.map(lifetime_millis);
+        let manifest = request_manifest(&hbfi, &nonce, freshness);
+        let request_signkey = request_sid.signing_key();
+        let signature = Some(request_signkey.sign(manifest));
+        Ok(NarrowWaistPacket::Request { hbfi, nonce, hop_limit, lifetime, freshness, signature })
+    }
+    // A producer announces the namespace of its `res` and `app` so brokers
+    // can route Requests for it before any have been answered.
+    pub fn announce(response_sid: PrivateIdentityInterface, app: &str) -> Result<Self> {
+        let hbfi = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), app, "", "", "")?;
+        let nonce: Nonce = Nonce::new();
+        let manifest = [hbfi.as_bytes(), nonce.as_bytes()].concat();
+        let response_signkey = response_sid.signing_key();
+        let signature = response_signkey.sign(manifest);
+        Ok(NarrowWaistPacket::Announce { hbfi, nonce, distance: 0, signature })
+    }
+    // A broker tells the neighbour on the other end of a link what it has
+    // cached, signed with the link's identity. Digests aren't forwarded.
+    pub fn digest(link_sid: PrivateIdentityInterface, digest: CacheDigest) -> Result<Self> {
+        let hbfi = HBFI::new(PublicIdentityInterface::Absent, link_sid.public_id(), "copernica", "cache", "digest", "")?;
+        let nonce: Nonce = Nonce::new();
+        let manifest = [hbfi.as_bytes(), nonce.as_bytes(), digest.as_bytes()].concat();
+        let link_signkey = link_sid.signing_key();
+        let signature = link_signkey.sign(manifest);
+        Ok(NarrowWaistPacket::Digest { hbfi, nonce, signature, digest })
+    }
+    // A broker tells the neighbour on the other end of a link how many more
+    // Requests and Responses it'll take, signed with the link's identity.
+    // Each grant replaces the last, so a lost one is made good by the next.
+    pub fn credit(link_sid: PrivateIdentityInterface, requests: u64, responses: u64) -> Result<Self> {
+        let hbfi = HBFI::new(PublicIdentityInterface::Absent, link_sid.public_id(), "copernica", "flow", "credit", "")?;
         let nonce: Nonce = Nonce::new();
-        Ok(NarrowWaistPacket::Request { hbfi, nonce })
+        let manifest = credit_manifest(&hbfi, &nonce, requests, responses);
+        let link_signkey = link_sid.signing_key();
+        let signature = link_signkey.sign(manifest);
+        Ok(NarrowWaistPacket::Credit { hbfi, nonce, signature, requests, responses })
+    }
+    // A requester that gave up on a Request withdraws it, brokers pass the
+    // Cancel on once no link downstream still wants the Response.
+    pub fn cancel(hbfi: HBFI) -> Result<Self> {
+        let nonce: Nonce = Nonce::new();
+        Ok(NarrowWaistPacket::Cancel { hbfi, nonce })
+    }
+    // A producer that won't answer a Request says why, signed like the
+    // Response it stands in for so brokers can pass it on and cache it.
+    pub fn nack(response_sid: PrivateIdentityInterface, hbfi: HBFI, reason: NackReason) -> Result<Self> {
+        if hbfi.response_pid != response_sid.public_id() {
+            let msg = "The Request's Response Public Identity doesn't match the Public Identity used to sign the Nack";
+            error!("{}", msg);
+            return Err(anyhow!(msg));
+        }
+        let nonce: Nonce = Nonce::new();
+        let manifest = nack_manifest(&hbfi, &nonce, reason);
+        let response_signkey = response_sid.signing_key();
+        let signature = response_signkey.sign(manifest);
+        Ok(NarrowWaistPacket::Nack { hbfi, nonce, signature, reason })
+    }
+    pub fn is_signed(&self) -> bool {
+        match self {
+            NarrowWaistPacket::Request { signature, .. } => signature.is_some(),
+            NarrowWaistPacket::Response { .. } => true,
+            NarrowWaistPacket::Announce { .. } => true,
+            NarrowWaistPacket::Digest { .. } => true,
+            NarrowWaistPacket::Credit { .. } => true,
+            NarrowWaistPacket::Cancel { .. } => false,
+            NarrowWaistPacket::Nack { .. } => true,
+        }
+    }
+    pub fn hbfi(&self) -> HBFI {
+        match self {
+            NarrowWaistPacket::Request { hbfi, .. } => hbfi.clone(),
+            NarrowWaistPacket::Response { hbfi, .. } => hbfi.clone(),
+            NarrowWaistPacket::Announce { hbfi, .. } => hbfi.clone(),
+            NarrowWaistPacket::Digest { hbfi, .. } => hbfi.clone(),
+            NarrowWaistPacket::Credit { hbfi, .. } => hbfi.clone(),
+            NarrowWaistPacket::Cancel { hbfi, .. } => hbfi.clone(),
+            NarrowWaistPacket::Nack { hbfi, .. } => hbfi.clone(),
+        }
+    }
+    pub fn hop_limit(&self) -> Option<u8> {
+        match self {
+            NarrowWaistPacket::Request { hop_limit, .. } => Some(*hop_limit),
+            NarrowWaistPacket::Response { .. } => None,
+            NarrowWaistPacket::Announce { .. } => None,
+            NarrowWaistPacket::Digest { .. } => None,
+            NarrowWaistPacket::Credit { .. } => None,
+            NarrowWaistPacket::Cancel { .. } => None,
+            NarrowWaistPacket::Nack { .. } => None,
+        }
+    }
+    // How much longer the requester waits, as of when the Request was last
+    // forwarded, None when it never said.
+    pub fn lifetime(&self) -> Option<Duration> {
+        match self {
+            NarrowWaistPacket::Request { lifetime, .. } => lifetime.map(Duration::from_millis),
+            _ => None,
+        }
+    }
+    // A broker forwards a Request with whatever is left of its lifetime.
+    pub fn with_lifetime(&self, remaining: Duration) -> Self {
+        match self {
+            NarrowWaistPacket::Request { hbfi, nonce, hop_limit, freshness, signature, .. } => {
+                let lifetime = Some(lifetime_millis(remaining));
+                NarrowWaistPacket::Request { hbfi: hbfi.clone(), nonce: nonce.clone(), hop_limit: *hop_limit, lifetime, freshness: *freshness, signature: signature.clone() }
+            },
+            _ => self.clone(),
+        }
+    }
+    pub fn freshness(&self) -> Freshness {
+        match self {
+            NarrowWaistPacket::Request { freshness, .. } => *freshness,
+            _ => Freshness::Any,
+        }
+    }
+    // How long after it's cached a Response stays fresh, None for forever.
+    pub fn fresh_for(&self) -> Option<Duration> {
+        match self {
+            NarrowWaistPacket::Response { fresh_for, .. } => fresh_for.map(Duration::from_millis),
+            _ => None,
+        }
+    }
+    pub fn version(&self) -> Option<u64> {
+        match self {
+            NarrowWaistPacket::Response { version, .. } => Some(*version),
+            _ => None,
+        }
+    }
+    pub fn distance(&self) -> Option<u8> {
+        match self {
+            NarrowWaistPacket::Announce { distance, .. } => Some(*distance),
+            _ => None,
+        }
+    }
+    // Each broker an Announce passes through adds one to the distance,
+    // None is returned once it's further than ANNOUNCE_MAX_DISTANCE.
+    pub fn increment_distance(&self) -> Option<Self> {
+        match self {
+            NarrowWaistPacket::Announce { hbfi, nonce, distance, signature } => {
+                let distance = distance.checked_add(1).filter(|d| *d <= ANNOUNCE_MAX_DISTANCE)?;
+                Some(NarrowWaistPacket::Announce { hbfi: hbfi.clone(), nonce: nonce.clone(), distance, signature: signature.clone() })
+            },
+            _ => Some(self.clone()),
+        }
+    }
+    // Each broker a Request passes through takes one hop off the limit,
+    // None is returned once a Request has no hops left to give.
+    pub fn decrement_hop_limit(&self) -> Option<Self> {
+        match self {
+            NarrowWaistPacket::Request { hbfi, nonce, hop_limit, lifetime, freshness, signature } => {
+                let hop_limit = hop_limit.checked_sub(1)?;
+                Some(NarrowWaistPacket::Request { hbfi: hbfi.clone(), nonce: nonce.clone(), hop_limit, lifetime: *lifetime, freshness: *freshness, signature: signature.clone() })
+            },
+            NarrowWaistPacket::Response { .. } => Some(self.clone()),
+            NarrowWaistPacket::Announce { .. } => Some(self.clone()),
+            NarrowWaistPacket::Digest { .. } => Some(self.clone()),
+            NarrowWaistPacket::Credit { .. } => Some(self.clone()),
+            NarrowWaistPacket::Cancel { .. } => Some(self.clone()),
+            NarrowWaistPacket::Nack { .. } => Some(self.clone()),
+        }
+    }
+    pub fn nonce(&self) -> Nonce {
+        match self {
+            NarrowWaistPacket::Request { nonce, .. } => nonce.clone(),
+            NarrowWaistPacket::Response { nonce, .. } => nonce.clone(),
+            NarrowWaistPacket::Announce { nonce, .. } => nonce.clone(),
+            NarrowWaistPacket::Digest { nonce, .. } => nonce.clone(),
+            NarrowWaistPacket::Credit { nonce, .. } => nonce.clone(),
+            NarrowWaistPacket::Cancel { nonce, .. } => nonce.clone(),
+            NarrowWaistPacket::Nack { nonce, .. } => nonce.clone(),
+        }
     }
     pub fn response(response_sid: PrivateIdentityInterface, hbfi: HBFI, data: Vec<u8>) -> Result<Self> {
+        NarrowWaistPacket::response_with_freshness(response_sid, hbfi, data, None, 0)
+    }
+    // A producer that may change what it serves under a name says how long a
+    // cached copy stays fresh and numbers each version, newer ones replace
+    // older ones in brokers' caches.
+    pub fn response_with_freshness(response_sid: PrivateIdentityInterface, hbfi: HBFI, data: Vec<u8>, fresh_for: Option<Duration>, version: u64) -> Result<Self> {
         if hbfi.response_pid != response_sid.public_id() {
             let msg = "The Request's Response Public Identity doesn't match the Public Identity used to sign or encypt the Response";
             error!("{}", msg);
@@ -41,18 +265,56 @@ impl NarrowWaistPacket {
         let hbfi = hbfi.clone();
         let nonce: Nonce = Nonce::new();
         let data = ResponseData::insert(response_sid.clone(), hbfi.request_pid.clone(), data, nonce.clone())?;
-        let manifest = manifest(&data, &hbfi, &nonce);
+        // 0 is reserved for forever
+        let fresh_for = fresh_for.map(|fresh_for| (fresh_for.as_millis() as u64).max(1));
+        let manifest = manifest(&data, &hbfi, &nonce, fresh_for, version);
         let response_signkey = response_sid.signing_key();
         let signature = response_signkey.sign(manifest);
-        Ok(NarrowWaistPacket::Response { hbfi, nonce, data, signature })
+        Ok(NarrowWaistPacket::Response { hbfi, nonce, fresh_for, version, data, signature })
     }
     pub fn verify(&self) -> Result<bool> {
         match self {
-            NarrowWaistPacket::Request {..} => {
+            NarrowWaistPacket::Request { signature: None, .. } => {
                 return Ok(true)
             },
-            NarrowWaistPacket::Response { data, hbfi, signature, nonce} => {
-                let manifest = manifest(&data, &hbfi, &nonce);
+            // a Cancel only withdraws the interest of the link it arrives on
+            NarrowWaistPacket::Cancel { .. } => {
+                return Ok(true)
+            },
+            NarrowWaistPacket::Request { hbfi, nonce, freshness, signature: Some(signature), .. } => {
+                // the hop limit and lifetime are left out as every broker changes them
+                let manifest = request_manifest(&hbfi, &nonce, *freshness);
+                let verify_key = hbfi.request_pid.public_identity()?.verify_key()?;
+                let verified = verify_key.verify(&signature, manifest);
+                return Ok(verified);
+            },
+            NarrowWaistPacket::Response { data, hbfi, signature, nonce, fresh_for, version } => {
+                let manifest = manifest(&data, &hbfi, &nonce, *fresh_for, *version);
+                let verify_key = hbfi.response_pid.verify_key()?;
+                let verified = verify_key.verify(&signature, manifest);
+                return Ok(verified);
+            },
+            NarrowWaistPacket::Announce { hbfi, nonce, signature, .. } => {
+                // the distance is left out as every broker changes it
+                let manifest = [hbfi.as_bytes(), nonce.as_bytes()].concat();
+                let verify_key = hbfi.response_pid.verify_key()?;
+                let verified = verify_key.verify(&signature, manifest);
+                return Ok(verified);
+            },
+            NarrowWaistPacket::Digest { hbfi, nonce, signature, digest } => {
+                let manifest = [hbfi.as_bytes(), nonce.as_bytes(), digest.as_bytes()].concat();
+                let verify_key = hbfi.response_pid.verify_key()?;
+                let verified = verify_key.verify(&signature, manifest);
+                return Ok(verified);
+            },
+            NarrowWaistPacket::Credit { hbfi, nonce, signature, requests, responses } => {
+                let manifest = credit_manifest(&hbfi, &nonce, *requests, *responses);
+                let verify_key = hbfi.response_pid.verify_key()?;
+                let verified = verify_key.verify(&signature, manifest);
+                return Ok(verified);
+            },
+            NarrowWaistPacket::Nack { hbfi, nonce, signature, reason } => {
+                let manifest = nack_manifest(&hbfi, &nonce, *reason);
                 let verify_key = hbfi.response_pid.verify_key()?;
                 let verified = verify_key.verify(&signature, manifest);
                 return Ok(verified);
@@ -66,6 +328,31 @@ impl NarrowWaistPacket {
                 error!("{}", err_msg);
                 return Err(anyhow!(err_msg))
             },
+            NarrowWaistPacket::Announce {..} => {
+                let err_msg = "No data in a NarrowWaistPacket::Announce";
+                error!("{}", err_msg);
+                return Err(anyhow!(err_msg))
+            },
+            NarrowWaistPacket::Digest {..} => {
+                let err_msg = "No data in a NarrowWaistPacket::Digest";
+                error!("{}", err_msg);
+                return Err(anyhow!(err_msg))
+            },
+            NarrowWaistPacket::Credit {..} => {
+                let err_msg = "No data in a NarrowWaistPacket::Credit";
+                error!("{}", err_msg);
+                return Err(anyhow!(err_msg))
+            },
+            NarrowWaistPacket::Cancel {..} => {
+                let err_msg = "No data in a NarrowWaistPacket::Cancel";
+                error!("{}", err_msg);
+                return Err(anyhow!(err_msg))
+            },
+            NarrowWaistPacket::Nack {..} => {
+                let err_msg = "No data in a NarrowWaistPacket::Nack";
+                error!("{}", err_msg);
+                return Err(anyhow!(err_msg))
+            },
             NarrowWaistPacket::Response { data, hbfi, nonce, ..}=> {
                 return Ok(data.extract(request_sid, hbfi.request_pid.clone(), hbfi.response_pid.clone(), nonce.clone())?)
 
@@ -75,19 +362,26 @@ impl NarrowWaistPacket {
     pub fn as_bytes(&self) -> Vec<u8> {
         let mut buf: Vec<u8> = vec![];
         match self {
-            NarrowWaistPacket::Request { hbfi, nonce } => {
-                match hbfi.request_pid {
-                    PublicIdentityInterface::Absent => {
+            NarrowWaistPacket::Request { hbfi, nonce, hop_limit, lifetime, freshness, signature } => {
+                match (&hbfi.request_pid, signature) {
+                    (PublicIdentityInterface::Absent, _) => {
                         buf.extend_from_slice(&[CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_INDEX]);
                     },
-                    PublicIdentityInterface::Present { .. } => {
+                    (PublicIdentityInterface::Present { .. }, None) => {
                         buf.extend_from_slice(&[CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_INDEX]);
-                    }
+                    },
+                    (PublicIdentityInterface::Present { .. }, Some(signature)) => {
+                        buf.extend_from_slice(&[SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_INDEX]);
+                        buf.extend_from_slice(signature.as_ref());
+                    },
                 }
                 buf.extend_from_slice(&nonce.as_bytes());
+                buf.extend_from_slice(&[*hop_limit]);
+                buf.extend_from_slice(&u64_to_u8(lifetime.unwrap_or(u64::MAX)));
+                buf.extend_from_slice(&[freshness.as_u8()]);
                 buf.extend_from_slice(&hbfi.as_bytes());
             },
-            NarrowWaistPacket::Response { hbfi, signature, nonce, data } => {
+            NarrowWaistPacket::Response { hbfi, signature, nonce, fresh_for, version, data } => {
                 match hbfi.request_pid {
                     PublicIdentityInterface::Absent => {
                         buf.extend_from_slice(&[CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_INDEX]);
@@ -99,8 +393,58 @@ impl NarrowWaistPacket {
                 buf.extend_from_slice(signature.as_ref());
                 buf.extend_from_slice(&nonce.as_bytes());
                 buf.extend_from_slice(&hbfi.as_bytes());
+                buf.extend_from_slice(&u64_to_u8(fresh_for.unwrap_or(0)));
+                buf.extend_from_slice(&u64_to_u8(*version));
                 buf.extend_from_slice(&data.as_bytes());
             },
+            NarrowWaistPacket::Announce { hbfi, nonce, distance, signature } => {
+                buf.extend_from_slice(&[CLEARTEXT_NARROW_WAIST_PACKET_ANNOUNCE_INDEX]);
+                buf.extend_from_slice(signature.as_ref());
+                buf.extend_from_slice(&nonce.as_bytes());
+                buf.extend_from_slice(&[*distance]);
+                buf.extend_from_slice(&hbfi.as_bytes());
+            },
+            NarrowWaistPacket::Digest { hbfi, nonce, signature, digest } => {
+                buf.extend_from_slice(&[CLEARTEXT_NARROW_WAIST_PACKET_DIGEST_INDEX]);
+                buf.extend_from_slice(signature.as_ref());
+                buf.extend_from_slice(&nonce.as_bytes());
+                buf.extend_from_slice(&hbfi.as_bytes());
+                buf.extend_from_slice(&digest.as_bytes());
+            },
+            NarrowWaistPacket::Credit { hbfi, nonce, signature, requests, responses } => {
+                buf.extend_from_slice(&[CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_INDEX]);
+                buf.extend_from_slice(signature.as_ref());
+                buf.extend_from_slice(&nonce.as_bytes());
+                buf.extend_from_slice(&hbfi.as_bytes());
+                buf.extend_from_slice(&u64_to_u8(*requests));
+                buf.extend_from_slice(&u64_to_u8(*responses));
+            },
+            NarrowWaistPacket::Cancel { hbfi, nonce } => {
+                match hbfi.request_pid {
+                    PublicIdentityInterface::Absent => {
+                        buf.extend_from_slice(&[CLEARTEXT_NARROW_WAIST_PACKET_CANCEL_INDEX]);
+                    },
+                    PublicIdentityInterface::Present { .. } => {
+                        buf.extend_from_slice(&[CYPHERTEXT_NARROW_WAIST_PACKET_CANCEL_INDEX]);
+                    }
+                }
+                buf.extend_from_slice(&nonce.as_bytes());
+                buf.extend_from_slice(&hbfi.as_bytes());
+            },
+            NarrowWaistPacket::Nack { hbfi, nonce, signature, reason } => {
+                match hbfi.request_pid {
+                    PublicIdentityInterface::Absent => {
+                        buf.extend_from_slice(&[CLEARTEXT_NARROW_WAIST_PACKET_NACK_INDEX]);
+                    },
+                    PublicIdentityInterface::Present { .. } => {
+                        buf.extend_from_slice(&[CYPHERTEXT_NARROW_WAIST_PACKET_NACK_INDEX]);
+                    }
+                }
+                buf.extend_from_slice(signature.as_ref());
+                buf.extend_from_slice(&nonce.as_bytes());
+                buf.extend_from_slice(&hbfi.as_bytes());
+                buf.extend_from_slice(&[reason.as_u8()]);
+            },
         }
         buf
     }
@@ -109,13 +453,36 @@ impl NarrowWaistPacket {
         let nw: NarrowWaistPacket = match nw_index {
             CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_INDEX => {
                 let nonce = Nonce::from_bytes(&data[CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_NONCE_START..CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_NONCE_END]);
+                let hop_limit = data[CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_HOP_LIMIT_START];
+                let mut lifetime = [0u8; LIFETIME_SIZE];
+                lifetime.clone_from_slice(&data[CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_LIFETIME_START..CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_LIFETIME_END]);
+                let lifetime = Some(u8_to_u64(lifetime)).filter(|lifetime| *lifetime != u64::MAX);
+                let freshness = Freshness::from_u8(data[CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_FRESHNESS_START])?;
                 let hbfi: HBFI = HBFI::from_bytes(&data[CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_HBFI_START..CLEARTEXT_NARROW_WAIST_PACKET_REQUEST_HBFI_END])?;
-                NarrowWaistPacket::Request { hbfi, nonce }
+                NarrowWaistPacket::Request { hbfi, nonce, hop_limit, lifetime, freshness, signature: None }
             },
             CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_INDEX => {
                 let nonce = Nonce::from_bytes(&data[CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_NONCE_START..CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_NONCE_END]);
+                let hop_limit = data[CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_HOP_LIMIT_START];
+                let mut lifetime = [0u8; LIFETIME_SIZE];
+                lifetime.clone_from_slice(&data[CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_LIFETIME_START..CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_LIFETIME_END]);
+                let lifetime = Some(u8_to_u64(lifetime)).filter(|lifetime| *lifetime != u64::MAX);
+                let freshness = Freshness::from_u8(data[CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_FRESHNESS_START])?;
                 let hbfi: HBFI = HBFI::from_bytes(&data[CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_HBFI_START..CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_HBFI_END])?;
-                NarrowWaistPacket::Request { hbfi, nonce }
+                NarrowWaistPacket::Request { hbfi, nonce, hop_limit, lifetime, freshness, signature: None }
+            },
+            SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_INDEX => {
+                let mut signature = [0u8; Signature::SIZE];
+                signature.clone_from_slice(&data[SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_SIG_START..SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_SIG_END]);
+                let signature: Signature = Signature::from(signature);
+                let nonce = Nonce::from_bytes(&data[SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_NONCE_START..SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_NONCE_END]);
+                let hop_limit = data[SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_HOP_LIMIT_START];
+                let mut lifetime = [0u8; LIFETIME_SIZE];
+                lifetime.clone_from_slice(&data[SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_LIFETIME_START..SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_LIFETIME_END]);
+                let lifetime = Some(u8_to_u64(lifetime)).filter(|lifetime| *lifetime != u64::MAX);
+                let freshness = Freshness::from_u8(data[SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_FRESHNESS_START])?;
+                let hbfi: HBFI = HBFI::from_bytes(&data[SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_HBFI_START..SIGNED_CYPHERTEXT_NARROW_WAIST_PACKET_REQUEST_HBFI_END])?;
+                NarrowWaistPacket::Request { hbfi, nonce, hop_limit, lifetime, freshness, signature: Some(signature) }
             },
             CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_INDEX => {
                 let mut signature = [0u8; Signature::SIZE];
@@ -123,8 +490,14 @@ impl NarrowWaistPacket {
                 let signature: Signature = Signature::from(signature);
                 let nonce = Nonce::from_bytes(&data[CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_NONCE_START..CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_NONCE_END]);
                 let hbfi: HBFI = HBFI::from_bytes(&data[CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_HBFI_START..CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_HBFI_END])?;
+                let mut fresh_for = [0u8; FRESH_FOR_SIZE];
+                fresh_for.clone_from_slice(&data[CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_FRESH_FOR_START..CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_FRESH_FOR_END]);
+                let fresh_for = Some(u8_to_u64(fresh_for)).filter(|fresh_for| *fresh_for != 0);
+                let mut version = [0u8; VERSION_SIZE];
+                version.clone_from_slice(&data[CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_VERSION_START..CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_VERSION_END]);
+                let version = u8_to_u64(version);
                 let data: ResponseData = ResponseData::from_bytes(&data[CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_DATA_START..CLEARTEXT_NARROW_WAIST_PACKET_RESPONSE_DATA_END])?;
-                NarrowWaistPacket::Response { hbfi, signature, nonce, data }
+                NarrowWaistPacket::Response { hbfi, signature, nonce, fresh_for, version, data }
             },
             CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_INDEX => {
                 let mut signature = [0u8; Signature::SIZE];
@@ -132,8 +505,72 @@ impl NarrowWaistPacket {
                 let signature: Signature = Signature::from(signature);
                 let nonce = Nonce::from_bytes(&data[CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_NONCE_START..CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_NONCE_END]);
                 let hbfi: HBFI = HBFI::from_bytes(&data[CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_HBFI_START..CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_HBFI_END])?;
+                let mut fresh_for = [0u8; FRESH_FOR_SIZE];
+                fresh_for.clone_from_slice(&data[CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_FRESH_FOR_START..CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_FRESH_FOR_END]);
+                let fresh_for = Some(u8_to_u64(fresh_for)).filter(|fresh_for| *fresh_for != 0);
+                let mut version = [0u8; VERSION_SIZE];
+                version.clone_from_slice(&data[CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_VERSION_START..CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_VERSION_END]);
+                let version = u8_to_u64(version);
                 let data: ResponseData = ResponseData::from_bytes(&data[CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_DATA_START..CYPHERTEXT_NARROW_WAIST_PACKET_RESPONSE_DATA_END])?;
-                NarrowWaistPacket::Response { hbfi, signature, nonce, data }
+                NarrowWaistPacket::Response { hbfi, signature, nonce, fresh_for, version, data }
+            },
+            CLEARTEXT_NARROW_WAIST_PACKET_ANNOUNCE_INDEX => {
+                let mut signature = [0u8; Signature::SIZE];
+                signature.clone_from_slice(&data[CLEARTEXT_NARROW_WAIST_PACKET_ANNOUNCE_SIG_START..CLEARTEXT_NARROW_WAIST_PACKET_ANNOUNCE_SIG_END]);
+                let signature: Signature = Signature::from(signature);
+                let nonce = Nonce::from_bytes(&data[CLEARTEXT_NARROW_WAIST_PACKET_ANNOUNCE_NONCE_START..CLEARTEXT_NARROW_WAIST_PACKET_ANNOUNCE_NONCE_END]);
+                let distance = data[CLEARTEXT_NARROW_WAIST_PACKET_ANNOUNCE_DISTANCE_START];
+                let hbfi: HBFI = HBFI::from_bytes(&data[CLEARTEXT_NARROW_WAIST_PACKET_ANNOUNCE_HBFI_START..CLEARTEXT_NARROW_WAIST_PACKET_ANNOUNCE_HBFI_END])?;
+                NarrowWaistPacket::Announce { hbfi, nonce, distance, signature }
+            },
+            CLEARTEXT_NARROW_WAIST_PACKET_DIGEST_INDEX => {
+                let mut signature = [0u8; Signature::SIZE];
+                signature.clone_from_slice(&data[CLEARTEXT_NARROW_WAIST_PACKET_DIGEST_SIG_START..CLEARTEXT_NARROW_WAIST_PACKET_DIGEST_SIG_END]);
+                let signature: Signature = Signature::from(signature);
+                let nonce = Nonce::from_bytes(&data[CLEARTEXT_NARROW_WAIST_PACKET_DIGEST_NONCE_START..CLEARTEXT_NARROW_WAIST_PACKET_DIGEST_NONCE_END]);
+                let hbfi: HBFI = HBFI::from_bytes(&data[CLEARTEXT_NARROW_WAIST_PACKET_DIGEST_HBFI_START..CLEARTEXT_NARROW_WAIST_PACKET_DIGEST_HBFI_END])?;
+                let digest = CacheDigest::from_bytes(&data[CLEARTEXT_NARROW_WAIST_PACKET_DIGEST_DATA_START..CLEARTEXT_NARROW_WAIST_PACKET_DIGEST_DATA_END])?;
+                NarrowWaistPacket::Digest { hbfi, nonce, signature, digest }
+            },
+            CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_INDEX => {
+                let mut signature = [0u8; Signature::SIZE];
+                signature.clone_from_slice(&data[CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_SIG_START..CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_SIG_END]);
+                let signature: Signature = Signature::from(signature);
+                let nonce = Nonce::from_bytes(&data[CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_NONCE_START..CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_NONCE_END]);
+                let hbfi: HBFI = HBFI::from_bytes(&data[CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_HBFI_START..CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_HBFI_END])?;
+                let mut requests = [0u8; U64_SIZE];
+                requests.clone_from_slice(&data[CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_REQUESTS_START..CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_REQUESTS_END]);
+                let mut responses = [0u8; U64_SIZE];
+                responses.clone_from_slice(&data[CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_RESPONSES_START..CLEARTEXT_NARROW_WAIST_PACKET_CREDIT_RESPONSES_END]);
+                NarrowWaistPacket::Credit { hbfi, nonce, signature, requests: u8_to_u64(requests), responses: u8_to_u64(responses) }
+            },
+            CLEARTEXT_NARROW_WAIST_PACKET_CANCEL_INDEX => {
+                let nonce = Nonce::from_bytes(&data[CLEARTEXT_NARROW_WAIST_PACKET_CANCEL_NONCE_START..CLEARTEXT_NARROW_WAIST_PACKET_CANCEL_NONCE_END]);
+                let hbfi: HBFI = HBFI::from_bytes(&data[CLEARTEXT_NARROW_WAIST_PACKET_CANCEL_HBFI_START..CLEARTEXT_NARROW_WAIST_PACKET_CANCEL_HBFI_END])?;
+                NarrowWaistPacket::Cancel { hbfi, nonce }
+            },
+            CYPHERTEXT_NARROW_WAIST_PACKET_CANCEL_INDEX => {
+                let nonce = Nonce::from_bytes(&data[CYPHERTEXT_NARROW_WAIST_PACKET_CANCEL_NONCE_START..CYPHERTEXT_NARROW_WAIST_PACKET_CANCEL_NONCE_END]);
+                let hbfi: HBFI = HBFI::from_bytes(&data[CYPHERTEXT_NARROW_WAIST_PACKET_CANCEL_HBFI_START..CYPHERTEXT_NARROW_WAIST_PACKET_CANCEL_HBFI_END])?;
+                NarrowWaistPacket::Cancel { hbfi, nonce }
+            },
+            CLEARTEXT_NARROW_WAIST_PACKET_NACK_INDEX => {
+                let mut signature = [0u8; Signature::SIZE];
+                signature.clone_from_slice(&data[CLEARTEXT_NARROW_WAIST_PACKET_NACK_SIG_START..CLEARTEXT_NARROW_WAIST_PACKET_NACK_SIG_END]);
+                let signature: Signature = Signature::from(signature);
+                let nonce = Nonce::from_bytes(&data[CLEARTEXT_NARROW_WAIST_PACKET_NACK_NONCE_START..CLEARTEXT_NARROW_WAIST_PACKET_NACK_NONCE_END]);
+                let hbfi: HBFI = HBFI::from_bytes(&data[CLEARTEXT_NARROW_WAIST_PACKET_NACK_HBFI_START..CLEARTEXT_NARROW_WAIST_PACKET_NACK_HBFI_END])?;
+                let reason = NackReason::from_u8(data[CLEARTEXT_NARROW_WAIST_PACKET_NACK_REASON_START])?;
+                NarrowWaistPacket::Nack { hbfi, nonce, signature, reason }
+            },
+            CYPHERTEXT_NARROW_WAIST_PACKET_NACK_INDEX => {
+                let mut signature = [0u8; Signature::SIZE];
+                signature.clone_from_slice(&data[CYPHERTEXT_NARROW_WAIST_PACKET_NACK_SIG_START..CYPHERTEXT_NARROW_WAIST_PACKET_NACK_SIG_END]);
+                let signature: Signature = Signature::from(signature);
+                let nonce = Nonce::from_bytes(&data[CYPHERTEXT_NARROW_WAIST_PACKET_NACK_NONCE_START..CYPHERTEXT_NARROW_WAIST_PACKET_NACK_NONCE_END]);
+                let hbfi: HBFI = HBFI::from_bytes(&data[CYPHERTEXT_NARROW_WAIST_PACKET_NACK_HBFI_START..CYPHERTEXT_NARROW_WAIST_PACKET_NACK_HBFI_END])?;
+                let reason = NackReason::from_u8(data[CYPHERTEXT_NARROW_WAIST_PACKET_NACK_REASON_START])?;
+                NarrowWaistPacket::Nack { hbfi, nonce, signature, reason }
             },
             _ => {
                 let msg = format!("Index used in the NarrowWaistPacket is unrecognized");
@@ -149,19 +586,48 @@ impl NarrowWaistPacket {
         Ok(nw)
     }
 }
-fn manifest(data: &ResponseData, hbfi: &HBFI, nonce: &Nonce) ->  Vec<u8> {
-    [data.as_bytes(), hbfi.as_bytes(), nonce.as_bytes()].concat()
+// The freshness period and version are signed so a broker can't keep
+// serving a Response longer than its producer allows or pass an old one off
+// as the latest.
+fn manifest(data: &ResponseData, hbfi: &HBFI, nonce: &Nonce, fresh_for: Option<u64>, version: u64) ->  Vec<u8> {
+    [data.as_bytes(), hbfi.as_bytes(), nonce.as_bytes(), u64_to_u8(fresh_for.unwrap_or(0)).to_vec(), u64_to_u8(version).to_vec()].concat()
+}
+// The freshness is signed so a broker can't loosen what a Request will take
+// from a cache.
+fn request_manifest(hbfi: &HBFI, nonce: &Nonce, freshness: Freshness) -> Vec<u8> {
+    let mut manifest = [hbfi.as_bytes(), nonce.as_bytes()].concat();
+    if freshness != Freshness::Any {
+        manifest.push(freshness.as_u8());
+    }
+    manifest
+}
+fn lifetime_millis(lifetime: Duration) -> u64 {
+    // u64::MAX is reserved for no lifetime
+    lifetime.as_millis().min(u64::MAX as u128 - 1) as u64
+}
+fn nack_manifest(hbfi: &HBFI, nonce: &Nonce, reason: NackReason) -> Vec<u8> {
+    [hbfi.as_bytes(), nonce.as_bytes(), vec![reason.as_u8()]].concat()
+}
+fn credit_manifest(hbfi: &HBFI, nonce: &Nonce, requests: u64, responses: u64) -> Vec<u8> {
+    [hbfi.as_bytes(), nonce.as_bytes(), u64_to_u8(requests).to_vec(), u64_to_u8(responses).to_vec()].concat()
 }
 impl fmt::Debug for NarrowWaistPacket {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match &*self {
-            NarrowWaistPacket::Request { hbfi, .. } => write!(f, "NW REQ {:?}", hbfi),
+            NarrowWaistPacket::Request { hbfi, hop_limit, lifetime: None, freshness, .. } => write!(f, "NW REQ {:?} hops {} {}", hbfi, hop_limit, freshness),
+            NarrowWaistPacket::Request { hbfi, hop_limit, lifetime: Some(lifetime), freshness, .. } => write!(f, "NW REQ {:?} hops {} lifetime {}ms {}", hbfi, hop_limit, lifetime, freshness),
             NarrowWaistPacket::Response {
                 hbfi,
                 signature,
                 nonce,
+                version,
                 ..
-            } => write!(f, "NW RES {:?} {} {:?}", hbfi, signature, nonce),
+            } => write!(f, "NW RES {:?} {} {:?} version {}", hbfi, signature, nonce, version),
+            NarrowWaistPacket::Announce { hbfi, distance, .. } => write!(f, "NW ANN {:?} distance {}", hbfi, distance),
+            NarrowWaistPacket::Digest { hbfi, digest, .. } => write!(f, "NW DIG {:?} {:?}", hbfi, digest),
+            NarrowWaistPacket::Credit { hbfi, requests, responses, .. } => write!(f, "NW CRD {:?} requests {} responses {}", hbfi, requests, responses),
+            NarrowWaistPacket::Cancel { hbfi, .. } => write!(f, "NW CAN {:?}", hbfi),
+            NarrowWaistPacket::Nack { hbfi, reason, .. } => write!(f, "NW NAK {:?} {}", hbfi, reason),
         }
     }
 }
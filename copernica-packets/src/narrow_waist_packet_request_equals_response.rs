@@ -14,11 +14,21 @@ impl NarrowWaistPacketReqEqResBounds for NarrowWaistPacketReqEqRes {
     fn contains(&self, v: &NarrowWaistPacketReqEqRes) -> bool {
         let self_frm = match &self.0 {
             NarrowWaistPacket::Request { hbfi, .. } => { hbfi.frm },
-            NarrowWaistPacket::Response { hbfi, .. } => { hbfi.frm }
+            NarrowWaistPacket::Response { hbfi, .. } => { hbfi.frm },
+            NarrowWaistPacket::Announce { hbfi, .. } => { hbfi.frm },
+            NarrowWaistPacket::Digest { hbfi, .. } => { hbfi.frm },
+            NarrowWaistPacket::Credit { hbfi, .. } => { hbfi.frm },
+            NarrowWaistPacket::Cancel { hbfi, .. } => { hbfi.frm },
+            NarrowWaistPacket::Nack { hbfi, .. } => { hbfi.frm }
         };
         let other_frm = match &v.0 {
             NarrowWaistPacket::Request { hbfi, .. } => { hbfi.frm },
-            NarrowWaistPacket::Response { hbfi, .. } => { hbfi.frm }
+            NarrowWaistPacket::Response { hbfi, .. } => { hbfi.frm },
+            NarrowWaistPacket::Announce { hbfi, .. } => { hbfi.frm },
+            NarrowWaistPacket::Digest { hbfi, .. } => { hbfi.frm },
+            NarrowWaistPacket::Credit { hbfi, .. } => { hbfi.frm },
+            NarrowWaistPacket::Cancel { hbfi, .. } => { hbfi.frm },
+            NarrowWaistPacket::Nack { hbfi, .. } => { hbfi.frm }
         };
         self_frm == other_frm
     }
@@ -38,7 +48,12 @@ impl Hash for NarrowWaistPacketReqEqRes {
     fn hash<H: Hasher>(&self, state: &mut H) {
         match &self.0 {
             NarrowWaistPacket::Request { hbfi, .. } => { hbfi.hash(state) },
-            NarrowWaistPacket::Response { hbfi, .. } => { hbfi.hash(state) }
+            NarrowWaistPacket::Response { hbfi, .. } => { hbfi.hash(state) },
+            NarrowWaistPacket::Announce { hbfi, .. } => { hbfi.hash(state) },
+            NarrowWaistPacket::Digest { hbfi, .. } => { hbfi.hash(state) },
+            NarrowWaistPacket::Credit { hbfi, .. } => { hbfi.hash(state) },
+            NarrowWaistPacket::Cancel { hbfi, .. } => { hbfi.hash(state) },
+            NarrowWaistPacket::Nack { hbfi, .. } => { hbfi.hash(state) }
         }
     }
 }
@@ -46,11 +61,21 @@ impl PartialOrd for NarrowWaistPacketReqEqRes {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         let self_hbfi = match &self.0 {
             NarrowWaistPacket::Request { hbfi, .. } => { hbfi },
-            NarrowWaistPacket::Response { hbfi, .. } => { hbfi }
+            NarrowWaistPacket::Response { hbfi, .. } => { hbfi },
+            NarrowWaistPacket::Announce { hbfi, .. } => { hbfi },
+            NarrowWaistPacket::Digest { hbfi, .. } => { hbfi },
+            NarrowWaistPacket::Credit { hbfi, .. } => { hbfi },
+            NarrowWaistPacket::Cancel { hbfi, .. } => { hbfi },
+            NarrowWaistPacket::Nack { hbfi, .. } => { hbfi }
         };
         let other_hbfi = match &other.0 {
             NarrowWaistPacket::Request { hbfi, .. } => { hbfi },
-            NarrowWaistPacket::Response { hbfi, .. } => { hbfi }
+            NarrowWaistPacket::Response { hbfi, .. } => { hbfi },
+            NarrowWaistPacket::Announce { hbfi, .. } => { hbfi },
+            NarrowWaistPacket::Digest { hbfi, .. } => { hbfi },
+            NarrowWaistPacket::Credit { hbfi, .. } => { hbfi },
+            NarrowWaistPacket::Cancel { hbfi, .. } => { hbfi },
+            NarrowWaistPacket::Nack { hbfi, .. } => { hbfi }
         };
         Some(self_hbfi.cmp(other_hbfi))
     }
@@ -60,11 +85,21 @@ impl Ord for NarrowWaistPacketReqEqRes {
     fn cmp(&self, other: &Self) -> Ordering {
         let self_hbfi = match &self.0 {
             NarrowWaistPacket::Request { hbfi, .. } => { hbfi },
-            NarrowWaistPacket::Response { hbfi, .. } => { hbfi }
+            NarrowWaistPacket::Response { hbfi, .. } => { hbfi },
+            NarrowWaistPacket::Announce { hbfi, .. } => { hbfi },
+            NarrowWaistPacket::Digest { hbfi, .. } => { hbfi },
+            NarrowWaistPacket::Credit { hbfi, .. } => { hbfi },
+            NarrowWaistPacket::Cancel { hbfi, .. } => { hbfi },
+            NarrowWaistPacket::Nack { hbfi, .. } => { hbfi }
         };
         let other_hbfi = match &other.0 {
             NarrowWaistPacket::Request { hbfi, .. } => { hbfi },
-            NarrowWaistPacket::Response { hbfi, .. } => { hbfi }
+            NarrowWaistPacket::Response { hbfi, .. } => { hbfi },
+            NarrowWaistPacket::Announce { hbfi, .. } => { hbfi },
+            NarrowWaistPacket::Digest { hbfi, .. } => { hbfi },
+            NarrowWaistPacket::Credit { hbfi, .. } => { hbfi },
+            NarrowWaistPacket::Cancel { hbfi, .. } => { hbfi },
+            NarrowWaistPacket::Nack { hbfi, .. } => { hbfi }
         };
         self_hbfi.frm.cmp(&other_hbfi.frm)
     }
@@ -73,11 +108,21 @@ impl PartialEq for NarrowWaistPacketReqEqRes {
     fn eq(&self, other: &Self) -> bool {
         let self_hbfi = match &self.0 {
             NarrowWaistPacket::Request { hbfi, .. } => { hbfi },
-            NarrowWaistPacket::Response { hbfi, .. } => { hbfi }
+            NarrowWaistPacket::Response { hbfi, .. } => { hbfi },
+            NarrowWaistPacket::Announce { hbfi, .. } => { hbfi },
+            NarrowWaistPacket::Digest { hbfi, .. } => { hbfi },
+            NarrowWaistPacket::Credit { hbfi, .. } => { hbfi },
+            NarrowWaistPacket::Cancel { hbfi, .. } => { hbfi },
+            NarrowWaistPacket::Nack { hbfi, .. } => { hbfi }
         };
         let other_hbfi = match &other.0 {
             NarrowWaistPacket::Request { hbfi, .. } => { hbfi },
-            NarrowWaistPacket::Response { hbfi, .. } => { hbfi }
+            NarrowWaistPacket::Response { hbfi, .. } => { hbfi },
+            NarrowWaistPacket::Announce { hbfi, .. } => { hbfi },
+            NarrowWaistPacket::Digest { hbfi, .. } => { hbfi },
+            NarrowWaistPacket::Credit { hbfi, .. } => { hbfi },
+            NarrowWaistPacket::Cancel { hbfi, .. } => { hbfi },
+            NarrowWaistPacket::Nack { hbfi, .. } => { hbfi }
         };
         self_hbfi == other_hbfi
     }
@@ -88,6 +133,11 @@ impl fmt::Debug for NarrowWaistPacketReqEqRes {
         match &self.0 {
             NarrowWaistPacket::Request  { hbfi, .. } => write!(f, "NWEQ REQ {:?}", hbfi),
             NarrowWaistPacket::Response { hbfi, .. } => write!(f, "NWEQ RES {:?}", hbfi),
+            NarrowWaistPacket::Announce { hbfi, .. } => write!(f, "NWEQ ANN {:?}", hbfi),
+            NarrowWaistPacket::Digest   { hbfi, .. } => write!(f, "NWEQ DIG {:?}", hbfi),
+            NarrowWaistPacket::Credit   { hbfi, .. } => write!(f, "NWEQ CRD {:?}", hbfi),
+            NarrowWaistPacket::Cancel   { hbfi, .. } => write!(f, "NWEQ CAN {:?}", hbfi),
+            NarrowWaistPacket::Nack     { hbfi, .. } => write!(f, "NWEQ NAK {:?}", hbfi),
         }
     }
 }
@@ -0,0 +1,35 @@
+use {
+    crate::Freshness,
+    copernica_common::constants::REQUEST_HOP_LIMIT,
+    std::time::Duration,
+};
+// What a requester asks of the brokers a Request passes through, the default
+// goes REQUEST_HOP_LIMIT hops, never expires and takes any cached Response.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RequestOptions {
+    pub hop_limit: u8,
+    // a requester that waits no longer than this says so, each broker counts
+    // down what's left of it and drops the Request once it's run out
+    pub lifetime: Option<Duration>,
+    pub freshness: Freshness,
+}
+impl Default for RequestOptions {
+    fn default() -> Self {
+        Self { hop_limit: REQUEST_HOP_LIMIT, lifetime: None, freshness: Freshness::Any }
+    }
+}
+impl RequestOptions {
+    pub fn hop_limit(mut self, hop_limit: u8) -> Self {
+        self.hop_limit = hop_limit;
+        self
+    }
+    pub fn lifetime(mut self, lifetime: Duration) -> Self {
+        self.lifetime = Some(lifetime);
+        self
+    }
+    // Whether a broker may answer the Request from its cache, see Freshness.
+    pub fn freshness(mut self, freshness: Freshness) -> Self {
+        self.freshness = freshness;
+        self
+    }
+}
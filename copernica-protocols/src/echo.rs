@@ -2,7 +2,7 @@ use {
     anyhow::{Result},
     bincode,
     copernica_packets::{
-        bloom_filter_index as bfi, NarrowWaistPacket, HBFI, PublicIdentity, PublicIdentityInterface, PrivateIdentityInterface
+        bloom_filter_index as bfi, NarrowWaistPacket, NackReason, HBFI, PublicIdentity, PublicIdentityInterface, PrivateIdentityInterface, Freshness,
     },
     copernica_common::{ Operations, constants::LABEL_SIZE },
     arrayvec::ArrayString,
@@ -20,6 +20,18 @@ pub struct Echo {
     ops: Operations,
 }
 impl Echo {
+    // Lets brokers route pings here before they've seen a single pong.
+    pub fn announce(&self) -> Result<()> {
+        self.txrx.announce("echo")
+    }
+    // See TxRx::request_lifetimes, pings are sent without one by default.
+    pub fn request_lifetimes(&self, on: bool) -> Result<()> {
+        self.txrx.request_lifetimes(on)
+    }
+    // See TxRx::request_freshness, pings take any cached pong by default.
+    pub fn request_freshness(&self, freshness: Freshness) -> Result<()> {
+        self.txrx.request_freshness(freshness)
+    }
     pub fn unreliable_sequenced_cleartext_ping(&mut self, response_pid: PublicIdentity) -> Result<String> {
         let hbfi = HBFI::new(PublicIdentityInterface::Absent, response_pid, "echo", "echo", "echo", UNRELIABLE_SEQUENCED_ECHO)?;
         let mut retries = 5;
@@ -228,9 +240,14 @@ impl Protocol for Echo {
                                                 }
                                                 txrx.respond(hbfi, echo)?;
                                             },
-                                            _ => {}
+                                            _ => {
+                                                txrx.nack(hbfi, NackReason::UnknownName)?;
+                                            }
                                         }
                                     }
+                                HBFI { ref res, .. } if res == &res_check => {
+                                    txrx.nack(hbfi, NackReason::UnknownName)?;
+                                }
                                 _ => {}
                             },
                             NarrowWaistPacket::Response { hbfi, .. } => match hbfi {
@@ -259,7 +276,35 @@ impl Protocol for Echo {
                                         }
                                     }
                                 _ => {}
-                            }
+                            },
+                            NarrowWaistPacket::Nack { hbfi, .. } => match hbfi {
+                                HBFI { app, m0d, fun, arg, .. }
+                                    if (app == app_check)
+                                        && (m0d == m0d_check)
+                                        && (fun == fun_check)
+                                    => {
+                                        match arg {
+                                            arg if arg == bfi(UNRELIABLE_SEQUENCED_ECHO)? => {
+                                                trace!("\t\t|  NACK PACKET ARRIVED");
+                                                txrx.unreliable_sequenced_response(ilp)?;
+                                            },
+                                            arg if arg == bfi(RELIABLE_ORDERED_ECHO)? => {
+                                                trace!("\t\t|  NACK PACKET ARRIVED");
+                                                txrx.reliable_ordered_response(ilp)?;
+                                            },
+                                            arg if arg == bfi(RELIABLE_SEQUENCED_ECHO)? => {
+                                                trace!("\t\t|  NACK PACKET ARRIVED");
+                                                txrx.reliable_sequenced_response(ilp)?;
+                                            },
+                                            _ => {}
+                                        }
+                                    }
+                                _ => {}
+                            },
+                            NarrowWaistPacket::Announce { .. } => {}
+                            NarrowWaistPacket::Digest { .. } => {}
+                            NarrowWaistPacket::Credit { .. } => {}
+                            NarrowWaistPacket::Cancel { .. } => {}
                         }
                     }
                     Err(_e) => {}
@@ -12,7 +12,7 @@ mod txrx;
 pub use {
     self::{
         protocol::{Protocol},
-        txrx::TxRx,
+        txrx::{TxRx, Nacked},
         echo::{Echo},
     },
 };
@@ -1,8 +1,8 @@
 use {
     copernica_packets::{
-        LinkId, NarrowWaistPacket, NarrowWaistPacketReqEqRes,
+        LinkId, NarrowWaistPacket, NarrowWaistPacketReqEqRes, RequestOptions,
         LinkPacket, InterLinkPacket, HBFI, HBFIExcludeFrame,
-        PrivateIdentityInterface, PublicIdentity
+        PrivateIdentityInterface, PublicIdentity, PublicIdentityInterface, NackReason, Freshness,
     },
     copernica_common::{constants::{BOUNDED_BUFFER_SIZE, LABEL_SIZE}, Operations},
     log::{trace,
@@ -13,6 +13,7 @@ use {
     crossbeam_channel::{Receiver, Sender, bounded, unbounded, RecvTimeoutError, SendError},
     arrayvec::ArrayString,
     std::{
+        fmt,
         time::{Duration},
         sync::{Arc, Mutex},
         collections::{BTreeSet, HashMap},
@@ -55,6 +56,19 @@ enum AIMD {
         failed: BTreeSet<NarrowWaistPacketReqEqRes>,
     },
 }
+// The error a request returns once the producer turns it down with a Nack,
+// rather than leaving the requester to run through its retries.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Nacked {
+    pub hbfi: HBFI,
+    pub reason: NackReason,
+}
+impl fmt::Display for Nacked {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "The producer refused the Request for frame {}: {}", self.hbfi.frm, self.reason)
+    }
+}
+impl std::error::Error for Nacked {}
 #[derive(Clone)]
 pub enum TxRx {
     Initialized {
@@ -65,6 +79,9 @@ pub enum TxRx {
         p2l_tx: Sender<InterLinkPacket>,
         l2p_rx: Receiver<InterLinkPacket>,
         responses: Arc<Mutex<HashMap<HBFIExcludeFrame, BTreeSet<NarrowWaistPacketReqEqRes>>>>,
+        nacks: Arc<Mutex<HashMap<HBFIExcludeFrame, Nacked>>>,
+        lifetimes: Arc<Mutex<bool>>,
+        freshness: Arc<Mutex<Freshness>>,
         unreliable_sequenced_response_tx: Sender<InterLinkPacket>,
         unreliable_sequenced_response_rx: Receiver<InterLinkPacket>,
         reliable_sequenced_response_tx: Sender<InterLinkPacket>,
@@ -91,6 +108,9 @@ impl TxRx {
             p2l_tx,
             l2p_rx,
             responses: Arc::new(Mutex::new(HashMap::new())),
+            nacks: Arc::new(Mutex::new(HashMap::new())),
+            lifetimes: Arc::new(Mutex::new(false)),
+            freshness: Arc::new(Mutex::new(Freshness::default())),
             unreliable_sequenced_response_rx,
             unreliable_sequenced_response_tx,
             reliable_sequenced_response_rx,
@@ -117,15 +137,56 @@ impl TxRx {
     }
     fn register_hbfi(&self, hbfi: HBFI) -> Result<()> {
         match self {
-            TxRx::Initialized { responses, .. } => {
+            TxRx::Initialized { responses, nacks, .. } => {
                 let responses_mutex = responses.clone();
                 let mut responses_ref = responses_mutex.lock().unwrap();
+                nacks.lock().unwrap().remove(&HBFIExcludeFrame(hbfi.clone()));
                 responses_ref.insert(HBFIExcludeFrame(hbfi), BTreeSet::new());
                 Ok(())
             },
             TxRx::Inert => Err(anyhow!("You must peer with a link first"))
         }
     }
+    // Off by default, Requests then wait as long as each broker's pending
+    // lifetime. Once on, each Request says it's only waited on for the window
+    // timeout it was sent in, so brokers drop it once that's up.
+    pub fn request_lifetimes(&self, on: bool) -> Result<()> {
+        match self {
+            TxRx::Initialized { lifetimes, .. } => {
+                *lifetimes.lock().unwrap() = on;
+                Ok(())
+            },
+            TxRx::Inert => Err(anyhow!("You must peer with a link first"))
+        }
+    }
+    // How fresh a Response brokers may answer Requests with from their
+    // caches, see Freshness.
+    pub fn request_freshness(&self, freshness: Freshness) -> Result<()> {
+        match self {
+            TxRx::Initialized { freshness: current, .. } => {
+                *current.lock().unwrap() = freshness;
+                Ok(())
+            },
+            TxRx::Inert => Err(anyhow!("You must peer with a link first"))
+        }
+    }
+    // Every transmission gets a fresh nonce as brokers drop nonces they've
+    // already seen. Cyphertext Requests name the requester so they're signed.
+    fn request_packet(&self, hbfi: HBFI, lifetime: Duration) -> Result<NarrowWaistPacket> {
+        match self {
+            TxRx::Initialized { protocol_sid, lifetimes, freshness, .. } => {
+                let mut options = RequestOptions::default().freshness(*freshness.lock().unwrap());
+                if *lifetimes.lock().unwrap() {
+                    options = options.lifetime(lifetime);
+                }
+                match hbfi.request_pid {
+                    PublicIdentityInterface::Absent => NarrowWaistPacket::request(hbfi, options),
+                    PublicIdentityInterface::Present { .. } => NarrowWaistPacket::signed_request(protocol_sid.clone(), hbfi, options),
+                }
+            },
+            TxRx::Inert => Err(anyhow!("You must peer with a link first"))
+        }
+    }
     pub fn next(&self) -> Result<InterLinkPacket> {
         match self {
             TxRx::Initialized { l2p_rx, .. } => {
@@ -135,6 +196,37 @@ impl TxRx {
             TxRx::Inert => Err(anyhow!("You must peer with a link first"))
         }
     }
+    // Withdraws the Requests sent for frames that never came back, so brokers
+    // along the path stop waiting on them. Frames never asked for aren't
+    // pending anywhere and are left alone.
+    fn cancel(&self, hbfi_seek: HBFI, sent: &BTreeSet<u64>) -> Result<()> {
+        match self {
+            TxRx::Initialized { ops, link_id, p2l_tx, responses, .. } => {
+                let arrived: BTreeSet<u64> = match responses.lock().unwrap().get(&HBFIExcludeFrame(hbfi_seek.clone())) {
+                    Some(set) => set.iter().map(|nw| nw.0.hbfi().frm).collect(),
+                    None => BTreeSet::new(),
+                };
+                for frm in sent.difference(&arrived) {
+                    let lp = LinkPacket::new(link_id.reply_to()?, NarrowWaistPacket::cancel(hbfi_seek.clone().offset(*frm))?);
+                    let ilp = InterLinkPacket::new(link_id.clone(), lp);
+                    trace!("\t\t|  protocol-to-link CANCEL");
+                    ops.message_from(self.label()?);
+                    match p2l_tx.send(ilp) {
+                        Ok(_) => {},
+                        Err(e) => error!("protocol send error {:?}", e),
+                    }
+                }
+                Ok(())
+            },
+            TxRx::Inert => Err(anyhow!("You must peer with a link first"))
+        }
+    }
+    fn nacked(&self, hbfi_seek: &HBFI) -> Option<Nacked> {
+        match self {
+            TxRx::Initialized { nacks, .. } => nacks.lock().unwrap().remove(&HBFIExcludeFrame(hbfi_seek.clone())),
+            TxRx::Inert => None,
+        }
+    }
     fn reconstruct_responses(&self, hbfi_seek: HBFI, start: u64, end: u64) -> Result<Vec<Vec<u8>>> {
         match self {
             TxRx::Initialized { responses, protocol_sid, .. } => {
@@ -143,8 +235,8 @@ impl TxRx {
                 let responses_ref = responses_mutex.lock().unwrap();
                 //debug!("{:#?}", responses_ref);
                 if let Some(set) = responses_ref.get(&HBFIExcludeFrame(hbfi_seek.clone())) {
-                    let start_nw = NarrowWaistPacket::request(hbfi_seek.clone().offset(start))?;
-                    let end_nw = NarrowWaistPacket::request(hbfi_seek.clone().offset(end))?;
+                    let start_nw = NarrowWaistPacket::request(hbfi_seek.clone().offset(start), RequestOptions::default())?;
+                    let end_nw = NarrowWaistPacket::request(hbfi_seek.clone().offset(end), RequestOptions::default())?;
                     for nw in set.range(&NarrowWaistPacketReqEqRes(start_nw)..=&NarrowWaistPacketReqEqRes(end_nw)) {
                         let chunk = nw.0.data(protocol_sid.clone())?;
                         reconstruct.push(chunk);
@@ -179,7 +271,7 @@ impl TxRx {
                             },
                             Reliability::ReliableSequenced(sequence_head) => {
                                 *congestion_window_size = 1;
-                                let sequence_head_nw = NarrowWaistPacketReqEqRes(NarrowWaistPacket::request(hbfi_seek.clone().offset(sequence_head))?);
+                                let sequence_head_nw = NarrowWaistPacketReqEqRes(NarrowWaistPacket::request(hbfi_seek.clone().offset(sequence_head), RequestOptions::default())?);
                                 for nw in failed {
                                     if nw > sequence_head_nw {
                                         pending_queue.insert(nw);
@@ -217,11 +309,11 @@ impl TxRx {
         , (timeout_tx, timeout_rx): (Sender<()>, Receiver<()>)
         ) -> Result<AIMD> {
         match self {
-            TxRx::Initialized { ops, link_id, p2l_tx, responses, .. } => {
+            TxRx::Initialized { ops, link_id, p2l_tx, responses, nacks, .. } => {
                 let congestion_window_guard = Arc::clone(&congestion_window);
                 let congestion_window_ref = congestion_window_guard.lock().unwrap();
                 for nw in congestion_window_ref.iter() {
-                    let lp = LinkPacket::new(link_id.reply_to()?, nw.0.clone());
+                    let lp = LinkPacket::new(link_id.reply_to()?, self.request_packet(nw.0.hbfi(), Duration::from_millis(*window_timeout))?);
                     let ilp = InterLinkPacket::new(link_id.clone(), lp);
                     trace!("\t\t|  protocol-to-link");
                     ops.message_from(self.label()?);
@@ -233,6 +325,7 @@ impl TxRx {
                 }
                 drop(congestion_window_ref);
                 let responses_to_thread = Arc::clone(&responses);
+                let nacks_to_thread = Arc::clone(&nacks);
                 let congestion_window_to_thread = Arc::clone(&congestion_window);
                 let reliability_to_thread = Arc::clone(&reliability);
                 std::thread::spawn(move || {
@@ -244,6 +337,22 @@ impl TxRx {
                                 let mut responses_ref = responses_mutex.lock().unwrap();
                                 match nw.clone() {
                                     NarrowWaistPacket::Request { .. } => { continue },
+                                    NarrowWaistPacket::Announce { .. } => { continue },
+                                    NarrowWaistPacket::Digest { .. } => { continue },
+                                    NarrowWaistPacket::Credit { .. } => { continue },
+                                    NarrowWaistPacket::Cancel { .. } => { continue },
+                                    // no point waiting on the rest once the producer refuses one
+                                    NarrowWaistPacket::Nack { hbfi, reason, .. } => {
+                                        if !responses_ref.contains_key(&HBFIExcludeFrame(hbfi.clone())) {
+                                            continue
+                                        }
+                                        nacks_to_thread.lock().unwrap().insert(HBFIExcludeFrame(hbfi.clone()), Nacked { hbfi, reason });
+                                        match timeout_tx.send(()) {
+                                            Ok(_) => {},
+                                            Err(SendError(_)) => { debug!("TxRx timeout mechanism failed") },
+                                        }
+                                        break
+                                    },
                                     NarrowWaistPacket::Response { hbfi, .. } => {
                                         let mut reliability_to_thread_ref = reliability_to_thread.lock().unwrap();
                                         match *reliability_to_thread_ref {
@@ -327,30 +436,42 @@ impl TxRx {
             let mut pending_queue: BTreeSet<NarrowWaistPacketReqEqRes> = BTreeSet::new();
             let congestion_window: Arc<Mutex<BTreeSet<NarrowWaistPacketReqEqRes>>> = Arc::new(Mutex::new(BTreeSet::new()));
             let mut congestion_window_size: u64 = 1;
+            // the frames Requests went out for
+            let mut sent: BTreeSet<u64> = BTreeSet::new();
             for counter in start..=end {
                 let hbfi_req = hbfi_seek.clone().offset(counter);
-                let nw = NarrowWaistPacket::request(hbfi_req)?;
+                let nw = NarrowWaistPacket::request(hbfi_req, RequestOptions::default())?;
                 pending_queue.insert(NarrowWaistPacketReqEqRes(nw));
             }
             let timeout_txrx = unbounded::<()>();
-            loop {
-                if retries <= &mut 0 { break }
-                if pending_queue.len() <= 0 { break }
-                let congestion_window_guard = Arc::clone(&congestion_window);
-                let mut congestion_window_ref = congestion_window_guard.lock().unwrap();
-                congestion_window_ref.clear();
-                for _ in 0..congestion_window_size {
-                    match pending_queue.pop_first() {
-                        Some(nw) => {
-                            congestion_window_ref.insert(nw);
-                        },
-                        None => continue,
+            let outcome = (|| -> Result<()> {
+                loop {
+                    if retries <= &mut 0 { break }
+                    if pending_queue.len() <= 0 { break }
+                    let congestion_window_guard = Arc::clone(&congestion_window);
+                    let mut congestion_window_ref = congestion_window_guard.lock().unwrap();
+                    congestion_window_ref.clear();
+                    for _ in 0..congestion_window_size {
+                        match pending_queue.pop_first() {
+                            Some(nw) => {
+                                sent.insert(nw.0.hbfi().frm);
+                                congestion_window_ref.insert(nw);
+                            },
+                            None => continue,
+                        }
+                    }
+                    drop(congestion_window_ref);
+                    let aimd = self.send_and_receive(Arc::clone(&congestion_window), hbfi_seek.clone(), Arc::clone(&reliability), rx.clone(), retries, window_timeout, timeout_txrx.clone())?;
+                    if let Some(nacked) = self.nacked(&hbfi_seek) {
+                        return Err(nacked.into())
                     }
+                    let _r = self.process_aimd(hbfi_seek.clone(), aimd, Arc::clone(&reliability), &mut congestion_window_size, &mut pending_queue);
                 }
-                drop(congestion_window_ref);
-                let aimd = self.send_and_receive(Arc::clone(&congestion_window), hbfi_seek.clone(), Arc::clone(&reliability), rx.clone(), retries, window_timeout, timeout_txrx.clone())?;
-                let _r = self.process_aimd(hbfi_seek.clone(), aimd, Arc::clone(&reliability), &mut congestion_window_size, &mut pending_queue);
-            }
+                Ok(())
+            })();
+            // the frames that never came back are withdrawn however it ended
+            self.cancel(hbfi_seek.clone(), &sent)?;
+            outcome?;
             self.reconstruct_responses(hbfi_seek, start, end)
     }
     pub fn unreliable_sequenced_request(&mut self, hbfi_seek: HBFI, start: u64, end: u64, retries: &mut u64, window_timeout: &mut u64) -> Result<Vec<Vec<u8>>> {
@@ -454,4 +575,42 @@ impl TxRx {
             TxRx::Inert => Err(anyhow!("You must peer with a link first"))
         }
     }
+    // Turns a Request down, so the requester stops waiting on it at once.
+    pub fn nack(&self, hbfi: HBFI, reason: NackReason) -> Result<()> {
+        match self {
+            TxRx::Initialized { ref p2l_tx, ref protocol_sid, ref link_id, ref ops, ref label, .. } => {
+                trace!("\t\t|  NACK {}", reason);
+                let nw = NarrowWaistPacket::nack(protocol_sid.clone(), hbfi, reason)?;
+                let lp = LinkPacket::new(link_id.reply_to()?, nw);
+                let ilp = InterLinkPacket::new(link_id.clone(), lp);
+                trace!("\t\t|  protocol-to-link");
+                ops.message_from(label.clone());
+                match p2l_tx.send(ilp) {
+                    Ok(_) => {},
+                    Err(e) => error!("protocol send error {:?}", e),
+                }
+                Ok(())
+            },
+            TxRx::Inert => Err(anyhow!("You must peer with a link first"))
+        }
+    }
+    // Tells brokers this protocol answers Requests for app, so they route
+    // toward it before Bayes has learnt anything.
+    pub fn announce(&self, app: &str) -> Result<()> {
+        match self {
+            TxRx::Initialized { ref p2l_tx, ref protocol_sid, ref link_id, ref ops, ref label, .. } => {
+                let nw = NarrowWaistPacket::announce(protocol_sid.clone(), app)?;
+                let lp = LinkPacket::new(link_id.reply_to()?, nw);
+                let ilp = InterLinkPacket::new(link_id.clone(), lp);
+                trace!("\t\t|  protocol-to-link");
+                ops.message_from(label.clone());
+                match p2l_tx.send(ilp) {
+                    Ok(_) => {},
+                    Err(e) => error!("protocol send error {:?}", e),
+                }
+                Ok(())
+            },
+            TxRx::Inert => Err(anyhow!("You must peer with a link first"))
+        }
+    }
 }
@@ -0,0 +1,109 @@
+use {
+    anyhow::{Result, anyhow},
+    copernica_protocols::{Echo, Protocol},
+    copernica_packets::{PrivateIdentityInterface},
+    copernica_common::{Operations},
+    copernica_broker::{Broker, Role, Action, Skipped},
+    copernica_links::{Link},
+    crate::{mpsc_pair, link_ids},
+    scaffolding::{ group, single, Ordering, TestTree, setting, settings::Timeout},
+    std::{
+        time::Duration,
+    },
+};
+pub fn broker_roles(ordering: Ordering) -> TestTree {
+    group!(
+        format!("Broker roles, ordering with {:?}", ordering),
+        ordering,
+        [
+            setting!(Timeout(Duration::from_secs(10))),
+            single!(|| { mixed_roles() }),
+        ]
+    )
+}
+// echo_protocol_0 - router_0 - router_1 - router_2 - echo_protocol_1
+//                   (leaf)     (core) |  (gateway)
+//                                   router_3 - echo_protocol_2
+//                                    (leaf)
+//
+// echo_protocol_0 pings echo_protocol_1. The core router_1 caches nothing
+// while the leaf and gateway on either side do, and router_3 never passes
+// the Requests router_1 sends it on to echo_protocol_2.
+pub fn mixed_roles() -> Result<()> {
+    let ops = Operations::turned_off();
+    let mut broker0 = Broker::new(ops.label("router_0"), Role::Leaf);
+    let mut broker1 = Broker::new(ops.label("router_1"), Role::Core);
+    let mut broker2 = Broker::new(ops.label("router_2"), Role::Gateway);
+    let mut broker3 = Broker::new(ops.label("router_3"), Role::Leaf);
+    let mut echo_protocol0: Echo = Protocol::new(PrivateIdentityInterface::new_key(), ops.label("echo_protocol_0"));
+    let echo_protocol_sid1 = PrivateIdentityInterface::new_key();
+    let mut echo_protocol1: Echo = Protocol::new(echo_protocol_sid1.clone(), ops.label("echo_protocol_1"));
+    let mut echo_protocol2: Echo = Protocol::new(PrivateIdentityInterface::new_key(), ops.label("echo_protocol_2"));
+
+    let mut links = vec![];
+    let (id0, id1) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_0", "link_1"), echo_protocol0.peer_with_link(id0.clone())?, broker0.peer_with_link(id1.clone())?, (id0, id1))?);
+    let (id2, id3) = link_ids();
+    broker0.upstream(id2.clone());
+    links.push(mpsc_pair(&ops, ("link_2", "link_3"), broker0.peer_with_link(id2.clone())?, broker1.peer_with_link(id3.clone())?, (id2, id3))?);
+    let (id4, id5) = link_ids();
+    broker2.upstream(id5.clone());
+    links.push(mpsc_pair(&ops, ("link_4", "link_5"), broker1.peer_with_link(id4.clone())?, broker2.peer_with_link(id5.clone())?, (id4, id5.clone()))?);
+    let (id6, id7) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_6", "link_7"), broker2.peer_with_link(id6.clone())?, echo_protocol1.peer_with_link(id7.clone())?, (id6.clone(), id7))?);
+    let (id8, id9) = link_ids();
+    broker3.upstream(id9.clone());
+    links.push(mpsc_pair(&ops, ("link_8", "link_9"), broker1.peer_with_link(id8.clone())?, broker3.peer_with_link(id9.clone())?, (id8, id9.clone()))?);
+    let (id10, id11) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_10", "link_11"), broker3.peer_with_link(id10.clone())?, echo_protocol2.peer_with_link(id11.clone())?, (id10.clone(), id11))?);
+
+    echo_protocol0.run()?;
+    echo_protocol1.run()?;
+    echo_protocol2.run()?;
+    broker0.run()?;
+    broker1.run()?;
+    broker2.run()?;
+    broker3.run()?;
+    for (left, right) in &mut links {
+        left.run()?;
+        right.run()?;
+    }
+    let echo_protocol_pid1 = echo_protocol_sid1.public_id();
+    let response = std::thread::spawn(move || {
+        echo_protocol0.unreliable_sequenced_cleartext_ping(echo_protocol_pid1)
+    }).join().expect("failed to extract data from JoinHandle")?;
+    if response != "pingpong" {
+        return Err(anyhow!("the ping returned {}", response))
+    }
+    if broker1.cache_stats().entries != 0 {
+        return Err(anyhow!("the core router cached {} Responses", broker1.cache_stats().entries))
+    }
+    if broker0.cache_stats().entries == 0 || broker2.cache_stats().entries == 0 {
+        return Err(anyhow!("the leaf and gateway routers should have cached the pongs"))
+    }
+    let from_upstream: Vec<_> = broker3.decisions()
+        .into_iter()
+        .filter(|decision| decision.from == id9)
+        .collect();
+    if from_upstream.is_empty() {
+        return Err(anyhow!("the core router sent router_3 no Requests"))
+    }
+    for decision in from_upstream {
+        if let Action::Forwarded(_) = decision.action {
+            return Err(anyhow!("the leaf router_3 forwarded a Request from upstream: {}", decision))
+        }
+        let skipped = decision.candidates.iter().find(|candidate| candidate.linkid == id10).and_then(|candidate| candidate.skipped);
+        if decision.action == Action::Unroutable && skipped != Some(Skipped::Role) {
+            return Err(anyhow!("router_3 passed over echo_protocol_2 for another reason: {}", decision))
+        }
+    }
+    for decision in broker2.decisions().into_iter().filter(|decision| decision.from == id5) {
+        if let Action::Forwarded(links) = &decision.action {
+            if *links == vec![id6.clone()] {
+                continue;
+            }
+            return Err(anyhow!("the gateway router_2 routed a Request from upstream unexpectedly: {}", decision))
+        }
+    }
+    Ok(())
+}
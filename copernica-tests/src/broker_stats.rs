@@ -0,0 +1,71 @@
+use {
+    anyhow::{Result, anyhow},
+    copernica_protocols::{Echo, Protocol},
+    copernica_packets::{PrivateIdentityInterface},
+    copernica_common::{Operations},
+    copernica_broker::{Broker, Role},
+    copernica_links::{Link},
+    crate::{mpsc_pair, link_ids},
+    scaffolding::{ group, single, Ordering, TestTree, setting, settings::Timeout},
+    std::{
+        time::Duration,
+    },
+};
+pub fn broker_stats(ordering: Ordering) -> TestTree {
+    group!(
+        format!("Broker stats, ordering with {:?}", ordering),
+        ordering,
+        [
+            setting!(Timeout(Duration::from_secs(10))),
+            single!(|| { stats_follow_a_ping() }),
+        ]
+    )
+}
+// echo_protocol_0 - router_0 - echo_protocol_1
+//
+// Every Request echo_protocol_0 sends is counted in on one link and out on
+// the other, and the Responses the other way round.
+pub fn stats_follow_a_ping() -> Result<()> {
+    let ops = Operations::turned_off();
+    let mut broker0 = Broker::new(ops.label("router_0"), Role::Mesh);
+    let mut echo_protocol0: Echo = Protocol::new(PrivateIdentityInterface::new_key(), ops.label("echo_protocol_0"));
+    let echo_protocol_sid1 = PrivateIdentityInterface::new_key();
+    let mut echo_protocol1: Echo = Protocol::new(echo_protocol_sid1.clone(), ops.label("echo_protocol_1"));
+    let mut links = vec![];
+    let (id0, id1) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_0", "link_1"), echo_protocol0.peer_with_link(id0.clone())?, broker0.peer_with_link(id1.clone())?, (id0, id1.clone()))?);
+    let (id2, id3) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_2", "link_3"), broker0.peer_with_link(id2.clone())?, echo_protocol1.peer_with_link(id3.clone())?, (id2.clone(), id3))?);
+    echo_protocol0.run()?;
+    echo_protocol1.run()?;
+    broker0.run()?;
+    for (left, right) in &mut links {
+        left.run()?;
+        right.run()?;
+    }
+    let subscription = broker0.subscribe(Duration::from_millis(50));
+    let echo_protocol_pid1 = echo_protocol_sid1.public_id();
+    let response = std::thread::spawn(move || {
+        echo_protocol0.unreliable_sequenced_cleartext_ping(echo_protocol_pid1)
+    }).join().expect("failed to extract data from JoinHandle")?;
+    if response != "pingpong" {
+        return Err(anyhow!("the ping returned {}", response))
+    }
+    let stats = broker0.stats();
+    let consumer = stats.link(&id1).ok_or_else(|| anyhow!("no stats for the consumer's link"))?;
+    let producer = stats.link(&id2).ok_or_else(|| anyhow!("no stats for the producer's link"))?;
+    if stats.requests.forwarded == 0 || consumer.received < stats.requests.forwarded {
+        return Err(anyhow!("{} Requests forwarded from {} received", stats.requests.forwarded, consumer.received))
+    }
+    if producer.queue.sent < stats.requests.forwarded || producer.received == 0 || consumer.queue.sent < producer.received {
+        return Err(anyhow!("packets went missing between the links: {:?}", stats.links))
+    }
+    if stats.cache.entries == 0 || stats.bayes_bfis == 0 || stats.decode_failures != 0 || stats.router_errors != 0 {
+        return Err(anyhow!("unexpected stats: {:?}", stats))
+    }
+    let published = subscription.recv_timeout(Duration::from_secs(1))?;
+    if published.links.is_empty() {
+        return Err(anyhow!("the subscription published no links"))
+    }
+    Ok(())
+}
@@ -0,0 +1,106 @@
+use {
+    anyhow::{Result, anyhow},
+    copernica_protocols::{Echo, Protocol},
+    copernica_packets::{LinkId, HBFI, PrivateIdentityInterface, PublicIdentityInterface},
+    copernica_common::{Operations},
+    copernica_broker::{Broker, Role, Action},
+    copernica_links::{Link},
+    crate::{mpsc_pair, link_ids},
+    scaffolding::{ group, single, Ordering, TestTree, setting, settings::Timeout},
+    std::{
+        time::Duration,
+    },
+};
+pub fn cache_digests(ordering: Ordering) -> TestTree {
+    group!(
+        format!("Cache digests, ordering with {:?}", ordering),
+        ordering,
+        [
+            setting!(Timeout(Duration::from_secs(10))),
+            single!(|| { prefer_neighbour_with_cached_response() }),
+        ]
+    )
+}
+//                       echo_protocol_2
+//                              |
+// echo_protocol_0 - router_1 - router_2
+//                         \    /
+//                        router_0 - echo_protocol_1
+//
+// echo_protocol_2 pings first so router_1 and router_2 cache the pongs,
+// router_0 caches nothing. Only router_2 sends digests, so router_0 ranks
+// router_2 above router_1 for every Request of the second ping.
+pub fn prefer_neighbour_with_cached_response() -> Result<()> {
+    let ops = Operations::turned_off();
+    let mut broker0 = Broker::new(ops.label("router_0"), Role::Mesh);
+    let mut broker1 = Broker::new(ops.label("router_1"), Role::Mesh);
+    let mut broker2 = Broker::new(ops.label("router_2"), Role::Mesh);
+    broker0.cache_capacity(0);
+    broker2.cache_digests(Duration::from_millis(100));
+    let echo_protocol_sid0 = PrivateIdentityInterface::new_key();
+    let mut echo_protocol0: Echo = Protocol::new(echo_protocol_sid0.clone(), ops.label("echo_protocol_0"));
+    let mut echo_protocol1: Echo = Protocol::new(PrivateIdentityInterface::new_key(), ops.label("echo_protocol_1"));
+    let mut echo_protocol2: Echo = Protocol::new(PrivateIdentityInterface::new_key(), ops.label("echo_protocol_2"));
+
+    let mut links = vec![];
+    let (id0, id1) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_0", "link_1"), broker1.peer_with_link(id0.clone())?, echo_protocol0.peer_with_link(id1.clone())?, (id0, id1))?);
+    let (id2, id3) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_2", "link_3"), broker1.peer_with_link(id2.clone())?, broker2.peer_with_link(id3.clone())?, (id2, id3))?);
+    let (id4, id5) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_4", "link_5"), broker2.peer_with_link(id4.clone())?, echo_protocol2.peer_with_link(id5.clone())?, (id4, id5))?);
+    let (id6, id7) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_6", "link_7"), broker0.peer_with_link(id6.clone())?, broker2.peer_with_link(id7.clone())?, (id6.clone(), id7))?);
+    let (id8, id9) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_8", "link_9"), broker0.peer_with_link(id8.clone())?, broker1.peer_with_link(id9.clone())?, (id8.clone(), id9))?);
+    let (id10, id11) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_10", "link_11"), broker0.peer_with_link(id10.clone())?, echo_protocol1.peer_with_link(id11.clone())?, (id10.clone(), id11))?);
+
+    echo_protocol0.run()?;
+    echo_protocol1.run()?;
+    echo_protocol2.run()?;
+    broker0.run()?;
+    broker1.run()?;
+    broker2.run()?;
+    for (left, right) in &mut links {
+        left.run()?;
+        right.run()?;
+    }
+    let echo_protocol_pid0 = echo_protocol_sid0.public_id();
+    let first_response = std::thread::spawn(move || {
+        echo_protocol2.unreliable_sequenced_cleartext_ping(echo_protocol_pid0)
+    }).join().expect("failed to extract data from JoinHandle")?;
+    if first_response != "pingpong" {
+        return Err(anyhow!("the first ping returned {}", first_response))
+    }
+    // long enough for router_2 to send a digest holding the pongs
+    std::thread::sleep(Duration::from_millis(500));
+    let echo_protocol_pid0 = echo_protocol_sid0.public_id();
+    let second_response = std::thread::spawn(move || {
+        echo_protocol1.unreliable_sequenced_cleartext_ping(echo_protocol_pid0)
+    }).join().expect("failed to extract data from JoinHandle")?;
+    if second_response != "pingpong" {
+        return Err(anyhow!("the second ping returned {}", second_response))
+    }
+    let hbfi = HBFI::new(PublicIdentityInterface::Absent, echo_protocol_sid0.public_id(), "echo", "echo", "echo", "unreliable_sequenced_echo")?;
+    for frm in 0..8 {
+        let decisions: Vec<_> = broker0.explain(&hbfi.clone().offset(frm))
+            .into_iter()
+            .filter(|decision| decision.from == id10)
+            .collect();
+        if decisions.len() != 1 {
+            return Err(anyhow!("frame {} has {} routing decisions from echo_protocol_1", frm, decisions.len()))
+        }
+        let decision = &decisions[0];
+        match &decision.action {
+            Action::Forwarded(links) if links.first() == Some(&id6) => {},
+            _ => return Err(anyhow!("frame {} was routed unexpectedly: {}", frm, decision)),
+        }
+        let weight = |link_id: &LinkId| decision.candidates.iter().find(|candidate| candidate.linkid == *link_id).map(|candidate| candidate.weight);
+        match (weight(&id6), weight(&id8)) {
+            (Some(holder), Some(other)) if holder > other => {},
+            _ => return Err(anyhow!("frame {} didn't rank router_2 above router_1: {}", frm, decision)),
+        }
+    }
+    Ok(())
+}
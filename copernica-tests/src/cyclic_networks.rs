@@ -0,0 +1,147 @@
+use {
+    anyhow::{Result, anyhow},
+    copernica_protocols::{Echo, Protocol},
+    copernica_packets::{PrivateIdentityInterface},
+    copernica_common::{Operations, LogEntry},
+    copernica_broker::{Broker, Role},
+    copernica_links::{Link},
+    crate::{process_network_at_most, mpsc_pair, link_ids},
+    scaffolding::{ group, single, Ordering, TestTree, setting, settings::Timeout},
+    crossbeam_channel::{unbounded},
+    std::{
+        time::Duration,
+        collections::HashMap,
+    },
+};
+pub fn cyclic_networks(ordering: Ordering) -> TestTree {
+    group!(
+        format!("Cyclic networks, ordering with {:?}", ordering),
+        ordering,
+        [
+            setting!(Timeout(Duration::from_secs(5))),
+            single!(|| { two_protocol_three_broker_triangle() }),
+            single!(|| { two_protocol_four_broker_ring() }),
+        ]
+    )
+}
+// Flooding around a cycle delivers a Request to some brokers more than once
+// and which copy wins the race isn't deterministic, hence only the upper
+// bounds are checked. Without loop detection the counts grow unbounded.
+pub fn two_protocol_three_broker_triangle() -> Result<()> {
+    let router_0 = "router_0";
+    let router_1 = "router_1";
+    let router_2 = "router_2";
+    let echo_protocol_0 = "echo_protocol_0";
+    let echo_protocol_1 = "echo_protocol_1";
+    let (sender, receiver) = unbounded::<LogEntry>();
+    let actual_behaviour = Operations::turned_on(sender);
+    let mut broker0 = Broker::new(actual_behaviour.label(router_0.clone()), Role::Mesh);
+    let mut broker1 = Broker::new(actual_behaviour.label(router_1.clone()), Role::Mesh);
+    let mut broker2 = Broker::new(actual_behaviour.label(router_2.clone()), Role::Mesh);
+    let echo_protocol_sid0 = PrivateIdentityInterface::new_key();
+    let echo_protocol_sid1 = PrivateIdentityInterface::new_key();
+    let mut echo_protocol0: Echo = Protocol::new(echo_protocol_sid0.clone(), actual_behaviour.label(echo_protocol_0.clone()));
+    let mut echo_protocol1: Echo = Protocol::new(echo_protocol_sid1.clone(), actual_behaviour.label(echo_protocol_1.clone()));
+    let mut links = vec![];
+    let (id0, id1) = link_ids();
+    links.push(mpsc_pair(&actual_behaviour, ("link_0", "link_1"), broker0.peer_with_link(id0.clone())?, echo_protocol0.peer_with_link(id1.clone())?, (id0, id1))?);
+    let (id2, id3) = link_ids();
+    links.push(mpsc_pair(&actual_behaviour, ("link_2", "link_3"), broker0.peer_with_link(id2.clone())?, broker1.peer_with_link(id3.clone())?, (id2, id3))?);
+    let (id4, id5) = link_ids();
+    links.push(mpsc_pair(&actual_behaviour, ("link_4", "link_5"), broker1.peer_with_link(id4.clone())?, broker2.peer_with_link(id5.clone())?, (id4, id5))?);
+    let (id6, id7) = link_ids();
+    links.push(mpsc_pair(&actual_behaviour, ("link_6", "link_7"), broker2.peer_with_link(id6.clone())?, broker0.peer_with_link(id7.clone())?, (id6, id7))?);
+    let (id8, id9) = link_ids();
+    links.push(mpsc_pair(&actual_behaviour, ("link_8", "link_9"), broker2.peer_with_link(id8.clone())?, echo_protocol1.peer_with_link(id9.clone())?, (id8, id9))?);
+    let mut upper_bounds: HashMap<LogEntry, i32> = HashMap::new();
+    upper_bounds.insert(LogEntry::forward_request_upstream(router_0.clone()), 8);
+    upper_bounds.insert(LogEntry::forward_request_upstream(router_1.clone()), 8);
+    upper_bounds.insert(LogEntry::forward_request_upstream(router_2.clone()), 8);
+    upper_bounds.insert(LogEntry::found_response_upstream(echo_protocol_0.clone()), 8);
+    upper_bounds.insert(LogEntry::response_arrived_downstream(echo_protocol_1.clone()), 8);
+
+    echo_protocol0.run()?;
+    echo_protocol1.run()?;
+    broker0.run()?;
+    broker1.run()?;
+    broker2.run()?;
+    for (left, right) in &mut links {
+        left.run()?;
+        right.run()?;
+    }
+    let response = std::thread::spawn(move || {
+        let data: String = echo_protocol1.unreliable_sequenced_cleartext_ping(echo_protocol_sid0.public_id()).unwrap();
+        actual_behaviour.end();
+        data
+    });
+    process_network_at_most(upper_bounds, receiver)?;
+    let actual_response = response.join().expect("failed to extract data from JoinHandle");
+    let expected_response = "pingpong".to_string();
+    if actual_response != expected_response{
+        Err(anyhow!("actual returned data (1st under) didn't match expected returned data (2nd under):\n{}\n{}", actual_response, expected_response))
+    } else {
+        Ok(())
+    }
+}
+pub fn two_protocol_four_broker_ring() -> Result<()> {
+    let router_0 = "router_0";
+    let router_1 = "router_1";
+    let router_2 = "router_2";
+    let router_3 = "router_3";
+    let echo_protocol_0 = "echo_protocol_0";
+    let echo_protocol_1 = "echo_protocol_1";
+    let (sender, receiver) = unbounded::<LogEntry>();
+    let actual_behaviour = Operations::turned_on(sender);
+    let mut broker0 = Broker::new(actual_behaviour.label(router_0.clone()), Role::Mesh);
+    let mut broker1 = Broker::new(actual_behaviour.label(router_1.clone()), Role::Mesh);
+    let mut broker2 = Broker::new(actual_behaviour.label(router_2.clone()), Role::Mesh);
+    let mut broker3 = Broker::new(actual_behaviour.label(router_3.clone()), Role::Mesh);
+    let echo_protocol_sid0 = PrivateIdentityInterface::new_key();
+    let echo_protocol_sid1 = PrivateIdentityInterface::new_key();
+    let mut echo_protocol0: Echo = Protocol::new(echo_protocol_sid0.clone(), actual_behaviour.label(echo_protocol_0.clone()));
+    let mut echo_protocol1: Echo = Protocol::new(echo_protocol_sid1.clone(), actual_behaviour.label(echo_protocol_1.clone()));
+    let mut links = vec![];
+    let (id0, id1) = link_ids();
+    links.push(mpsc_pair(&actual_behaviour, ("link_0", "link_1"), broker0.peer_with_link(id0.clone())?, echo_protocol0.peer_with_link(id1.clone())?, (id0, id1))?);
+    let (id2, id3) = link_ids();
+    links.push(mpsc_pair(&actual_behaviour, ("link_2", "link_3"), broker0.peer_with_link(id2.clone())?, broker1.peer_with_link(id3.clone())?, (id2, id3))?);
+    let (id4, id5) = link_ids();
+    links.push(mpsc_pair(&actual_behaviour, ("link_4", "link_5"), broker1.peer_with_link(id4.clone())?, broker2.peer_with_link(id5.clone())?, (id4, id5))?);
+    let (id6, id7) = link_ids();
+    links.push(mpsc_pair(&actual_behaviour, ("link_6", "link_7"), broker2.peer_with_link(id6.clone())?, broker3.peer_with_link(id7.clone())?, (id6, id7))?);
+    let (id8, id9) = link_ids();
+    links.push(mpsc_pair(&actual_behaviour, ("link_8", "link_9"), broker3.peer_with_link(id8.clone())?, broker0.peer_with_link(id9.clone())?, (id8, id9))?);
+    let (id10, id11) = link_ids();
+    links.push(mpsc_pair(&actual_behaviour, ("link_10", "link_11"), broker2.peer_with_link(id10.clone())?, echo_protocol1.peer_with_link(id11.clone())?, (id10, id11))?);
+    let mut upper_bounds: HashMap<LogEntry, i32> = HashMap::new();
+    upper_bounds.insert(LogEntry::forward_request_upstream(router_0.clone()), 8);
+    upper_bounds.insert(LogEntry::forward_request_upstream(router_1.clone()), 8);
+    upper_bounds.insert(LogEntry::forward_request_upstream(router_2.clone()), 8);
+    upper_bounds.insert(LogEntry::forward_request_upstream(router_3.clone()), 8);
+    upper_bounds.insert(LogEntry::found_response_upstream(echo_protocol_0.clone()), 8);
+    upper_bounds.insert(LogEntry::response_arrived_downstream(echo_protocol_1.clone()), 8);
+
+    echo_protocol0.run()?;
+    echo_protocol1.run()?;
+    broker0.run()?;
+    broker1.run()?;
+    broker2.run()?;
+    broker3.run()?;
+    for (left, right) in &mut links {
+        left.run()?;
+        right.run()?;
+    }
+    let response = std::thread::spawn(move || {
+        let data: String = echo_protocol1.unreliable_sequenced_cleartext_ping(echo_protocol_sid0.public_id()).unwrap();
+        actual_behaviour.end();
+        data
+    });
+    process_network_at_most(upper_bounds, receiver)?;
+    let actual_response = response.join().expect("failed to extract data from JoinHandle");
+    let expected_response = "pingpong".to_string();
+    if actual_response != expected_response{
+        Err(anyhow!("actual returned data (1st under) didn't match expected returned data (2nd under):\n{}\n{}", actual_response, expected_response))
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,72 @@
+use {
+    anyhow::{Result, anyhow},
+    copernica_protocols::{Echo, Protocol},
+    copernica_packets::{PrivateIdentityInterface},
+    copernica_common::{Operations},
+    copernica_broker::{Broker, Role, CreditPolicy},
+    copernica_links::{Link},
+    crate::{mpsc_pair, link_ids},
+    scaffolding::{ group, single, Ordering, TestTree, setting, settings::Timeout},
+    std::{
+        time::Duration,
+    },
+};
+pub fn flow_control(ordering: Ordering) -> TestTree {
+    group!(
+        format!("Flow control, ordering with {:?}", ordering),
+        ordering,
+        [
+            setting!(Timeout(Duration::from_secs(10))),
+            single!(|| { pace_forwarding_within_granted_credits() }),
+        ]
+    )
+}
+// echo_protocol_0 - router_0 - router_1 - echo_protocol_1
+//
+// Both brokers grant one Request and one Response at a time, so router_0
+// runs out of credit forwarding the burst of Requests in a ping and waits
+// on router_1's next grant, yet the ping still gets its pongs.
+pub fn pace_forwarding_within_granted_credits() -> Result<()> {
+    let ops = Operations::turned_off();
+    let policy = CreditPolicy { requests: 1, responses: 1, interval: Duration::from_millis(50) };
+    let mut broker0 = Broker::new(ops.label("router_0"), Role::Mesh);
+    let mut broker1 = Broker::new(ops.label("router_1"), Role::Mesh);
+    broker0.flow_control(policy.clone());
+    broker1.flow_control(policy);
+    let mut echo_protocol0: Echo = Protocol::new(PrivateIdentityInterface::new_key(), ops.label("echo_protocol_0"));
+    let echo_protocol_sid1 = PrivateIdentityInterface::new_key();
+    let mut echo_protocol1: Echo = Protocol::new(echo_protocol_sid1.clone(), ops.label("echo_protocol_1"));
+    let mut links = vec![];
+    let (id0, id1) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_0", "link_1"), echo_protocol0.peer_with_link(id0.clone())?, broker0.peer_with_link(id1.clone())?, (id0, id1))?);
+    let (id2, id3) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_2", "link_3"), broker0.peer_with_link(id2.clone())?, broker1.peer_with_link(id3.clone())?, (id2.clone(), id3))?);
+    let (id4, id5) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_4", "link_5"), broker1.peer_with_link(id4.clone())?, echo_protocol1.peer_with_link(id5.clone())?, (id4, id5))?);
+    echo_protocol0.run()?;
+    echo_protocol1.run()?;
+    broker0.run()?;
+    broker1.run()?;
+    for (left, right) in &mut links {
+        left.run()?;
+        right.run()?;
+    }
+    // long enough for router_1 to grant router_0 its first credits
+    std::thread::sleep(Duration::from_millis(200));
+    let echo_protocol_pid1 = echo_protocol_sid1.public_id();
+    let response = std::thread::spawn(move || {
+        echo_protocol0.unreliable_sequenced_cleartext_ping(echo_protocol_pid1)
+    }).join().expect("failed to extract data from JoinHandle")?;
+    if response != "pingpong" {
+        return Err(anyhow!("the ping returned {}", response))
+    }
+    let stats = broker0.stats();
+    let link = stats.link(&id2).ok_or_else(|| anyhow!("router_0 has no stats for the link to router_1"))?;
+    if link.credits.requests.is_none() {
+        return Err(anyhow!("router_1 never granted router_0 credit"))
+    }
+    if link.credits.starved == 0 {
+        return Err(anyhow!("router_0 forwarded the Requests without waiting on credit: {:?}", link.credits))
+    }
+    Ok(())
+}
@@ -9,6 +9,22 @@ mod reliable_sequenced_cleartext;
 mod reliable_ordered_cleartext;
 mod primitive_link_packet;
 mod networks;
+mod cyclic_networks;
+mod request_flood;
+mod warm_restart;
+mod routing_explanations;
+mod cache_digests;
+mod sharded_routers;
+mod broker_roles;
+mod broker_stats;
+mod prometheus_metrics;
+mod runtime_links;
+mod flow_control;
+mod request_cancellation;
+mod request_deadlines;
+mod nacks;
+mod response_freshness;
+mod prefetching;
 mod logger;
 pub use {
     unreliable_sequenced_cleartext::{unreliable_sequenced_cleartext_ping_pong},
@@ -19,14 +35,68 @@ pub use {
     reliable_ordered_cyphertext::{reliable_ordered_cyphertext_ping_pong},
     primitive_link_packet::{primitive_link_packet},
     networks::{basic_networks, },
+    cyclic_networks::{cyclic_networks},
+    request_flood::{request_flood},
+    warm_restart::{warm_restart},
+    routing_explanations::{routing_explanations},
+    cache_digests::{cache_digests},
+    sharded_routers::{sharded_routers},
+    broker_roles::{broker_roles},
+    broker_stats::{broker_stats},
+    prometheus_metrics::{prometheus_metrics},
+    runtime_links::{runtime_links},
+    flow_control::{flow_control},
+    request_cancellation::{request_cancellation},
+    request_deadlines::{request_deadlines},
+    nacks::{nacks},
+    response_freshness::{response_freshness},
+    prefetching::{prefetching},
     logger::{setup_logging},
 };
 use {
     anyhow::{Result, anyhow},
-    copernica_common::{LogEntry},
-    crossbeam_channel::{Receiver},
-    std::collections::HashMap,
+    copernica_common::{LogEntry, Operations},
+    copernica_packets::{LinkId, ReplyTo, InterLinkPacket, PrivateIdentityInterface, PublicIdentityInterface},
+    copernica_links::{Link, MpscChannel},
+    crossbeam_channel::{Sender, Receiver},
+    std::{
+        collections::HashMap,
+        time::{Duration, Instant},
+    },
 };
+// Links two brokers, or a broker and a protocol, over a cleartext mpsc channel.
+pub fn mpsc_pair(
+    ops: &Operations,
+    labels: (&str, &str),
+    left: (Sender<InterLinkPacket>, Receiver<InterLinkPacket>),
+    right: (Sender<InterLinkPacket>, Receiver<InterLinkPacket>),
+    ids: (LinkId, LinkId),
+) -> Result<(MpscChannel, MpscChannel)> {
+    let mut left: MpscChannel = Link::new(ids.0, ops.label(labels.0), left)?;
+    let mut right: MpscChannel = Link::new(ids.1, ops.label(labels.1), right)?;
+    left.female(right.male());
+    right.female(left.male());
+    Ok((left, right))
+}
+pub fn link_ids() -> (LinkId, LinkId) {
+    let link_sid0 = PrivateIdentityInterface::new_key();
+    let link_sid1 = PrivateIdentityInterface::new_key();
+    let link_id0 = LinkId::link_with_type(link_sid0, PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+    let link_id1 = LinkId::link_with_type(link_sid1, PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+    (link_id0, link_id1)
+}
+// Checks `done` every few milliseconds until it holds, false if it still
+// doesn't once `timeout` is up.
+pub fn eventually<F: FnMut() -> bool>(timeout: Duration, mut done: F) -> bool {
+    let deadline = Instant::now() + timeout;
+    while !done() {
+        if Instant::now() >= deadline {
+            return false
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    true
+}
 pub fn process_network(mut expected_behaviour: HashMap<LogEntry, i32>, receiver: Receiver<LogEntry>) -> Result<()> {
     let ref_expected_behaviour = expected_behaviour.clone();
     let mut error: String = "Corrections below:\n".into();
@@ -75,6 +145,15 @@ pub fn process_network(mut expected_behaviour: HashMap<LogEntry, i32>, receiver:
                     return Err(anyhow!("\"{}\" not present in expected_behaviour", label))
                 }
             },
+            LogEntry::Alert(label) => {
+                if let Some(count) = expected_behaviour.get_mut(&log_entry) {
+                    *count -= 1;
+                } else {
+                    return Err(anyhow!("\"{}\" not present in expected_behaviour", label))
+                }
+            },
+            // routing explanations are diagnostics, they aren't counted
+            LogEntry::Decision(_) => {},
             LogEntry::End => {
                 for (key, value) in &expected_behaviour {
                     if value != &0 {
@@ -93,3 +172,34 @@ pub fn process_network(mut expected_behaviour: HashMap<LogEntry, i32>, receiver:
     }
     Ok(())
 }
+// Like process_network but only checks that the listed entries occur no more
+// often than expected, for networks where the exact counts depend on timing.
+pub fn process_network_at_most(mut upper_bounds: HashMap<LogEntry, i32>, receiver: Receiver<LogEntry>) -> Result<()> {
+    let ref_upper_bounds = upper_bounds.clone();
+    let mut error: String = "Exceeded below:\n".into();
+    loop {
+        let log_entry = receiver.recv()?;
+        match log_entry {
+            LogEntry::End => {
+                for (key, value) in &upper_bounds {
+                    if value < &0 {
+                        if let Some(ref_value) = ref_upper_bounds.get(key) {
+                            error.push_str(&format!("{} {});\n", key, ref_value - value))
+                        }
+                    }
+                }
+                if error == "Exceeded below:\n".to_string() {
+                    break
+                } else {
+                    return Err(anyhow!("{}", error))
+                }
+            },
+            log_entry => {
+                if let Some(count) = upper_bounds.get_mut(&log_entry) {
+                    *count -= 1;
+                }
+            },
+        }
+    }
+    Ok(())
+}
@@ -5,7 +5,7 @@ use {
     copernica_tests::{
         unreliable_sequenced_cleartext_ping_pong, reliable_sequenced_cleartext_ping_pong, reliable_ordered_cleartext_ping_pong,
         unreliable_sequenced_cyphertext_ping_pong, reliable_sequenced_cyphertext_ping_pong, reliable_ordered_cyphertext_ping_pong,
-        primitive_link_packet, basic_networks, setup_logging,
+        primitive_link_packet, basic_networks, cyclic_networks, request_flood, warm_restart, routing_explanations, cache_digests, sharded_routers, broker_roles, broker_stats, prometheus_metrics, runtime_links, flow_control, request_cancellation, request_deadlines, nacks, response_freshness, prefetching, setup_logging,
     },
 };
 
@@ -25,6 +25,22 @@ fn main() -> Result<()> {
                     reliable_sequenced_cyphertext_ping_pong(Ordering::Any),
                     reliable_ordered_cyphertext_ping_pong(Ordering::Any),
                     basic_networks(Ordering::Any),
+                    cyclic_networks(Ordering::Any),
+                    request_flood(Ordering::Any),
+                    warm_restart(Ordering::Any),
+                    routing_explanations(Ordering::Any),
+                    cache_digests(Ordering::Any),
+                    sharded_routers(Ordering::Any),
+                    broker_roles(Ordering::Any),
+                    broker_stats(Ordering::Any),
+                    prometheus_metrics(Ordering::Any),
+                    runtime_links(Ordering::Any),
+                    flow_control(Ordering::Any),
+                    request_cancellation(Ordering::Any),
+                    request_deadlines(Ordering::Any),
+                    nacks(Ordering::Any),
+                    response_freshness(Ordering::Any),
+                    prefetching(Ordering::Any),
                 ]
             ),
         ]
@@ -0,0 +1,121 @@
+use {
+    anyhow::{Result, anyhow},
+    copernica_protocols::{Echo, Protocol, Nacked},
+    copernica_packets::{
+        ReplyTo, HBFI, InterLinkPacket, LinkPacket, NarrowWaistPacket, NackReason, RequestOptions,
+        PrivateIdentityInterface, PublicIdentityInterface,
+    },
+    copernica_common::{Operations, constants::BOUNDED_BUFFER_SIZE},
+    copernica_broker::{Broker, Role, Action},
+    copernica_links::{Link},
+    crate::{mpsc_pair, link_ids},
+    scaffolding::{ group, single, Ordering, TestTree, setting, settings::Timeout},
+    crossbeam_channel::{bounded, unbounded},
+    std::{
+        time::{Duration, Instant},
+    },
+};
+pub fn nacks(ordering: Ordering) -> TestTree {
+    group!(
+        format!("Nacks, ordering with {:?}", ordering),
+        ordering,
+        [
+            setting!(Timeout(Duration::from_secs(10))),
+            single!(|| { unknown_names_are_nacked() }),
+            single!(|| { nacks_end_requests_early() }),
+        ]
+    )
+}
+// requester - router_0 - echo_protocol_0
+//
+// echo_protocol_0 turns down a Request for an echo it doesn't know. The
+// Nack satisfies router_0's pending Request and is cached, so the same
+// Request asked again is answered by router_0 alone.
+pub fn unknown_names_are_nacked() -> Result<()> {
+    let ops = Operations::turned_off();
+    let mut broker0 = Broker::new(ops.label("router_0"), Role::Mesh);
+    let echo_protocol_sid0 = PrivateIdentityInterface::new_key();
+    let mut echo_protocol0: Echo = Protocol::new(echo_protocol_sid0.clone(), ops.label("echo_protocol_0"));
+    let mut links = vec![];
+    // the requester sits directly on link_0 instead of a protocol
+    let (r2l_tx, r2l_rx) = bounded::<InterLinkPacket>(BOUNDED_BUFFER_SIZE);
+    let (l2r_tx, l2r_rx) = unbounded::<InterLinkPacket>();
+    let (id0, id1) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_0", "link_1"), (l2r_tx, r2l_rx), broker0.peer_with_link(id1.clone())?, (id0.clone(), id1))?);
+    let (id2, id3) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_2", "link_3"), broker0.peer_with_link(id2.clone())?, echo_protocol0.peer_with_link(id3.clone())?, (id2, id3))?);
+    echo_protocol0.run()?;
+    broker0.run()?;
+    for (left, right) in &mut links {
+        left.run()?;
+        right.run()?;
+    }
+    let hbfi = HBFI::new(PublicIdentityInterface::Absent, echo_protocol_sid0.public_id(), "echo", "echo", "echo", "missing_echo")?;
+    for _ in 0..2 {
+        let nw = NarrowWaistPacket::request(hbfi.clone(), RequestOptions::default())?;
+        r2l_tx.send(InterLinkPacket::new(id0.clone(), LinkPacket::new(ReplyTo::Mpsc, nw)))?;
+        match l2r_rx.recv_timeout(Duration::from_secs(2))?.narrow_waist() {
+            NarrowWaistPacket::Nack { hbfi: nacked, reason: NackReason::UnknownName, .. } if nacked == hbfi => {},
+            nw => return Err(anyhow!("expected a nack, got {:?}", nw)),
+        }
+    }
+    let actions: Vec<Action> = broker0.explain(&hbfi).into_iter().map(|decision| decision.action).collect();
+    match actions.as_slice() {
+        [Action::Forwarded(_), Action::FoundNack] => {},
+        actions => return Err(anyhow!("router_0 took {:?}", actions)),
+    }
+    if !broker0.pending().is_empty() {
+        return Err(anyhow!("the Nack left the Request pending"))
+    }
+    Ok(())
+}
+// echo_protocol_0 - router_0 - producer
+//
+// A producer that refuses every Request ends echo_protocol_0's ping with a
+// Nacked error long before its retries would have run out.
+pub fn nacks_end_requests_early() -> Result<()> {
+    let ops = Operations::turned_off();
+    let mut broker0 = Broker::new(ops.label("router_0"), Role::Mesh);
+    let mut echo_protocol0: Echo = Protocol::new(PrivateIdentityInterface::new_key(), ops.label("echo_protocol_0"));
+    let mut links = vec![];
+    let (id0, id1) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_0", "link_1"), echo_protocol0.peer_with_link(id0.clone())?, broker0.peer_with_link(id1.clone())?, (id0, id1))?);
+    // the producer sits directly on link_3 instead of a protocol
+    let (p2l_tx, p2l_rx) = bounded::<InterLinkPacket>(BOUNDED_BUFFER_SIZE);
+    let (l2p_tx, l2p_rx) = unbounded::<InterLinkPacket>();
+    let (id2, id3) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_2", "link_3"), broker0.peer_with_link(id2.clone())?, (l2p_tx, p2l_rx), (id2, id3.clone()))?);
+    echo_protocol0.run()?;
+    broker0.run()?;
+    for (left, right) in &mut links {
+        left.run()?;
+        right.run()?;
+    }
+    let producer_sid = PrivateIdentityInterface::new_key();
+    let producer_pid = producer_sid.public_id();
+    let producer = std::thread::spawn(move || {
+        while let Ok(ilp) = l2p_rx.recv_timeout(Duration::from_secs(3)) {
+            if let NarrowWaistPacket::Request { hbfi, .. } = ilp.narrow_waist() {
+                let nw = NarrowWaistPacket::nack(producer_sid.clone(), hbfi, NackReason::Failed)?;
+                p2l_tx.send(InterLinkPacket::new(id3.clone(), LinkPacket::new(ReplyTo::Mpsc, nw)))?;
+            }
+        }
+        Ok::<(), anyhow::Error>(())
+    });
+    let started = Instant::now();
+    let ping = echo_protocol0.unreliable_sequenced_cleartext_ping(producer_pid);
+    let elapsed = started.elapsed();
+    match ping {
+        Ok(response) => return Err(anyhow!("a refusing producer answered the ping with {}", response)),
+        Err(error) => match error.downcast_ref::<Nacked>() {
+            Some(Nacked { reason: NackReason::Failed, .. }) => {},
+            _ => return Err(anyhow!("the ping failed with {} rather than a Nack", error)),
+        },
+    }
+    // five retries of 500ms each without the Nack
+    if elapsed >= Duration::from_millis(1000) {
+        return Err(anyhow!("the ping took {:?} to give up", elapsed))
+    }
+    producer.join().expect("failed to join the producer")?;
+    Ok(())
+}
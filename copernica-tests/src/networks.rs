@@ -3,7 +3,7 @@ use {
     copernica_protocols::{Echo, Protocol},
     copernica_packets::{LinkId, ReplyTo, PrivateIdentityInterface, PublicIdentityInterface},
     copernica_common::{Operations, LogEntry},
-    copernica_broker::{Broker},
+    copernica_broker::{Broker, Role},
     copernica_links::{Link, MpscChannel, MpscCorruptor, UdpIpV4, Corruption},
     crate::process_network,
     scaffolding::{ group, single, Ordering, TestTree, setting, settings::Timeout},
@@ -37,7 +37,7 @@ pub fn three_protocol_one_broker() -> Result<()> {
     let link_5 = "link_5";
     let (sender, receiver) = unbounded::<LogEntry>();
     let actual_behaviour = Operations::turned_on(sender);
-    let mut broker0 = Broker::new(actual_behaviour.label(router_0.clone()));
+    let mut broker0 = Broker::new(actual_behaviour.label(router_0.clone()), Role::Mesh);
     let echo_protocol_sid0 = PrivateIdentityInterface::new_key();
     let echo_protocol_sid1 = PrivateIdentityInterface::new_key();
     let echo_protocol_sid2 = PrivateIdentityInterface::new_key();
@@ -137,8 +137,8 @@ pub fn two_protocol_two_broker_one_protocol() -> Result<()> {
     let link_7 = "link_7";
     let (sender, receiver) = unbounded::<LogEntry>();
     let actual_behaviour = Operations::turned_on(sender);
-    let mut broker0 = Broker::new(actual_behaviour.label(router_0.clone()));
-    let mut broker1 = Broker::new(actual_behaviour.label(router_1.clone()));
+    let mut broker0 = Broker::new(actual_behaviour.label(router_0.clone()), Role::Mesh);
+    let mut broker1 = Broker::new(actual_behaviour.label(router_1.clone()), Role::Mesh);
     let echo_protocol_sid0 = PrivateIdentityInterface::new_key();
     let echo_protocol_sid1 = PrivateIdentityInterface::new_key();
     let echo_protocol_sid2 = PrivateIdentityInterface::new_key();
@@ -0,0 +1,91 @@
+use {
+    anyhow::{Result, anyhow},
+    copernica_packets::{
+        HBFI, InterLinkPacket, LinkPacket, NarrowWaistPacket, NackReason, ReplyTo, RequestOptions,
+        PrivateIdentityInterface, PublicIdentityInterface,
+    },
+    copernica_common::{Operations, constants::BOUNDED_BUFFER_SIZE},
+    copernica_broker::{Broker, Role, Action, PrefetchPolicy},
+    copernica_links::{Link},
+    crate::{mpsc_pair, link_ids},
+    scaffolding::{ group, single, Ordering, TestTree, setting, settings::Timeout},
+    crossbeam_channel::{bounded, unbounded},
+    std::{
+        time::{Duration},
+    },
+};
+pub fn prefetching(ordering: Ordering) -> TestTree {
+    group!(
+        format!("Prefetching, ordering with {:?}", ordering),
+        ordering,
+        [
+            setting!(Timeout(Duration::from_secs(10))),
+            single!(|| { prefetch_up_to_the_last_frame() }),
+        ]
+    )
+}
+// requester - router_0 - producer
+//
+// The producer serves frames 0 to 3 of an object and Nacks any frame past
+// them. Reading frame 1 after frame 0 has router_0 prefetch frames 2 to 5
+// ahead of forwarding the Request for frame 1, so the Nacks for frames 4 and
+// 5 reach it before the requester moves on. Frames 2 and 3 are answered from
+// its cache and nothing past frame 5 is ever asked for.
+pub fn prefetch_up_to_the_last_frame() -> Result<()> {
+    let ops = Operations::turned_off();
+    let mut broker0 = Broker::new(ops.label("router_0"), Role::Mesh);
+    broker0.prefetch(PrefetchPolicy { depth: 4, popularity: 1, bandwidth: u32::MAX });
+    let mut links = vec![];
+    // the requester sits directly on link_0 instead of a protocol
+    let (r2l_tx, r2l_rx) = bounded::<InterLinkPacket>(BOUNDED_BUFFER_SIZE);
+    let (l2r_tx, l2r_rx) = unbounded::<InterLinkPacket>();
+    let (id0, id1) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_0", "link_1"), (l2r_tx, r2l_rx), broker0.peer_with_link(id1.clone())?, (id0.clone(), id1))?);
+    // the producer sits directly on link_3 instead of a protocol
+    let (p2l_tx, p2l_rx) = bounded::<InterLinkPacket>(BOUNDED_BUFFER_SIZE);
+    let (l2p_tx, l2p_rx) = unbounded::<InterLinkPacket>();
+    let (id2, id3) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_2", "link_3"), broker0.peer_with_link(id2.clone())?, (l2p_tx, p2l_rx), (id2, id3.clone()))?);
+    broker0.run()?;
+    for (left, right) in &mut links {
+        left.run()?;
+        right.run()?;
+    }
+    let producer_sid = PrivateIdentityInterface::new_key();
+    let hbfi = HBFI::new(PublicIdentityInterface::Absent, producer_sid.public_id(), "prefetch", "prefetch", "prefetch", "prefetch")?;
+    let producer = std::thread::spawn(move || {
+        let mut asked = vec![];
+        while let Ok(ilp) = l2p_rx.recv_timeout(Duration::from_secs(1)) {
+            if let NarrowWaistPacket::Request { hbfi, .. } = ilp.narrow_waist() {
+                asked.push(hbfi.frm);
+                let nw = if hbfi.frm <= 3 {
+                    NarrowWaistPacket::response(producer_sid.clone(), hbfi, b"frame".to_vec())?
+                } else {
+                    NarrowWaistPacket::nack(producer_sid.clone(), hbfi, NackReason::UnknownName)?
+                };
+                p2l_tx.send(InterLinkPacket::new(id3.clone(), LinkPacket::new(ReplyTo::Mpsc, nw)))?;
+            }
+        }
+        Ok::<Vec<u64>, anyhow::Error>(asked)
+    });
+    for frm in 0..=3 {
+        let nw = NarrowWaistPacket::request(hbfi.clone().offset(frm), RequestOptions::default())?;
+        r2l_tx.send(InterLinkPacket::new(id0.clone(), LinkPacket::new(ReplyTo::Mpsc, nw)))?;
+        match l2r_rx.recv_timeout(Duration::from_secs(2))?.narrow_waist() {
+            NarrowWaistPacket::Response { hbfi, .. } if hbfi.frm == frm => {},
+            nw => return Err(anyhow!("frame {} was answered with {:?}", frm, nw)),
+        }
+    }
+    for frm in 2..=3 {
+        let cached = broker0.explain(&hbfi.clone().offset(frm)).into_iter().any(|decision| decision.action == Action::FoundInCache);
+        if !cached {
+            return Err(anyhow!("frame {} wasn't prefetched", frm))
+        }
+    }
+    let mut asked = producer.join().expect("failed to join the producer")?;
+    asked.sort();
+    if asked != vec![0, 1, 2, 3, 4, 5] {
+        return Err(anyhow!("the producer was asked for frames {:?}", asked))
+    }
+    Ok(())
+}
@@ -2,10 +2,11 @@ use {
     scaffolding::{ group, single, Ordering, TestTree, setting, settings::Timeout},
     copernica_packets::{
         ReplyTo, LinkPacket,
-        LinkId, NarrowWaistPacket, PublicIdentityInterface, PrivateIdentityInterface, HBFI,
+        LinkId, NarrowWaistPacket, PublicIdentityInterface, PrivateIdentityInterface, HBFI, CacheDigest, NackReason, Freshness, RequestOptions,
     },
+    copernica_common::constants::ANNOUNCE_MAX_DISTANCE,
     std::{
-        time::Duration,
+        time::{Duration},
     },
 };
 pub fn primitive_link_packet(ordering: Ordering) -> TestTree {
@@ -22,6 +23,25 @@ pub fn primitive_link_packet(ordering: Ordering) -> TestTree {
             single!(|| { cyphertext_link_packet_on_cleartext_response() }),
             single!(|| { cleartext_link_packet_on_cyphertext_response() }),
             single!(|| { cyphertext_link_packet_on_cyphertext_response() }),
+            single!(|| { hop_limit_survives_cyphertext_link_packet() }),
+            single!(|| { hop_limit_exhausts() }),
+            single!(|| { cyphertext_link_packet_on_signed_request() }),
+            single!(|| { lifetime_survives_signed_request() }),
+            single!(|| { counted_down_lifetime_verifies() }),
+            single!(|| { freshness_survives_cleartext_request() }),
+            single!(|| { tampered_freshness_fails() }),
+            single!(|| { freshness_survives_cyphertext_response() }),
+            single!(|| { tampered_version_fails() }),
+            single!(|| { cyphertext_link_packet_on_announce() }),
+            single!(|| { announce_distance_exhausts() }),
+            single!(|| { cyphertext_link_packet_on_digest() }),
+            single!(|| { cyphertext_link_packet_on_credit() }),
+            single!(|| { tampered_credit_fails() }),
+            single!(|| { cleartext_link_packet_on_cancel() }),
+            single!(|| { cyphertext_link_packet_on_cyphertext_cancel() }),
+            single!(|| { cleartext_link_packet_on_nack() }),
+            single!(|| { cyphertext_link_packet_on_cyphertext_nack() }),
+            single!(|| { tampered_nack_fails() }),
         ]
     )
 }
@@ -30,7 +50,7 @@ fn cleartext_link_packet_on_cleartext_request() {
     let link_sid1 = PrivateIdentityInterface::new_key();
     let link_id1 = LinkId::link_with_type(link_sid1.clone(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
     let hbfi = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "test", "test", "test", "test").unwrap();
-    let nw = NarrowWaistPacket::request(hbfi).unwrap();
+    let nw = NarrowWaistPacket::request(hbfi, RequestOptions::default()).unwrap();
     let expected_lp = LinkPacket::new(ReplyTo::Mpsc, nw);
     let actual_lps = expected_lp.as_bytes(link_id1.clone()).unwrap();
     let (_pub_id, actual_lp) = LinkPacket::from_bytes(&actual_lps, link_id1).unwrap();
@@ -43,7 +63,7 @@ fn cyphertext_link_packet_on_cleartext_request() {
     let link_id0 = LinkId::link_with_type(link_sid0.clone(), PublicIdentityInterface::new(link_sid1.public_id()), ReplyTo::Mpsc);
     let link_id1 = LinkId::link_with_type(link_sid1.clone(), PublicIdentityInterface::new(link_sid0.public_id()), ReplyTo::Mpsc);
     let hbfi = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "test", "test", "test", "test").unwrap();
-    let nw = NarrowWaistPacket::request(hbfi).unwrap();
+    let nw = NarrowWaistPacket::request(hbfi, RequestOptions::default()).unwrap();
     let expected_lp = LinkPacket::new(ReplyTo::Mpsc, nw);
     let actual_lps = expected_lp.as_bytes(link_id1.clone()).unwrap();
     let (_pub_id, actual_lp) = LinkPacket::from_bytes(&actual_lps, link_id0).unwrap();
@@ -55,7 +75,7 @@ fn cleartext_link_packet_on_cyphertext_request() {
     let link_sid1 = PrivateIdentityInterface::new_key();
     let link_id1 = LinkId::link_with_type(link_sid1.clone(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
     let hbfi = HBFI::new(PublicIdentityInterface::new(request_sid.public_id()), response_sid.public_id(), "test", "test", "test", "test").unwrap();
-    let nw = NarrowWaistPacket::request(hbfi).unwrap();
+    let nw = NarrowWaistPacket::request(hbfi, RequestOptions::default()).unwrap();
     let expected_lp = LinkPacket::new(ReplyTo::Mpsc, nw);
     let actual_lps = expected_lp.as_bytes(link_id1.clone()).unwrap();
     let (_pub_id, actual_lp) = LinkPacket::from_bytes(&actual_lps, link_id1).unwrap();
@@ -69,7 +89,7 @@ fn cyphertext_link_packet_on_cyphertext_request() {
     let link_id0 = LinkId::link_with_type(link_sid0.clone(), PublicIdentityInterface::new(link_sid1.public_id()), ReplyTo::Mpsc);
     let link_id1 = LinkId::link_with_type(link_sid1.clone(), PublicIdentityInterface::new(link_sid0.public_id()), ReplyTo::Mpsc);
     let hbfi = HBFI::new(PublicIdentityInterface::new(request_sid.public_id()), response_sid.public_id(), "test", "test", "test", "test").unwrap();
-    let nw = NarrowWaistPacket::request(hbfi).unwrap();
+    let nw = NarrowWaistPacket::request(hbfi, RequestOptions::default()).unwrap();
     let expected_lp = LinkPacket::new(ReplyTo::Mpsc, nw);
     let actual_lps = expected_lp.as_bytes(link_id1.clone()).unwrap();
     let (_pub_id, actual_lp) = LinkPacket::from_bytes(&actual_lps, link_id0).unwrap();
@@ -127,3 +147,266 @@ fn cyphertext_link_packet_on_cyphertext_response() {
     let (_pub_id, actual_lp) = LinkPacket::from_bytes(&actual_lps, link_id0).unwrap();
     assert_eq!(expected_lp, actual_lp);
 }
+fn hop_limit_survives_cyphertext_link_packet() {
+    let response_sid = PrivateIdentityInterface::new_key();
+    let link_sid0 = PrivateIdentityInterface::new_key();
+    let link_sid1 = PrivateIdentityInterface::new_key();
+    let link_id0 = LinkId::link_with_type(link_sid0.clone(), PublicIdentityInterface::new(link_sid1.public_id()), ReplyTo::Mpsc);
+    let link_id1 = LinkId::link_with_type(link_sid1.clone(), PublicIdentityInterface::new(link_sid0.public_id()), ReplyTo::Mpsc);
+    let hbfi = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "test", "test", "test", "test").unwrap();
+    let nw = NarrowWaistPacket::request(hbfi, RequestOptions::default().hop_limit(3)).unwrap();
+    let expected_lp = LinkPacket::new(ReplyTo::Mpsc, nw.decrement_hop_limit().unwrap());
+    let actual_lps = expected_lp.as_bytes(link_id1.clone()).unwrap();
+    let (_pub_id, actual_lp) = LinkPacket::from_bytes(&actual_lps, link_id0).unwrap();
+    assert_eq!(actual_lp.narrow_waist().hop_limit(), Some(2));
+    assert_eq!(expected_lp, actual_lp);
+}
+fn hop_limit_exhausts() {
+    let response_sid = PrivateIdentityInterface::new_key();
+    let hbfi = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "test", "test", "test", "test").unwrap();
+    let nw = NarrowWaistPacket::request(hbfi, RequestOptions::default().hop_limit(1)).unwrap();
+    let nw = nw.decrement_hop_limit().unwrap();
+    assert_eq!(nw.hop_limit(), Some(0));
+    assert_eq!(nw.decrement_hop_limit(), None);
+}
+fn cyphertext_link_packet_on_signed_request() {
+    let request_sid = PrivateIdentityInterface::new_key();
+    let response_sid = PrivateIdentityInterface::new_key();
+    let link_sid0 = PrivateIdentityInterface::new_key();
+    let link_sid1 = PrivateIdentityInterface::new_key();
+    let link_id0 = LinkId::link_with_type(link_sid0.clone(), PublicIdentityInterface::new(link_sid1.public_id()), ReplyTo::Mpsc);
+    let link_id1 = LinkId::link_with_type(link_sid1.clone(), PublicIdentityInterface::new(link_sid0.public_id()), ReplyTo::Mpsc);
+    let hbfi = HBFI::new(PublicIdentityInterface::new(request_sid.public_id()), response_sid.public_id(), "test", "test", "test", "test").unwrap();
+    assert!(NarrowWaistPacket::signed_request(response_sid, hbfi.clone(), RequestOptions::default()).is_err());
+    let nw = NarrowWaistPacket::signed_request(request_sid, hbfi, RequestOptions::default()).unwrap();
+    let expected_lp = LinkPacket::new(ReplyTo::Mpsc, nw.decrement_hop_limit().unwrap());
+    let actual_lps = expected_lp.as_bytes(link_id1.clone()).unwrap();
+    let (_pub_id, actual_lp) = LinkPacket::from_bytes(&actual_lps, link_id0).unwrap();
+    assert!(actual_lp.narrow_waist().is_signed());
+    assert_eq!(expected_lp, actual_lp);
+}
+fn lifetime_survives_signed_request() {
+    let request_sid = PrivateIdentityInterface::new_key();
+    let response_sid = PrivateIdentityInterface::new_key();
+    let link_sid0 = PrivateIdentityInterface::new_key();
+    let link_sid1 = PrivateIdentityInterface::new_key();
+    let link_id0 = LinkId::link_with_type(link_sid0.clone(), PublicIdentityInterface::new(link_sid1.public_id()), ReplyTo::Mpsc);
+    let link_id1 = LinkId::link_with_type(link_sid1.clone(), PublicIdentityInterface::new(link_sid0.public_id()), ReplyTo::Mpsc);
+    let hbfi = HBFI::new(PublicIdentityInterface::new(request_sid.public_id()), response_sid.public_id(), "test", "test", "test", "test").unwrap();
+    let nw = NarrowWaistPacket::signed_request(request_sid, hbfi, RequestOptions::default().lifetime(Duration::from_secs(2))).unwrap();
+    let expected_lp = LinkPacket::new(ReplyTo::Mpsc, nw.decrement_hop_limit().unwrap());
+    let actual_lps = expected_lp.as_bytes(link_id1.clone()).unwrap();
+    let (_pub_id, actual_lp) = LinkPacket::from_bytes(&actual_lps, link_id0).unwrap();
+    assert_eq!(expected_lp, actual_lp);
+    assert_eq!(actual_lp.narrow_waist().lifetime(), Some(Duration::from_secs(2)));
+    assert_eq!(NarrowWaistPacket::request(actual_lp.narrow_waist().hbfi(), RequestOptions::default()).unwrap().lifetime(), None);
+}
+fn counted_down_lifetime_verifies() {
+    let request_sid = PrivateIdentityInterface::new_key();
+    let response_sid = PrivateIdentityInterface::new_key();
+    let hbfi = HBFI::new(PublicIdentityInterface::new(request_sid.public_id()), response_sid.public_id(), "test", "test", "test", "test").unwrap();
+    let nw = NarrowWaistPacket::signed_request(request_sid, hbfi, RequestOptions::default().lifetime(Duration::from_secs(2))).unwrap();
+    for remaining in &[Duration::from_millis(500), Duration::from_millis(0)] {
+        let nw = nw.with_lifetime(*remaining);
+        assert!(nw.verify().unwrap());
+        let nw = NarrowWaistPacket::from_bytes(&nw.as_bytes()).unwrap();
+        assert_eq!(nw.lifetime(), Some(*remaining));
+    }
+}
+fn freshness_survives_cleartext_request() {
+    let response_sid = PrivateIdentityInterface::new_key();
+    let link_sid1 = PrivateIdentityInterface::new_key();
+    let link_id1 = LinkId::link_with_type(link_sid1.clone(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+    let hbfi = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "test", "test", "test", "test").unwrap();
+    let nw = NarrowWaistPacket::request(hbfi, RequestOptions::default().freshness(Freshness::MustBeFresh)).unwrap();
+    let expected_lp = LinkPacket::new(ReplyTo::Mpsc, nw);
+    let actual_lps = expected_lp.as_bytes(link_id1.clone()).unwrap();
+    let (_pub_id, actual_lp) = LinkPacket::from_bytes(&actual_lps, link_id1).unwrap();
+    assert_eq!(expected_lp, actual_lp);
+    assert_eq!(actual_lp.narrow_waist().freshness(), Freshness::MustBeFresh);
+}
+fn tampered_freshness_fails() {
+    let request_sid = PrivateIdentityInterface::new_key();
+    let response_sid = PrivateIdentityInterface::new_key();
+    let hbfi = HBFI::new(PublicIdentityInterface::new(request_sid.public_id()), response_sid.public_id(), "test", "test", "test", "test").unwrap();
+    let nw = NarrowWaistPacket::signed_request(request_sid, hbfi, RequestOptions::default().lifetime(Duration::from_secs(2)).freshness(Freshness::MustBeFresh)).unwrap();
+    assert!(nw.verify().unwrap());
+    assert_eq!(nw.lifetime(), Some(Duration::from_secs(2)));
+    let nw = match nw {
+        NarrowWaistPacket::Request { hbfi, nonce, hop_limit, lifetime, signature, .. } => {
+            NarrowWaistPacket::Request { hbfi, nonce, hop_limit, lifetime, freshness: Freshness::Any, signature }
+        },
+        nw => panic!("expected a request, got {:?}", nw),
+    };
+    assert!(!nw.verify().unwrap());
+    assert!(NarrowWaistPacket::from_bytes(&nw.as_bytes()).is_err());
+}
+fn freshness_survives_cyphertext_response() {
+    let request_sid = PrivateIdentityInterface::new_key();
+    let response_sid = PrivateIdentityInterface::new_key();
+    let link_sid0 = PrivateIdentityInterface::new_key();
+    let link_sid1 = PrivateIdentityInterface::new_key();
+    let link_id0 = LinkId::link_with_type(link_sid0.clone(), PublicIdentityInterface::new(link_sid1.public_id()), ReplyTo::Mpsc);
+    let link_id1 = LinkId::link_with_type(link_sid1.clone(), PublicIdentityInterface::new(link_sid0.public_id()), ReplyTo::Mpsc);
+    let hbfi = HBFI::new(PublicIdentityInterface::new(request_sid.public_id()), response_sid.public_id(), "test", "test", "test", "test").unwrap();
+    let fresh_for = Some(Duration::from_secs(10));
+    let nw = NarrowWaistPacket::response_with_freshness(response_sid, hbfi, "0123".as_bytes().to_vec(), fresh_for, 7).unwrap();
+    let expected_lp = LinkPacket::new(ReplyTo::Mpsc, nw);
+    let actual_lps = expected_lp.as_bytes(link_id1.clone()).unwrap();
+    let (_pub_id, actual_lp) = LinkPacket::from_bytes(&actual_lps, link_id0).unwrap();
+    assert_eq!(expected_lp, actual_lp);
+    assert_eq!(actual_lp.narrow_waist().fresh_for(), fresh_for);
+    assert_eq!(actual_lp.narrow_waist().version(), Some(7));
+    assert_eq!(actual_lp.narrow_waist().data(request_sid).unwrap(), "0123".as_bytes().to_vec());
+}
+fn tampered_version_fails() {
+    let response_sid = PrivateIdentityInterface::new_key();
+    let hbfi = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "test", "test", "test", "test").unwrap();
+    let nw = match NarrowWaistPacket::response_with_freshness(response_sid, hbfi, "0123".as_bytes().to_vec(), None, 1).unwrap() {
+        NarrowWaistPacket::Response { hbfi, nonce, signature, fresh_for, data, .. } => {
+            NarrowWaistPacket::Response { hbfi, nonce, signature, fresh_for, version: 2, data }
+        },
+        nw => panic!("expected a response, got {:?}", nw),
+    };
+    assert!(!nw.verify().unwrap());
+    assert!(NarrowWaistPacket::from_bytes(&nw.as_bytes()).is_err());
+}
+fn cyphertext_link_packet_on_announce() {
+    let response_sid = PrivateIdentityInterface::new_key();
+    let link_sid0 = PrivateIdentityInterface::new_key();
+    let link_sid1 = PrivateIdentityInterface::new_key();
+    let link_id0 = LinkId::link_with_type(link_sid0.clone(), PublicIdentityInterface::new(link_sid1.public_id()), ReplyTo::Mpsc);
+    let link_id1 = LinkId::link_with_type(link_sid1.clone(), PublicIdentityInterface::new(link_sid0.public_id()), ReplyTo::Mpsc);
+    let nw = NarrowWaistPacket::announce(response_sid, "test").unwrap();
+    // the distance isn't signed so a broker can change it
+    let expected_lp = LinkPacket::new(ReplyTo::Mpsc, nw.increment_distance().unwrap());
+    let actual_lps = expected_lp.as_bytes(link_id1.clone()).unwrap();
+    let (_pub_id, actual_lp) = LinkPacket::from_bytes(&actual_lps, link_id0).unwrap();
+    assert_eq!(actual_lp.narrow_waist().distance(), Some(1));
+    assert!(actual_lp.narrow_waist().verify().unwrap());
+    assert_eq!(expected_lp, actual_lp);
+}
+fn announce_distance_exhausts() {
+    let response_sid = PrivateIdentityInterface::new_key();
+    let mut nw = NarrowWaistPacket::announce(response_sid, "test").unwrap();
+    for _ in 0..ANNOUNCE_MAX_DISTANCE {
+        nw = nw.increment_distance().unwrap();
+    }
+    assert_eq!(nw.distance(), Some(ANNOUNCE_MAX_DISTANCE));
+    assert_eq!(nw.increment_distance(), None);
+}
+fn cyphertext_link_packet_on_digest() {
+    let response_sid = PrivateIdentityInterface::new_key();
+    let link_sid0 = PrivateIdentityInterface::new_key();
+    let link_sid1 = PrivateIdentityInterface::new_key();
+    let link_id0 = LinkId::link_with_type(link_sid0.clone(), PublicIdentityInterface::new(link_sid1.public_id()), ReplyTo::Mpsc);
+    let link_id1 = LinkId::link_with_type(link_sid1.clone(), PublicIdentityInterface::new(link_sid0.public_id()), ReplyTo::Mpsc);
+    let hbfi = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "test", "test", "test", "test").unwrap();
+    let mut digest = CacheDigest::new();
+    digest.insert(&hbfi);
+    let nw = NarrowWaistPacket::digest(link_sid1.clone(), digest).unwrap();
+    let expected_lp = LinkPacket::new(ReplyTo::Mpsc, nw);
+    let actual_lps = expected_lp.as_bytes(link_id1.clone()).unwrap();
+    let (_pub_id, actual_lp) = LinkPacket::from_bytes(&actual_lps, link_id0).unwrap();
+    match actual_lp.narrow_waist() {
+        NarrowWaistPacket::Digest { digest, .. } => {
+            assert!(digest.contains(&hbfi));
+            assert!(!digest.contains(&hbfi.clone().offset(1)));
+        },
+        nw => panic!("expected a digest, got {:?}", nw),
+    }
+    assert_eq!(expected_lp, actual_lp);
+}
+fn cyphertext_link_packet_on_credit() {
+    let link_sid0 = PrivateIdentityInterface::new_key();
+    let link_sid1 = PrivateIdentityInterface::new_key();
+    let link_id0 = LinkId::link_with_type(link_sid0.clone(), PublicIdentityInterface::new(link_sid1.public_id()), ReplyTo::Mpsc);
+    let link_id1 = LinkId::link_with_type(link_sid1.clone(), PublicIdentityInterface::new(link_sid0.public_id()), ReplyTo::Mpsc);
+    let nw = NarrowWaistPacket::credit(link_sid1.clone(), 64, u64::MAX).unwrap();
+    let expected_lp = LinkPacket::new(ReplyTo::Mpsc, nw);
+    let actual_lps = expected_lp.as_bytes(link_id1.clone()).unwrap();
+    let (_pub_id, actual_lp) = LinkPacket::from_bytes(&actual_lps, link_id0).unwrap();
+    match actual_lp.narrow_waist() {
+        NarrowWaistPacket::Credit { requests, responses, .. } => {
+            assert_eq!(requests, 64);
+            assert_eq!(responses, u64::MAX);
+        },
+        nw => panic!("expected a credit, got {:?}", nw),
+    }
+    assert_eq!(expected_lp, actual_lp);
+}
+fn tampered_credit_fails() {
+    let link_sid = PrivateIdentityInterface::new_key();
+    let nw = match NarrowWaistPacket::credit(link_sid, 1, 1).unwrap() {
+        NarrowWaistPacket::Credit { hbfi, nonce, signature, .. } => {
+            NarrowWaistPacket::Credit { hbfi, nonce, signature, requests: 1000, responses: 1 }
+        },
+        nw => panic!("expected a credit, got {:?}", nw),
+    };
+    assert!(!nw.verify().unwrap());
+    assert!(NarrowWaistPacket::from_bytes(&nw.as_bytes()).is_err());
+}
+fn cleartext_link_packet_on_cancel() {
+    let response_sid = PrivateIdentityInterface::new_key();
+    let link_sid1 = PrivateIdentityInterface::new_key();
+    let link_id1 = LinkId::link_with_type(link_sid1.clone(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+    let hbfi = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "test", "test", "test", "test").unwrap();
+    let nw = NarrowWaistPacket::cancel(hbfi.offset(3)).unwrap();
+    let expected_lp = LinkPacket::new(ReplyTo::Mpsc, nw);
+    let actual_lps = expected_lp.as_bytes(link_id1.clone()).unwrap();
+    let (_pub_id, actual_lp) = LinkPacket::from_bytes(&actual_lps, link_id1).unwrap();
+    assert_eq!(expected_lp, actual_lp);
+}
+fn cyphertext_link_packet_on_cyphertext_cancel() {
+    let request_sid = PrivateIdentityInterface::new_key();
+    let response_sid = PrivateIdentityInterface::new_key();
+    let link_sid0 = PrivateIdentityInterface::new_key();
+    let link_sid1 = PrivateIdentityInterface::new_key();
+    let link_id0 = LinkId::link_with_type(link_sid0.clone(), PublicIdentityInterface::new(link_sid1.public_id()), ReplyTo::Mpsc);
+    let link_id1 = LinkId::link_with_type(link_sid1.clone(), PublicIdentityInterface::new(link_sid0.public_id()), ReplyTo::Mpsc);
+    let hbfi = HBFI::new(PublicIdentityInterface::new(request_sid.public_id()), response_sid.public_id(), "test", "test", "test", "test").unwrap();
+    let nw = NarrowWaistPacket::cancel(hbfi).unwrap();
+    let expected_lp = LinkPacket::new(ReplyTo::Mpsc, nw);
+    let actual_lps = expected_lp.as_bytes(link_id1.clone()).unwrap();
+    let (_pub_id, actual_lp) = LinkPacket::from_bytes(&actual_lps, link_id0).unwrap();
+    assert_eq!(expected_lp, actual_lp);
+}
+fn cleartext_link_packet_on_nack() {
+    let response_sid = PrivateIdentityInterface::new_key();
+    let link_sid1 = PrivateIdentityInterface::new_key();
+    let link_id1 = LinkId::link_with_type(link_sid1.clone(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+    let hbfi = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "test", "test", "test", "test").unwrap();
+    let nw = NarrowWaistPacket::nack(response_sid, hbfi.offset(3), NackReason::UnknownName).unwrap();
+    let expected_lp = LinkPacket::new(ReplyTo::Mpsc, nw);
+    let actual_lps = expected_lp.as_bytes(link_id1.clone()).unwrap();
+    let (_pub_id, actual_lp) = LinkPacket::from_bytes(&actual_lps, link_id1).unwrap();
+    assert_eq!(expected_lp, actual_lp);
+}
+fn cyphertext_link_packet_on_cyphertext_nack() {
+    let request_sid = PrivateIdentityInterface::new_key();
+    let response_sid = PrivateIdentityInterface::new_key();
+    let link_sid0 = PrivateIdentityInterface::new_key();
+    let link_sid1 = PrivateIdentityInterface::new_key();
+    let link_id0 = LinkId::link_with_type(link_sid0.clone(), PublicIdentityInterface::new(link_sid1.public_id()), ReplyTo::Mpsc);
+    let link_id1 = LinkId::link_with_type(link_sid1.clone(), PublicIdentityInterface::new(link_sid0.public_id()), ReplyTo::Mpsc);
+    let hbfi = HBFI::new(PublicIdentityInterface::new(request_sid.public_id()), response_sid.public_id(), "test", "test", "test", "test").unwrap();
+    assert!(NarrowWaistPacket::nack(request_sid, hbfi.clone(), NackReason::Failed).is_err());
+    let nw = NarrowWaistPacket::nack(response_sid, hbfi, NackReason::Failed).unwrap();
+    let expected_lp = LinkPacket::new(ReplyTo::Mpsc, nw);
+    let actual_lps = expected_lp.as_bytes(link_id1.clone()).unwrap();
+    let (_pub_id, actual_lp) = LinkPacket::from_bytes(&actual_lps, link_id0).unwrap();
+    assert!(actual_lp.narrow_waist().is_signed());
+    assert_eq!(expected_lp, actual_lp);
+}
+fn tampered_nack_fails() {
+    let response_sid = PrivateIdentityInterface::new_key();
+    let hbfi = HBFI::new(PublicIdentityInterface::Absent, response_sid.public_id(), "test", "test", "test", "test").unwrap();
+    let nw = match NarrowWaistPacket::nack(response_sid, hbfi, NackReason::Failed).unwrap() {
+        NarrowWaistPacket::Nack { hbfi, nonce, signature, .. } => {
+            NarrowWaistPacket::Nack { hbfi, nonce, signature, reason: NackReason::UnknownName }
+        },
+        nw => panic!("expected a nack, got {:?}", nw),
+    };
+    assert!(!nw.verify().unwrap());
+    assert!(NarrowWaistPacket::from_bytes(&nw.as_bytes()).is_err());
+}
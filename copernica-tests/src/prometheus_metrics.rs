@@ -0,0 +1,94 @@
+use {
+    anyhow::{Result, anyhow},
+    copernica_protocols::{Echo, Protocol},
+    copernica_packets::{PrivateIdentityInterface},
+    copernica_common::{Operations},
+    copernica_broker::{Broker, Role},
+    copernica_links::{Link},
+    crate::{mpsc_pair, link_ids},
+    scaffolding::{ group, single, Ordering, TestTree, setting, settings::Timeout},
+    std::{
+        io::{Read, Write},
+        net::{SocketAddr, TcpStream},
+        time::Duration,
+    },
+};
+pub fn prometheus_metrics(ordering: Ordering) -> TestTree {
+    group!(
+        format!("Prometheus metrics, ordering with {:?}", ordering),
+        ordering,
+        [
+            setting!(Timeout(Duration::from_secs(10))),
+            single!(|| { scrape_after_a_ping() }),
+        ]
+    )
+}
+fn scrape(addr: SocketAddr, path: &str) -> Result<String> {
+    let mut stream = TcpStream::connect(addr)?;
+    write!(stream, "GET {} HTTP/1.0\r\n\r\n", path)?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    Ok(response)
+}
+// echo_protocol_0 - router_0 - echo_protocol_1
+//
+// After a ping both links are up, Requests were forwarded and the pongs cached.
+pub fn scrape_after_a_ping() -> Result<()> {
+    let ops = Operations::turned_off();
+    let mut broker0 = Broker::new(ops.label("router_0"), Role::Mesh);
+    let mut echo_protocol0: Echo = Protocol::new(PrivateIdentityInterface::new_key(), ops.label("echo_protocol_0"));
+    let echo_protocol_sid1 = PrivateIdentityInterface::new_key();
+    let mut echo_protocol1: Echo = Protocol::new(echo_protocol_sid1.clone(), ops.label("echo_protocol_1"));
+    let mut links = vec![];
+    let (id0, id1) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_0", "link_1"), echo_protocol0.peer_with_link(id0.clone())?, broker0.peer_with_link(id1.clone())?, (id0, id1.clone()))?);
+    let (id2, id3) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_2", "link_3"), broker0.peer_with_link(id2.clone())?, echo_protocol1.peer_with_link(id3.clone())?, (id2.clone(), id3))?);
+    echo_protocol0.run()?;
+    echo_protocol1.run()?;
+    broker0.run()?;
+    for (left, right) in &mut links {
+        left.run()?;
+        right.run()?;
+    }
+    let addr = broker0.serve_metrics("127.0.0.1:0".parse()?)?;
+    let before = scrape(addr, "/metrics")?;
+    if !before.contains(&format!("copernica_link_up{{broker=\"router_0\",link=\"{}\"}} 0\n", id1.lookup_id()?)) {
+        return Err(anyhow!("the consumer's link should be down before the ping:\n{}", before))
+    }
+    let echo_protocol_pid1 = echo_protocol_sid1.public_id();
+    let response = std::thread::spawn(move || {
+        echo_protocol0.unreliable_sequenced_cleartext_ping(echo_protocol_pid1)
+    }).join().expect("failed to extract data from JoinHandle")?;
+    if response != "pingpong" {
+        return Err(anyhow!("the ping returned {}", response))
+    }
+    let after = scrape(addr, "/metrics")?;
+    if !after.starts_with("HTTP/1.0 200 OK\r\n") || !after.contains("Content-Type: text/plain; version=0.0.4\r\n") {
+        return Err(anyhow!("not a Prometheus response:\n{}", after))
+    }
+    for link_id in [&id1, &id2].iter() {
+        let up = format!("copernica_link_up{{broker=\"router_0\",link=\"{}\"}} 1\n", link_id.lookup_id()?);
+        if !after.contains(&up) {
+            return Err(anyhow!("missing {}in:\n{}", up, after))
+        }
+    }
+    let value = |name: &str| -> Option<u64> {
+        after.lines()
+            .find(|line| line.starts_with(name))
+            .and_then(|line| line.rsplit(' ').next())
+            .and_then(|value| value.parse().ok())
+    };
+    match value("copernica_requests_total{broker=\"router_0\",action=\"forwarded\"}") {
+        Some(forwarded) if forwarded > 0 => {},
+        _ => return Err(anyhow!("no forwarded Requests in:\n{}", after)),
+    }
+    match value("copernica_cache_entries{broker=\"router_0\"}") {
+        Some(entries) if entries > 0 => {},
+        _ => return Err(anyhow!("nothing cached in:\n{}", after)),
+    }
+    if !scrape(addr, "/")?.starts_with("HTTP/1.0 404 Not Found\r\n") {
+        return Err(anyhow!("only /metrics is served"))
+    }
+    Ok(())
+}
@@ -3,7 +3,7 @@ use {
     copernica_protocols::{Echo, Protocol},
     copernica_packets::{LinkId, ReplyTo, PrivateIdentityInterface, PublicIdentityInterface},
     copernica_common::{Operations, LogEntry},
-    copernica_broker::{Broker},
+    copernica_broker::{Broker, Role},
     copernica_links::{Link, MpscChannel, MpscCorruptor, UdpIpV4, Corruption},
     crate::process_network,
     scaffolding::{ group, single, Ordering, TestTree, setting, settings::Timeout},
@@ -39,8 +39,8 @@ pub fn reliable_sequenced_cleartext_ping_pong_corrupt_immune() -> Result<()> {
     let link_5 = "link_5";
     let (sender, receiver) = unbounded::<LogEntry>();
     let actual_behaviour = Operations::turned_on(sender);
-    let mut broker0 = Broker::new(actual_behaviour.label(router_0.clone()));
-    let mut broker1 = Broker::new(actual_behaviour.label(router_1.clone()));
+    let mut broker0 = Broker::new(actual_behaviour.label(router_0.clone()), Role::Mesh);
+    let mut broker1 = Broker::new(actual_behaviour.label(router_1.clone()), Role::Mesh);
     let echo_protocol_sid0 = PrivateIdentityInterface::new_key();
     let echo_protocol_sid1 = PrivateIdentityInterface::new_key();
     let mut echo_protocol0: Echo = Protocol::new(echo_protocol_sid0.clone(), actual_behaviour.label(echo_protocol_0.clone()));
@@ -137,8 +137,8 @@ pub fn reliable_sequenced_cleartext_ping_pong_corrupt_integrity() -> Result<()>
     let link_5 = "link_5";
     let (sender, receiver) = unbounded::<LogEntry>();
     let actual_behaviour = Operations::turned_on(sender);
-    let mut broker0 = Broker::new(actual_behaviour.label(router_0.clone()));
-    let mut broker1 = Broker::new(actual_behaviour.label(router_1.clone()));
+    let mut broker0 = Broker::new(actual_behaviour.label(router_0.clone()), Role::Mesh);
+    let mut broker1 = Broker::new(actual_behaviour.label(router_1.clone()), Role::Mesh);
     let echo_protocol_sid0 = PrivateIdentityInterface::new_key();
     let echo_protocol_sid1 = PrivateIdentityInterface::new_key();
     let mut echo_protocol0: Echo = Protocol::new(echo_protocol_sid0.clone(), actual_behaviour.label(echo_protocol_0.clone()));
@@ -237,8 +237,8 @@ pub fn reliable_sequenced_cleartext_ping_pong_corrupt_order() -> Result<()> {
     let link_5 = "link_5";
     let (sender, receiver) = unbounded::<LogEntry>();
     let actual_behaviour = Operations::turned_on(sender);
-    let mut broker0 = Broker::new(actual_behaviour.label(router_0.clone()));
-    let mut broker1 = Broker::new(actual_behaviour.label(router_1.clone()));
+    let mut broker0 = Broker::new(actual_behaviour.label(router_0.clone()), Role::Mesh);
+    let mut broker1 = Broker::new(actual_behaviour.label(router_1.clone()), Role::Mesh);
     let echo_protocol_sid0 = PrivateIdentityInterface::new_key();
     let echo_protocol_sid1 = PrivateIdentityInterface::new_key();
     let mut echo_protocol0: Echo = Protocol::new(echo_protocol_sid0.clone(), actual_behaviour.label(echo_protocol_0.clone()));
@@ -337,8 +337,8 @@ pub fn reliable_sequenced_cleartext_ping_pong_corrupt_presence() -> Result<()> {
     let link_5 = "link_5";
     let (sender, receiver) = unbounded::<LogEntry>();
     let actual_behaviour = Operations::turned_on(sender);
-    let mut broker0 = Broker::new(actual_behaviour.label(router_0.clone()));
-    let mut broker1 = Broker::new(actual_behaviour.label(router_1.clone()));
+    let mut broker0 = Broker::new(actual_behaviour.label(router_0.clone()), Role::Mesh);
+    let mut broker1 = Broker::new(actual_behaviour.label(router_1.clone()), Role::Mesh);
     let echo_protocol_sid0 = PrivateIdentityInterface::new_key();
     let echo_protocol_sid1 = PrivateIdentityInterface::new_key();
     let mut echo_protocol0: Echo = Protocol::new(echo_protocol_sid0.clone(), actual_behaviour.label(echo_protocol_0.clone()));
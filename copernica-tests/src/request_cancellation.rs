@@ -0,0 +1,66 @@
+use {
+    anyhow::{Result, anyhow},
+    copernica_protocols::{Echo, Protocol},
+    copernica_packets::{PrivateIdentityInterface},
+    copernica_common::{Operations},
+    copernica_broker::{Broker, Role},
+    copernica_links::{Link},
+    crate::{mpsc_pair, link_ids, eventually},
+    scaffolding::{ group, single, Ordering, TestTree, setting, settings::Timeout},
+    std::{
+        time::Duration,
+    },
+};
+pub fn request_cancellation(ordering: Ordering) -> TestTree {
+    group!(
+        format!("Request cancellation, ordering with {:?}", ordering),
+        ordering,
+        [
+            setting!(Timeout(Duration::from_secs(10))),
+            single!(|| { abandoned_requests_are_cancelled_upstream() }),
+        ]
+    )
+}
+// echo_protocol_0 - router_0 - router_1
+//
+// Nobody answers the ping, so its Requests wait in both brokers' pending
+// tables, which would hold them for a minute. Once echo_protocol_0 runs out
+// of retries its Cancels clear router_0, and router_0's clear router_1.
+pub fn abandoned_requests_are_cancelled_upstream() -> Result<()> {
+    let ops = Operations::turned_off();
+    let mut broker0 = Broker::new(ops.label("router_0"), Role::Mesh);
+    let mut broker1 = Broker::new(ops.label("router_1"), Role::Mesh);
+    broker0.pending_lifetime(Duration::from_secs(60));
+    broker1.pending_lifetime(Duration::from_secs(60));
+    let mut echo_protocol0: Echo = Protocol::new(PrivateIdentityInterface::new_key(), ops.label("echo_protocol_0"));
+    let mut links = vec![];
+    let (id0, id1) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_0", "link_1"), echo_protocol0.peer_with_link(id0.clone())?, broker0.peer_with_link(id1.clone())?, (id0, id1))?);
+    let (id2, id3) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_2", "link_3"), broker0.peer_with_link(id2.clone())?, broker1.peer_with_link(id3.clone())?, (id2, id3))?);
+    echo_protocol0.run()?;
+    broker0.run()?;
+    broker1.run()?;
+    for (left, right) in &mut links {
+        left.run()?;
+        right.run()?;
+    }
+    let absent_pid = PrivateIdentityInterface::new_key().public_id();
+    let ping = std::thread::spawn(move || {
+        echo_protocol0.unreliable_sequenced_cleartext_ping(absent_pid)
+    });
+    if !eventually(Duration::from_secs(2), || !broker1.pending().is_empty()) {
+        return Err(anyhow!("the ping's Requests never reached router_1"))
+    }
+    match ping.join().expect("failed to extract data from JoinHandle") {
+        Ok(response) if response == "pingpong" => return Err(anyhow!("an absent producer answered the ping")),
+        _ => {},
+    }
+    // the Cancels cross both brokers
+    for (label, broker) in &[("router_0", &broker0), ("router_1", &broker1)] {
+        if !eventually(Duration::from_secs(2), || broker.pending().is_empty()) {
+            return Err(anyhow!("{} still holds {} pending Requests", label, broker.pending().len()))
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,84 @@
+use {
+    anyhow::{Result, anyhow},
+    copernica_packets::{
+        ReplyTo, HBFI, InterLinkPacket, LinkPacket, NarrowWaistPacket, RequestOptions,
+        PrivateIdentityInterface, PublicIdentityInterface,
+    },
+    copernica_common::{Operations, constants::BOUNDED_BUFFER_SIZE},
+    copernica_broker::{Broker, Role, Action},
+    copernica_links::{Link},
+    crate::{mpsc_pair, link_ids},
+    scaffolding::{ group, single, Ordering, TestTree, setting, settings::Timeout},
+    crossbeam_channel::{bounded, unbounded},
+    std::{
+        time::{Duration, Instant},
+    },
+};
+pub fn request_deadlines(ordering: Ordering) -> TestTree {
+    group!(
+        format!("Request deadlines, ordering with {:?}", ordering),
+        ordering,
+        [
+            setting!(Timeout(Duration::from_secs(10))),
+            single!(|| { expired_requests_are_dropped() }),
+        ]
+    )
+}
+// requester - router_0 - router_1
+//
+// A Request whose requester has already given up is dropped by router_0
+// and never reaches router_1. One still waited on is forwarded with what's
+// left of its lifetime, and neither router holds it pending any longer
+// than its requester waits, however long the pending lifetime is.
+pub fn expired_requests_are_dropped() -> Result<()> {
+    let ops = Operations::turned_off();
+    let mut broker0 = Broker::new(ops.label("router_0"), Role::Mesh);
+    let mut broker1 = Broker::new(ops.label("router_1"), Role::Mesh);
+    broker0.pending_lifetime(Duration::from_secs(60));
+    broker1.pending_lifetime(Duration::from_secs(60));
+    let mut links = vec![];
+    // the requester sits directly on link_0 instead of a protocol
+    let (r2l_tx, r2l_rx) = bounded::<InterLinkPacket>(BOUNDED_BUFFER_SIZE);
+    let (l2r_tx, _l2r_rx) = unbounded::<InterLinkPacket>();
+    let (id0, id1) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_0", "link_1"), (l2r_tx, r2l_rx), broker0.peer_with_link(id1.clone())?, (id0.clone(), id1))?);
+    let (id2, id3) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_2", "link_3"), broker0.peer_with_link(id2.clone())?, broker1.peer_with_link(id3.clone())?, (id2, id3))?);
+    broker0.run()?;
+    broker1.run()?;
+    for (left, right) in &mut links {
+        left.run()?;
+        right.run()?;
+    }
+    let nobody = PrivateIdentityInterface::new_key();
+    let hbfi = HBFI::new(PublicIdentityInterface::Absent, nobody.public_id(), "deadline", "deadline", "deadline", "deadline")?;
+    let expired = hbfi.clone().offset(0);
+    let waiting = hbfi.clone().offset(1);
+    let nw = NarrowWaistPacket::request(expired.clone(), RequestOptions::default().lifetime(Duration::from_millis(0)))?;
+    r2l_tx.send(InterLinkPacket::new(id0.clone(), LinkPacket::new(ReplyTo::Mpsc, nw)))?;
+    let nw = NarrowWaistPacket::request(waiting.clone(), RequestOptions::default().lifetime(Duration::from_millis(500)))?;
+    r2l_tx.send(InterLinkPacket::new(id0.clone(), LinkPacket::new(ReplyTo::Mpsc, nw)))?;
+    std::thread::sleep(Duration::from_millis(200));
+    let decisions = broker0.explain(&expired);
+    if decisions.len() != 1 || decisions[0].action != Action::DroppedExpired {
+        return Err(anyhow!("router_0 didn't drop the expired Request: {:?}", decisions))
+    }
+    if broker0.stats().requests.expired != 1 {
+        return Err(anyhow!("router_0 counted {} expired Requests", broker0.stats().requests.expired))
+    }
+    if !broker1.explain(&expired).is_empty() {
+        return Err(anyhow!("the expired Request reached router_1"))
+    }
+    if broker1.explain(&waiting).is_empty() {
+        return Err(anyhow!("the Request still waited on never reached router_1"))
+    }
+    for (name, broker) in &[("router_0", &broker0), ("router_1", &broker1)] {
+        let pending = broker.pending();
+        match pending.iter().find(|(pending_hbfi, _)| *pending_hbfi == waiting) {
+            Some((_, pending)) if pending.expiry <= Instant::now() + Duration::from_millis(500) => {},
+            Some((_, pending)) => return Err(anyhow!("{} holds the Request for another {:?}", name, pending.expiry - Instant::now())),
+            None => return Err(anyhow!("{} isn't holding the Request still waited on", name)),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,105 @@
+use {
+    anyhow::{Result, anyhow},
+    copernica_protocols::{Echo, Protocol},
+    copernica_packets::{
+        LinkId, ReplyTo, HBFI, InterLinkPacket, LinkPacket, NarrowWaistPacket, RequestOptions,
+        PrivateIdentityInterface, PublicIdentityInterface,
+    },
+    copernica_common::{Operations, LogEntry, constants::BOUNDED_BUFFER_SIZE},
+    copernica_broker::{Broker, Role},
+    copernica_links::{Link, MpscChannel},
+    crate::{eventually},
+    scaffolding::{ group, single, Ordering, TestTree, setting, settings::Timeout},
+    crossbeam_channel::{bounded, unbounded},
+    std::{
+        time::Duration,
+    },
+};
+pub fn request_flood(ordering: Ordering) -> TestTree {
+    group!(
+        format!("Request flood, ordering with {:?}", ordering),
+        ordering,
+        [
+            setting!(Timeout(Duration::from_secs(10))),
+            single!(|| { unanswerable_request_flood() }),
+        ]
+    )
+}
+// An attacker floods the broker with Requests nobody will answer. The choke
+// link climbs the Defcon levels: Defcon 2 drops the unsigned Requests and
+// alerts, Defcon 1 drops everything, so only the first few get forwarded.
+pub fn unanswerable_request_flood() -> Result<()> {
+    let router_0 = "router_0";
+    let echo_protocol_0 = "echo_protocol_0";
+    let link_0 = "link_0";
+    let link_1 = "link_1";
+    let link_2 = "link_2";
+    let link_3 = "link_3";
+    let flood_size = 200;
+    let (sender, receiver) = unbounded::<LogEntry>();
+    let actual_behaviour = Operations::turned_on(sender);
+    let mut broker0 = Broker::new(actual_behaviour.label(router_0.clone()), Role::Mesh);
+    let echo_protocol_sid0 = PrivateIdentityInterface::new_key();
+    let mut echo_protocol0: Echo = Protocol::new(echo_protocol_sid0.clone(), actual_behaviour.label(echo_protocol_0.clone()));
+    let link_sid0 = PrivateIdentityInterface::new_key();
+    let link_sid1 = PrivateIdentityInterface::new_key();
+    let link_id0 = LinkId::link_with_type(link_sid0.clone(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+    let link_id1 = LinkId::link_with_type(link_sid1.clone(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+    let mut link0: MpscChannel = Link::new(link_id0.clone(), actual_behaviour.label(link_0.clone()), broker0.peer_with_link(link_id0.clone())?)?;
+    let mut link1: MpscChannel = Link::new(link_id1.clone(), actual_behaviour.label(link_1.clone()), echo_protocol0.peer_with_link(link_id1.clone())?)?;
+    link0.female(link1.male());
+    link1.female(link0.male());
+    let link_sid2 = PrivateIdentityInterface::new_key();
+    let link_sid3 = PrivateIdentityInterface::new_key();
+    let link_id2 = LinkId::link_with_type(link_sid2.clone(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+    let link_id3 = LinkId::link_with_type(link_sid3.clone(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+    // the attacker sits directly on link_3 instead of a protocol
+    let (a2l_tx, a2l_rx) = bounded::<InterLinkPacket>(BOUNDED_BUFFER_SIZE);
+    let (l2a_tx, l2a_rx) = unbounded::<InterLinkPacket>();
+    let mut link2: MpscChannel = Link::new(link_id2.clone(), actual_behaviour.label(link_2.clone()), broker0.peer_with_link(link_id2.clone())?)?;
+    let mut link3: MpscChannel = Link::new(link_id3.clone(), actual_behaviour.label(link_3.clone()), (l2a_tx, a2l_rx))?;
+    link2.female(link3.male());
+    link3.female(link2.male());
+
+    echo_protocol0.run()?;
+    link0.run()?;
+    link1.run()?;
+    broker0.run()?;
+    link2.run()?;
+    link3.run()?;
+    let attacker = std::thread::spawn(move || {
+        let nobody = PrivateIdentityInterface::new_key();
+        let hbfi = HBFI::new(PublicIdentityInterface::Absent, nobody.public_id(), "flood", "flood", "flood", "flood")?;
+        for frm in 0..flood_size {
+            let nw = NarrowWaistPacket::request(hbfi.clone().offset(frm), RequestOptions::default())?;
+            let lp = LinkPacket::new(ReplyTo::Mpsc, nw);
+            a2l_tx.send(InterLinkPacket::new(link_id3.clone(), lp))?;
+        }
+        Ok::<(), anyhow::Error>(())
+    });
+    attacker.join().expect("failed to join the attacker")?;
+    // the router decides on every Request one way or another
+    if !eventually(Duration::from_secs(5), || broker0.stats().requests.total() >= flood_size) {
+        return Err(anyhow!("the router didn't get through the flood"))
+    }
+    drop(l2a_rx);
+    actual_behaviour.end();
+    let mut forwarded = 0;
+    let mut alerts = 0;
+    loop {
+        match receiver.recv()? {
+            LogEntry::End => break,
+            log_entry if log_entry == LogEntry::forward_request_upstream(router_0.clone()) => forwarded += 1,
+            log_entry if log_entry == LogEntry::alert(router_0.clone(), 2) => alerts += 1,
+            log_entry if log_entry == LogEntry::alert(router_0.clone(), 1) => alerts += 1,
+            _ => {},
+        }
+    }
+    if alerts == 0 {
+        return Err(anyhow!("the request flood raised no alerts"))
+    }
+    if forwarded >= flood_size / 4 {
+        return Err(anyhow!("{} of {} flooded requests were forwarded", forwarded, flood_size))
+    }
+    Ok(())
+}
@@ -0,0 +1,94 @@
+use {
+    anyhow::{Result, anyhow},
+    copernica_packets::{
+        ReplyTo, HBFI, InterLinkPacket, LinkPacket, NarrowWaistPacket, Freshness, RequestOptions,
+        PrivateIdentityInterface, PublicIdentityInterface,
+    },
+    copernica_common::{Operations, constants::BOUNDED_BUFFER_SIZE},
+    copernica_broker::{Broker, Role, Action},
+    copernica_links::{Link},
+    crate::{mpsc_pair, link_ids},
+    scaffolding::{ group, single, Ordering, TestTree, setting, settings::Timeout},
+    crossbeam_channel::{bounded, unbounded},
+    std::{
+        time::{Duration},
+    },
+};
+pub fn response_freshness(ordering: Ordering) -> TestTree {
+    group!(
+        format!("Response freshness, ordering with {:?}", ordering),
+        ordering,
+        [
+            setting!(Timeout(Duration::from_secs(10))),
+            single!(|| { stale_responses_are_refetched() }),
+        ]
+    )
+}
+// requester - router_0 - producer
+//
+// The producer numbers each Response it sends and lets it stay fresh for
+// 200ms. router_0 serves its cached copy while it's fresh and evicts it once
+// it's stale, so any Request after that goes back to the producer, as does
+// every Request that wants the latest version.
+pub fn stale_responses_are_refetched() -> Result<()> {
+    let ops = Operations::turned_off();
+    let mut broker0 = Broker::new(ops.label("router_0"), Role::Mesh);
+    let mut links = vec![];
+    // the requester sits directly on link_0 instead of a protocol
+    let (r2l_tx, r2l_rx) = bounded::<InterLinkPacket>(BOUNDED_BUFFER_SIZE);
+    let (l2r_tx, l2r_rx) = unbounded::<InterLinkPacket>();
+    let (id0, id1) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_0", "link_1"), (l2r_tx, r2l_rx), broker0.peer_with_link(id1.clone())?, (id0.clone(), id1))?);
+    // the producer sits directly on link_3 instead of a protocol
+    let (p2l_tx, p2l_rx) = bounded::<InterLinkPacket>(BOUNDED_BUFFER_SIZE);
+    let (l2p_tx, l2p_rx) = unbounded::<InterLinkPacket>();
+    let (id2, id3) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_2", "link_3"), broker0.peer_with_link(id2.clone())?, (l2p_tx, p2l_rx), (id2, id3.clone()))?);
+    broker0.run()?;
+    for (left, right) in &mut links {
+        left.run()?;
+        right.run()?;
+    }
+    let producer_sid = PrivateIdentityInterface::new_key();
+    let hbfi = HBFI::new(PublicIdentityInterface::Absent, producer_sid.public_id(), "fresh", "fresh", "fresh", "fresh")?;
+    let produced = hbfi.clone();
+    let producer = std::thread::spawn(move || {
+        let mut version = 0;
+        while let Ok(ilp) = l2p_rx.recv_timeout(Duration::from_secs(3)) {
+            // frames router_0 prefetches are left unanswered
+            match ilp.narrow_waist() {
+                NarrowWaistPacket::Request { hbfi, .. } if hbfi == produced => {
+                    version += 1;
+                    let fresh_for = Some(Duration::from_millis(200));
+                    let nw = NarrowWaistPacket::response_with_freshness(producer_sid.clone(), hbfi, b"fresh".to_vec(), fresh_for, version)?;
+                    p2l_tx.send(InterLinkPacket::new(id3.clone(), LinkPacket::new(ReplyTo::Mpsc, nw)))?;
+                },
+                _ => {},
+            }
+        }
+        Ok::<(), anyhow::Error>(())
+    });
+    let request = |freshness: Freshness| -> Result<u64> {
+        let nw = NarrowWaistPacket::request(hbfi.clone(), RequestOptions::default().freshness(freshness))?;
+        r2l_tx.send(InterLinkPacket::new(id0.clone(), LinkPacket::new(ReplyTo::Mpsc, nw)))?;
+        match l2r_rx.recv_timeout(Duration::from_secs(2))?.narrow_waist().version() {
+            Some(version) => Ok(version),
+            None => Err(anyhow!("expected a response to a {} request", freshness)),
+        }
+    };
+    let mut versions = vec![request(Freshness::Any)?, request(Freshness::MustBeFresh)?];
+    std::thread::sleep(Duration::from_millis(300));
+    versions.push(request(Freshness::Any)?);
+    versions.push(request(Freshness::MustBeFresh)?);
+    versions.push(request(Freshness::Latest)?);
+    versions.push(request(Freshness::Any)?);
+    if versions != vec![1, 1, 2, 2, 3, 3] {
+        return Err(anyhow!("the requester got versions {:?}", versions))
+    }
+    let cached = broker0.explain(&hbfi).into_iter().filter(|decision| decision.action == Action::FoundInCache).count();
+    if cached != 3 {
+        return Err(anyhow!("router_0 answered {} Requests from its cache", cached))
+    }
+    producer.join().expect("failed to join the producer")?;
+    Ok(())
+}
@@ -0,0 +1,166 @@
+use {
+    anyhow::{Result, anyhow},
+    copernica_protocols::{Echo, Protocol},
+    copernica_packets::{LinkId, ReplyTo, HBFI, PrivateIdentityInterface, PublicIdentityInterface},
+    copernica_common::{Operations, LogEntry},
+    copernica_broker::{Broker, Role, Action, Skipped},
+    copernica_links::{Link, MpscChannel},
+    scaffolding::{ group, single, Ordering, TestTree, setting, settings::Timeout},
+    crossbeam_channel::{unbounded},
+    std::{
+        time::Duration,
+    },
+};
+pub fn routing_explanations(ordering: Ordering) -> TestTree {
+    group!(
+        format!("Routing explanations, ordering with {:?}", ordering),
+        ordering,
+        [
+            setting!(Timeout(Duration::from_secs(5))),
+            single!(|| { explain_forwarded_requests() }),
+            single!(|| { explain_announced_routes() }),
+        ]
+    )
+}
+// Every Request of a ping is forwarded to the responder's link, passing over
+// the choke link and the link it came in on.
+pub fn explain_forwarded_requests() -> Result<()> {
+    let router_0 = "router_0";
+    let echo_protocol_0 = "echo_protocol_0";
+    let echo_protocol_1 = "echo_protocol_1";
+    let (sender, receiver) = unbounded::<LogEntry>();
+    let actual_behaviour = Operations::turned_on(sender);
+    let mut broker0 = Broker::new(actual_behaviour.label(router_0.clone()), Role::Mesh);
+    let echo_protocol_sid0 = PrivateIdentityInterface::new_key();
+    let mut echo_protocol0: Echo = Protocol::new(echo_protocol_sid0.clone(), actual_behaviour.label(echo_protocol_0.clone()));
+    let mut echo_protocol1: Echo = Protocol::new(PrivateIdentityInterface::new_key(), actual_behaviour.label(echo_protocol_1.clone()));
+    let link_id0 = LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+    let link_id1 = LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+    let mut link0: MpscChannel = Link::new(link_id0.clone(), actual_behaviour.label("link_0"), broker0.peer_with_link(link_id0.clone())?)?;
+    let mut link1: MpscChannel = Link::new(link_id1.clone(), actual_behaviour.label("link_1"), echo_protocol0.peer_with_link(link_id1.clone())?)?;
+    link0.female(link1.male());
+    link1.female(link0.male());
+    let link_id2 = LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+    let link_id3 = LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+    let mut link2: MpscChannel = Link::new(link_id2.clone(), actual_behaviour.label("link_2"), broker0.peer_with_link(link_id2.clone())?)?;
+    let mut link3: MpscChannel = Link::new(link_id3.clone(), actual_behaviour.label("link_3"), echo_protocol1.peer_with_link(link_id3.clone())?)?;
+    link2.female(link3.male());
+    link3.female(link2.male());
+
+    echo_protocol0.run()?;
+    link0.run()?;
+    link1.run()?;
+    broker0.run()?;
+    link2.run()?;
+    link3.run()?;
+    echo_protocol1.run()?;
+    let echo_protocol_pid0 = echo_protocol_sid0.public_id();
+    let response = std::thread::spawn(move || {
+        let data = echo_protocol1.unreliable_sequenced_cleartext_ping(echo_protocol_pid0);
+        actual_behaviour.end();
+        data
+    });
+    let mut explained = 0;
+    loop {
+        match receiver.recv()? {
+            LogEntry::End => break,
+            LogEntry::Decision(_) => explained += 1,
+            _ => {},
+        }
+    }
+    response.join().expect("failed to extract data from JoinHandle")?;
+    if explained != 8 {
+        return Err(anyhow!("{} of 8 routing decisions were emitted", explained))
+    }
+    let hbfi = HBFI::new(PublicIdentityInterface::Absent, echo_protocol_sid0.public_id(), "echo", "echo", "echo", "unreliable_sequenced_echo")?;
+    for frm in 0..8 {
+        let decisions = broker0.explain(&hbfi.clone().offset(frm));
+        if decisions.len() != 1 {
+            return Err(anyhow!("frame {} has {} routing decisions", frm, decisions.len()))
+        }
+        let decision = &decisions[0];
+        if decision.from != link_id2 || decision.action != Action::Forwarded(vec![link_id0.clone()]) {
+            return Err(anyhow!("frame {} was routed unexpectedly: {}", frm, decision))
+        }
+        for candidate in &decision.candidates {
+            let expected = if candidate.linkid == LinkId::choke() {
+                Some(Skipped::Choke)
+            } else if candidate.linkid == link_id2 {
+                Some(Skipped::SameLink)
+            } else {
+                None
+            };
+            if candidate.skipped != expected {
+                return Err(anyhow!("frame {} explained {:?} wrongly: {}", frm, candidate.linkid, decision))
+            }
+        }
+    }
+    Ok(())
+}
+// The responder announces itself, so its link outranks a third link Bayes
+// knows nothing about, though the Request is still forwarded on both.
+pub fn explain_announced_routes() -> Result<()> {
+    let router_0 = "router_0";
+    let echo_protocol_0 = "echo_protocol_0";
+    let echo_protocol_1 = "echo_protocol_1";
+    let echo_protocol_2 = "echo_protocol_2";
+    let ops = Operations::turned_off();
+    let mut broker0 = Broker::new(ops.label(router_0.clone()), Role::Mesh);
+    let echo_protocol_sid0 = PrivateIdentityInterface::new_key();
+    let mut echo_protocol0: Echo = Protocol::new(echo_protocol_sid0.clone(), ops.label(echo_protocol_0.clone()));
+    let mut echo_protocol1: Echo = Protocol::new(PrivateIdentityInterface::new_key(), ops.label(echo_protocol_1.clone()));
+    let mut echo_protocol2: Echo = Protocol::new(PrivateIdentityInterface::new_key(), ops.label(echo_protocol_2.clone()));
+    let link_id0 = LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+    let link_id1 = LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+    let mut link0: MpscChannel = Link::new(link_id0.clone(), ops.label("link_0"), broker0.peer_with_link(link_id0.clone())?)?;
+    let mut link1: MpscChannel = Link::new(link_id1.clone(), ops.label("link_1"), echo_protocol0.peer_with_link(link_id1.clone())?)?;
+    link0.female(link1.male());
+    link1.female(link0.male());
+    let link_id2 = LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+    let link_id3 = LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+    let mut link2: MpscChannel = Link::new(link_id2.clone(), ops.label("link_2"), broker0.peer_with_link(link_id2.clone())?)?;
+    let mut link3: MpscChannel = Link::new(link_id3.clone(), ops.label("link_3"), echo_protocol1.peer_with_link(link_id3.clone())?)?;
+    link2.female(link3.male());
+    link3.female(link2.male());
+    let link_id4 = LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+    let link_id5 = LinkId::link_with_type(PrivateIdentityInterface::new_key(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+    let mut link4: MpscChannel = Link::new(link_id4.clone(), ops.label("link_4"), broker0.peer_with_link(link_id4.clone())?)?;
+    let mut link5: MpscChannel = Link::new(link_id5.clone(), ops.label("link_5"), echo_protocol2.peer_with_link(link_id5.clone())?)?;
+    link4.female(link5.male());
+    link5.female(link4.male());
+
+    echo_protocol0.run()?;
+    link0.run()?;
+    link1.run()?;
+    broker0.run()?;
+    link2.run()?;
+    link3.run()?;
+    echo_protocol1.run()?;
+    link4.run()?;
+    link5.run()?;
+    echo_protocol2.run()?;
+    echo_protocol0.announce()?;
+    std::thread::sleep(Duration::from_millis(100));
+    let echo_protocol_pid0 = echo_protocol_sid0.public_id();
+    let response = std::thread::spawn(move || {
+        echo_protocol1.unreliable_sequenced_cleartext_ping(echo_protocol_pid0)
+    });
+    let actual_response = response.join().expect("failed to extract data from JoinHandle")?;
+    if actual_response != "pingpong" {
+        return Err(anyhow!("the ping returned {}", actual_response))
+    }
+    let hbfi = HBFI::new(PublicIdentityInterface::Absent, echo_protocol_sid0.public_id(), "echo", "echo", "echo", "unreliable_sequenced_echo")?;
+    let decisions = broker0.explain(&hbfi);
+    if decisions.len() != 1 {
+        return Err(anyhow!("frame 0 has {} routing decisions", decisions.len()))
+    }
+    let decision = &decisions[0];
+    if decision.action != Action::Forwarded(vec![link_id0.clone(), link_id4.clone()]) {
+        return Err(anyhow!("frame 0 was routed unexpectedly: {}", decision))
+    }
+    let weight = |link_id: &LinkId| decision.candidates.iter().find(|candidate| candidate.linkid == *link_id).map(|candidate| candidate.weight);
+    match (weight(&link_id0), weight(&link_id4)) {
+        (Some(announced), Some(unannounced)) if announced > unannounced => Ok(()),
+        _ => Err(anyhow!("the announced link didn't outrank the unannounced one: {}", decision)),
+    }
+}
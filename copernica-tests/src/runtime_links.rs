@@ -0,0 +1,97 @@
+use {
+    anyhow::{Result, anyhow},
+    copernica_protocols::{Echo, Protocol},
+    copernica_packets::{LinkId, HBFI, PrivateIdentityInterface, PublicIdentity, PublicIdentityInterface},
+    copernica_common::{Operations},
+    copernica_broker::{Broker, Role},
+    copernica_links::{Link},
+    crate::{mpsc_pair, link_ids},
+    scaffolding::{ group, single, Ordering, TestTree, setting, settings::Timeout},
+    std::{
+        time::Duration,
+    },
+};
+pub fn runtime_links(ordering: Ordering) -> TestTree {
+    group!(
+        format!("Runtime links, ordering with {:?}", ordering),
+        ordering,
+        [
+            setting!(Timeout(Duration::from_secs(10))),
+            single!(|| { inspect_and_relink_a_running_broker() }),
+        ]
+    )
+}
+fn ping(mut echo_protocol: Echo, echo_protocol_pid: PublicIdentity) -> Result<Echo> {
+    let (echo_protocol, response) = std::thread::spawn(move || {
+        let response = echo_protocol.unreliable_sequenced_cleartext_ping(echo_protocol_pid);
+        (echo_protocol, response)
+    }).join().expect("failed to extract data from JoinHandle");
+    match response? {
+        response if response == "pingpong" => Ok(echo_protocol),
+        response => Err(anyhow!("the ping returned {}", response)),
+    }
+}
+fn weight(broker: &Broker, hbfi: &HBFI, link_id: &LinkId) -> Option<f64> {
+    broker.weights(hbfi)
+        .into_iter()
+        .find(|weight| weight.linkid.lookup_id().ok() == link_id.lookup_id().ok())
+        .map(|weight| weight.weight)
+}
+// echo_protocol_0 - router_0 - router_1 - echo_protocol_1
+//
+// After a ping router_0 holds the pongs and weighs the link to router_1
+// above the consumer's. Its cache is flushed and the link to router_1 is
+// swapped for a new one while both brokers run, the second ping takes it.
+pub fn inspect_and_relink_a_running_broker() -> Result<()> {
+    let ops = Operations::turned_off();
+    let mut broker0 = Broker::new(ops.label("router_0"), Role::Mesh);
+    let mut broker1 = Broker::new(ops.label("router_1"), Role::Mesh);
+    let mut echo_protocol0: Echo = Protocol::new(PrivateIdentityInterface::new_key(), ops.label("echo_protocol_0"));
+    let echo_protocol_sid1 = PrivateIdentityInterface::new_key();
+    let mut echo_protocol1: Echo = Protocol::new(echo_protocol_sid1.clone(), ops.label("echo_protocol_1"));
+    let mut links = vec![];
+    let (id0, id1) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_0", "link_1"), echo_protocol0.peer_with_link(id0.clone())?, broker0.peer_with_link(id1.clone())?, (id0, id1.clone()))?);
+    let (id2, id3) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_2", "link_3"), broker0.peer_with_link(id2.clone())?, broker1.peer_with_link(id3.clone())?, (id2.clone(), id3))?);
+    let (id4, id5) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_4", "link_5"), broker1.peer_with_link(id4.clone())?, echo_protocol1.peer_with_link(id5.clone())?, (id4, id5))?);
+    echo_protocol0.run()?;
+    echo_protocol1.run()?;
+    broker0.run()?;
+    broker1.run()?;
+    for (left, right) in &mut links {
+        left.run()?;
+        right.run()?;
+    }
+    let echo_protocol0 = ping(echo_protocol0, echo_protocol_sid1.public_id())?;
+    let hbfi = HBFI::new(PublicIdentityInterface::Absent, echo_protocol_sid1.public_id(), "echo", "echo", "echo", "unreliable_sequenced_echo")?;
+    let cached = broker0.cached();
+    if !cached.iter().any(|cached| cached.response_pid == hbfi.response_pid) {
+        return Err(anyhow!("router_0 didn't cache the pongs: {:?}", cached))
+    }
+    match (weight(&broker0, &hbfi, &id2), weight(&broker0, &hbfi, &id1)) {
+        (Some(producer), Some(consumer)) if producer > consumer => {},
+        weights => return Err(anyhow!("unexpected weights toward router_1 and the consumer: {:?}", weights)),
+    }
+    broker0.flush_cache();
+    if !broker0.cached().is_empty() {
+        return Err(anyhow!("the flushed cache still holds {} Responses", broker0.cached().len()))
+    }
+    broker0.unpeer(&id2)?;
+    if broker0.unpeer(&id2).is_ok() {
+        return Err(anyhow!("unpeered the same link twice"))
+    }
+    if weight(&broker0, &hbfi, &id2).is_some() || broker0.stats().links.iter().any(|link| link.link_id.lookup_id().ok() == id2.lookup_id().ok()) {
+        return Err(anyhow!("router_0 still knows the unpeered link"))
+    }
+    let (id6, id7) = link_ids();
+    let (mut left, mut right) = mpsc_pair(&ops, ("link_6", "link_7"), broker0.peer_with_link(id6.clone())?, broker1.peer_with_link(id7.clone())?, (id6.clone(), id7))?;
+    left.run()?;
+    right.run()?;
+    ping(echo_protocol0, echo_protocol_sid1.public_id())?;
+    if weight(&broker0, &hbfi, &id6).is_none() {
+        return Err(anyhow!("router_0 didn't learn the new link"))
+    }
+    Ok(())
+}
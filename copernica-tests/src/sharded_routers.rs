@@ -0,0 +1,77 @@
+use {
+    anyhow::{Result, anyhow},
+    copernica_protocols::{Echo, Protocol},
+    copernica_packets::{PrivateIdentityInterface},
+    copernica_common::{Operations},
+    copernica_broker::{Broker, Role},
+    copernica_links::{Link},
+    crate::{mpsc_pair, link_ids},
+    scaffolding::{ group, single, Ordering, TestTree, setting, settings::Timeout},
+    std::{
+        time::Duration,
+    },
+};
+pub fn sharded_routers(ordering: Ordering) -> TestTree {
+    group!(
+        format!("Sharded routers, ordering with {:?}", ordering),
+        ordering,
+        [
+            setting!(Timeout(Duration::from_secs(10))),
+            single!(|| { concurrent_pings_through_sharded_routers() }),
+        ]
+    )
+}
+// echo_protocol_0 -\                    /- echo_protocol_3
+// echo_protocol_1 -- router_0 - router_1 -- echo_protocol_4
+// echo_protocol_2 -/                    \- echo_protocol_5
+//
+// router_0 routes on 4 threads and router_1 on 3, echo_protocol_3, 4 and 5
+// ping echo_protocol_0, 1 and 2 at the same time.
+pub fn concurrent_pings_through_sharded_routers() -> Result<()> {
+    let ops = Operations::turned_off();
+    let mut broker0 = Broker::new(ops.label("router_0"), Role::Mesh);
+    let mut broker1 = Broker::new(ops.label("router_1"), Role::Mesh);
+    broker0.router_threads(4)?;
+    broker1.router_threads(3)?;
+    let mut links = vec![];
+    let (id0, id1) = link_ids();
+    links.push(mpsc_pair(&ops, ("link_0", "link_1"), broker0.peer_with_link(id0.clone())?, broker1.peer_with_link(id1.clone())?, (id0, id1))?);
+    let mut producers = vec![];
+    let mut consumers = vec![];
+    for n in 0..3 {
+        let producer_sid = PrivateIdentityInterface::new_key();
+        let mut producer: Echo = Protocol::new(producer_sid.clone(), ops.label(&format!("echo_protocol_{}", n)));
+        let mut consumer: Echo = Protocol::new(PrivateIdentityInterface::new_key(), ops.label(&format!("echo_protocol_{}", n + 3)));
+        let (id2, id3) = link_ids();
+        let labels = (format!("link_{}", 4 * n + 2), format!("link_{}", 4 * n + 3));
+        links.push(mpsc_pair(&ops, (&labels.0, &labels.1), broker0.peer_with_link(id2.clone())?, producer.peer_with_link(id3.clone())?, (id2, id3))?);
+        let (id4, id5) = link_ids();
+        let labels = (format!("link_{}", 4 * n + 4), format!("link_{}", 4 * n + 5));
+        links.push(mpsc_pair(&ops, (&labels.0, &labels.1), broker1.peer_with_link(id4.clone())?, consumer.peer_with_link(id5.clone())?, (id4, id5))?);
+        producer.run()?;
+        consumer.run()?;
+        producers.push((producer, producer_sid));
+        consumers.push(consumer);
+    }
+    broker0.run()?;
+    broker1.run()?;
+    for (left, right) in &mut links {
+        left.run()?;
+        right.run()?;
+    }
+    let pings: Vec<_> = consumers
+        .into_iter()
+        .zip(producers.iter())
+        .map(|(mut consumer, (_, producer_sid))| {
+            let producer_pid = producer_sid.public_id();
+            std::thread::spawn(move || consumer.unreliable_sequenced_cleartext_ping(producer_pid))
+        })
+        .collect();
+    for ping in pings {
+        let response = ping.join().expect("failed to extract data from JoinHandle")?;
+        if response != "pingpong" {
+            return Err(anyhow!("a ping through sharded routers returned {}", response))
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,116 @@
+use {
+    anyhow::{Result, anyhow},
+    copernica_protocols::{Echo, Protocol},
+    copernica_packets::{LinkId, ReplyTo, PrivateIdentityInterface, PublicIdentityInterface},
+    copernica_common::{Operations, LogEntry, constants::SNAPSHOT_INTERVAL},
+    copernica_broker::{Broker, Role},
+    copernica_links::{Link, MpscChannel},
+    crate::common::generate_random_dir_name,
+    scaffolding::{ group, single, Ordering, TestTree, setting, settings::Timeout},
+    crossbeam_channel::{unbounded},
+    std::{
+        time::Duration,
+    },
+};
+pub fn warm_restart(ordering: Ordering) -> TestTree {
+    group!(
+        format!("Warm restart, ordering with {:?}", ordering),
+        ordering,
+        [
+            setting!(Timeout(Duration::from_secs(10))),
+            single!(|| { restored_broker_serves_from_cache() }),
+        ]
+    )
+}
+// A broker is shut down after a ping and a new broker restores its snapshot.
+// The responder is gone the second time round, so the ping only succeeds if
+// the restored cache answers every Request.
+pub fn restored_broker_serves_from_cache() -> Result<()> {
+    let router_0 = "router_0";
+    let echo_protocol_0 = "echo_protocol_0";
+    let echo_protocol_1 = "echo_protocol_1";
+    let echo_protocol_2 = "echo_protocol_2";
+    let mut path = generate_random_dir_name();
+    path.push("router_0.snapshot");
+    let echo_protocol_sid0 = PrivateIdentityInterface::new_key();
+    let link_sid0 = PrivateIdentityInterface::new_key();
+    let link_sid1 = PrivateIdentityInterface::new_key();
+    let link_sid2 = PrivateIdentityInterface::new_key();
+    let link_sid3 = PrivateIdentityInterface::new_key();
+    {
+        let ops = Operations::turned_off();
+        let mut broker0 = Broker::new(ops.label(router_0.clone()), Role::Mesh);
+        broker0.persist(path.clone(), Duration::from_millis(SNAPSHOT_INTERVAL));
+        let mut echo_protocol0: Echo = Protocol::new(echo_protocol_sid0.clone(), ops.label(echo_protocol_0.clone()));
+        let mut echo_protocol1: Echo = Protocol::new(PrivateIdentityInterface::new_key(), ops.label(echo_protocol_1.clone()));
+        let link_id0 = LinkId::link_with_type(link_sid0.clone(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+        let link_id1 = LinkId::link_with_type(link_sid1.clone(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+        let mut link0: MpscChannel = Link::new(link_id0.clone(), ops.label("link_0"), broker0.peer_with_link(link_id0.clone())?)?;
+        let mut link1: MpscChannel = Link::new(link_id1.clone(), ops.label("link_1"), echo_protocol0.peer_with_link(link_id1.clone())?)?;
+        link0.female(link1.male());
+        link1.female(link0.male());
+        let link_id2 = LinkId::link_with_type(link_sid2.clone(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+        let link_id3 = LinkId::link_with_type(link_sid3.clone(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+        let mut link2: MpscChannel = Link::new(link_id2.clone(), ops.label("link_2"), broker0.peer_with_link(link_id2.clone())?)?;
+        let mut link3: MpscChannel = Link::new(link_id3.clone(), ops.label("link_3"), echo_protocol1.peer_with_link(link_id3.clone())?)?;
+        link2.female(link3.male());
+        link3.female(link2.male());
+
+        echo_protocol0.run()?;
+        link0.run()?;
+        link1.run()?;
+        broker0.run()?;
+        link2.run()?;
+        link3.run()?;
+        echo_protocol1.run()?;
+        let echo_protocol_pid0 = echo_protocol_sid0.public_id();
+        let response = std::thread::spawn(move || {
+            echo_protocol1.unreliable_sequenced_cleartext_ping(echo_protocol_pid0)
+        });
+        let first_response = response.join().expect("failed to extract data from JoinHandle")?;
+        if first_response != "pingpong" {
+            return Err(anyhow!("the first ping returned {}", first_response))
+        }
+        broker0.shutdown()?;
+    }
+    let (sender, receiver) = unbounded::<LogEntry>();
+    let actual_behaviour = Operations::turned_on(sender);
+    let mut broker0 = Broker::new(actual_behaviour.label(router_0.clone()), Role::Mesh);
+    let mut echo_protocol2: Echo = Protocol::new(PrivateIdentityInterface::new_key(), actual_behaviour.label(echo_protocol_2.clone()));
+    // the same link_sid as before the restart with a new lookup id
+    let link_id2 = LinkId::link_with_type(link_sid2.clone(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+    let link_id3 = LinkId::link_with_type(link_sid3.clone(), PublicIdentityInterface::Absent, ReplyTo::Mpsc);
+    let mut link2: MpscChannel = Link::new(link_id2.clone(), actual_behaviour.label("link_2"), broker0.peer_with_link(link_id2.clone())?)?;
+    let mut link3: MpscChannel = Link::new(link_id3.clone(), actual_behaviour.label("link_3"), echo_protocol2.peer_with_link(link_id3.clone())?)?;
+    link2.female(link3.male());
+    link3.female(link2.male());
+    broker0.restore(&path)?;
+
+    broker0.run()?;
+    link2.run()?;
+    link3.run()?;
+    echo_protocol2.run()?;
+    let response = std::thread::spawn(move || {
+        let data = echo_protocol2.unreliable_sequenced_cleartext_ping(echo_protocol_sid0.public_id());
+        actual_behaviour.end();
+        data
+    });
+    let mut found = 0;
+    let mut forwarded = 0;
+    loop {
+        match receiver.recv()? {
+            LogEntry::End => break,
+            log_entry if log_entry == LogEntry::found_response_upstream(router_0.clone()) => found += 1,
+            log_entry if log_entry == LogEntry::forward_request_upstream(router_0.clone()) => forwarded += 1,
+            _ => {},
+        }
+    }
+    let actual_response = response.join().expect("failed to extract data from JoinHandle")?;
+    if actual_response != "pingpong" {
+        return Err(anyhow!("the ping after the restart returned {}", actual_response))
+    }
+    if found != 8 || forwarded != 0 {
+        return Err(anyhow!("restored broker found {} cached and forwarded {} of 8 requests", found, forwarded))
+    }
+    Ok(())
+}
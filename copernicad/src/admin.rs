@@ -0,0 +1,248 @@
+use {
+    crate::{
+        config::{LinkConfig, LinkType},
+        daemon::{Daemon},
+    },
+    copernica_packets::{HBFI, LinkId, PublicIdentity, PublicIdentityInterface},
+    anyhow::{anyhow, Result},
+    log::{error, warn, LevelFilter},
+    std::{
+        fmt::Write as _,
+        fs,
+        io::{BufRead, BufReader, Write},
+        os::unix::{fs::{DirBuilderExt, PermissionsExt}, net::{UnixListener, UnixStream}},
+        path::Path,
+        str::FromStr,
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    },
+};
+// How long a client may take to send its command or read the reply before
+// it's dropped, connections are answered one at a time.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+pub const USAGE: &str = "\
+links                                              the links and their state
+add-link <name> udpipv4 <bind> <remote> [<remote_identity>] [upstream]
+remove-link <name>
+pending                                            the pending table
+cache                                              the cached Responses
+weights <cop://uri>                                the Bayes weight of each link for the HBFI
+weights <response_pid> <app> <m0d> <fun> <arg> [<frm>] [<request_pid>]
+                                                   the same, with the HBFI given in parts
+flush                                              empties the cache
+log <off|error|warn|info|debug|trace>
+";
+#[derive(Clone, Debug)]
+pub enum Command {
+    Links,
+    AddLink(LinkConfig),
+    RemoveLink(String),
+    Pending,
+    Cache,
+    Weights(HBFI),
+    Flush,
+    Log(LevelFilter),
+    Help,
+}
+impl FromStr for Command {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        match words.as_slice() {
+            ["links"] => Ok(Command::Links),
+            ["add-link", name, "udpipv4", bind, remote, rest @ ..] => {
+                let (remote_identity, upstream) = match rest {
+                    [] => (None, false),
+                    ["upstream"] => (None, true),
+                    [pid] => (Some(pid.to_string()), false),
+                    [pid, "upstream"] => (Some(pid.to_string()), true),
+                    _ => return Err(anyhow!("unknown command: {}, try help", s.trim())),
+                };
+                Ok(Command::AddLink(LinkConfig {
+                    name: name.to_string(),
+                    link_type: LinkType::UdpIpV4,
+                    bind: bind.parse()?,
+                    remote: remote.parse()?,
+                    remote_identity,
+                    upstream,
+                }))
+            },
+            ["remove-link", name] => Ok(Command::RemoveLink(name.to_string())),
+            ["pending"] => Ok(Command::Pending),
+            ["cache"] => Ok(Command::Cache),
+            ["weights", uri] => Ok(Command::Weights(uri.parse()?)),
+            ["weights", response_pid, app, m0d, fun, arg, rest @ ..] if rest.len() <= 2 => {
+                let response_pid = PublicIdentity::from_str(response_pid)?;
+                let frm = match rest.first() {
+                    Some(frm) => frm.parse()?,
+                    None => 0,
+                };
+                let request_pid = match rest.get(1) {
+                    Some(request_pid) => PublicIdentityInterface::new(PublicIdentity::from_str(request_pid)?),
+                    None => PublicIdentityInterface::Absent,
+                };
+                Ok(Command::Weights(HBFI::new(request_pid, response_pid, app, m0d, fun, arg)?.offset(frm)))
+            },
+            ["flush"] => Ok(Command::Flush),
+            ["log", level] => level.parse().map(Command::Log).map_err(|_| anyhow!("unknown log level: {}", level)),
+            ["help"] | [] => Ok(Command::Help),
+            _ => Err(anyhow!("unknown command: {}, try help", s.trim())),
+        }
+    }
+}
+impl Command {
+    pub fn run(self, daemon: &mut Daemon) -> Result<String> {
+        let mut out = String::new();
+        match self {
+            Command::Links => {
+                let stats = daemon.broker.stats();
+                let now = Instant::now();
+                for (link, link_id) in daemon.links() {
+                    let lookup_id = link_id.lookup_id()?;
+                    // a link's stats may be under any of the addresses it replied from
+                    let stats: Vec<_> = stats.links.iter().filter(|stats| stats.link_id.lookup_id().ok() == Some(lookup_id)).collect();
+                    let up = stats.iter().any(|stats| stats.is_up(now));
+                    let received: u64 = stats.iter().map(|stats| stats.received).sum();
+                    let sent: u64 = stats.iter().map(|stats| stats.queue.sent).sum();
+                    let dropped: u64 = stats.iter().map(|stats| stats.queue.dropped).sum();
+                    let queued: usize = stats.iter().map(|stats| stats.queue.total_depth()).sum();
+                    writeln!(out, "{} {} {} {} -> {} {} received {} sent {} dropped {} queued {}",
+                        link.name, lookup_id, if up { "up" } else { "down" }, link.bind, link.remote,
+                        if link.upstream { "upstream" } else { "downstream" }, received, sent, dropped, queued)?;
+                }
+            },
+            Command::AddLink(link) => {
+                let name = link.name.clone();
+                daemon.add_link(link)?;
+                writeln!(out, "added {}", name)?;
+            },
+            Command::RemoveLink(name) => {
+                daemon.remove_link(&name)?;
+                writeln!(out, "removed {}", name)?;
+            },
+            Command::Pending => {
+                let now = Instant::now();
+                for (hbfi, pending) in daemon.broker.pending() {
+                    write!(out, "{} expires in {}ms downstream", hbfi, pending.expiry.saturating_duration_since(now).as_millis())?;
+                    for link_id in &pending.downstream {
+                        write!(out, " {}", link(daemon, link_id))?;
+                    }
+                    write!(out, " upstream")?;
+                    for link_id in &pending.upstream {
+                        write!(out, " {}", link(daemon, link_id))?;
+                    }
+                    writeln!(out)?;
+                }
+            },
+            Command::Cache => {
+                for hbfi in daemon.broker.cached() {
+                    writeln!(out, "{}", hbfi)?;
+                }
+            },
+            Command::Weights(hbfi) => {
+                for weight in daemon.broker.weights(&hbfi) {
+                    writeln!(out, "{} {:.6}", link(daemon, &weight.linkid), weight.weight)?;
+                }
+            },
+            Command::Flush => {
+                let cached = daemon.broker.cache_stats().entries;
+                daemon.broker.flush_cache();
+                writeln!(out, "flushed {} responses", cached)?;
+            },
+            Command::Log(level) => {
+                log::set_max_level(level);
+                writeln!(out, "log level {}", level)?;
+            },
+            Command::Help => out.push_str(USAGE),
+        }
+        Ok(out)
+    }
+}
+fn link(daemon: &Daemon, link_id: &LinkId) -> String {
+    match (daemon.link_name(link_id), link_id.lookup_id()) {
+        (Some(name), _) => name.to_string(),
+        (None, Ok(lookup_id)) => lookup_id.to_string(),
+        (None, Err(_)) => "choke".into(),
+    }
+}
+// Answers one command per connection on a socket only its owner may use.
+// The socket is bound in a directory only the owner can enter and moved into
+// place once it's locked down, it's never reachable with the umask's mode.
+pub fn serve(path: &Path, daemon: Arc<Mutex<Daemon>>) -> Result<()> {
+    let name = path.file_name().ok_or_else(|| anyhow!("no socket name in {}", path.display()))?;
+    let private = path.with_file_name(format!(".{}.{}", name.to_string_lossy(), std::process::id()));
+    if private.exists() {
+        fs::remove_dir_all(&private)?;
+    }
+    fs::DirBuilder::new().mode(0o700).create(&private)?;
+    let bound = private.join(name);
+    let listener = UnixListener::bind(&bound)
+        .and_then(|listener| fs::set_permissions(&bound, fs::Permissions::from_mode(0o600)).map(|_| listener))
+        .and_then(|listener| fs::rename(&bound, path).map(|_| listener));
+    fs::remove_dir_all(&private)?;
+    let listener = listener?;
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(error) = answer(stream, &daemon) {
+                        warn!("admin: {}", error);
+                    }
+                },
+                Err(error) => error!("admin: {}", error),
+            }
+        }
+    });
+    Ok(())
+}
+fn answer(stream: UnixStream, daemon: &Mutex<Daemon>) -> Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let reply = line
+        .parse::<Command>()
+        .and_then(|command| command.run(&mut daemon.lock().unwrap()))
+        .unwrap_or_else(|error| format!("error: {}\n", error));
+    (&stream).write_all(reply.as_bytes())?;
+    Ok(())
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use copernica_packets::PrivateIdentityInterface;
+
+    #[test]
+    fn test_parse_commands() {
+        let producer = PrivateIdentityInterface::new_key().public_id();
+        match "add-link lan udpipv4 0.0.0.0:50001 192.168.1.20:50001".parse::<Command>().unwrap() {
+            Command::AddLink(link) => {
+                assert_eq!(link.name, "lan");
+                assert_eq!(link.remote_identity, None);
+            },
+            command => panic!("parsed {:?}", command),
+        }
+        match format!("add-link core udpipv4 0.0.0.0:50002 10.0.0.1:50002 {} upstream", producer).parse::<Command>().unwrap() {
+            Command::AddLink(link) => {
+                assert_eq!(link.remote_identity, Some(producer.to_string()));
+                assert!(link.upstream);
+            },
+            command => panic!("parsed {:?}", command),
+        }
+        match format!("weights {} echo echo echo ping 3", producer).parse::<Command>().unwrap() {
+            Command::Weights(hbfi) => {
+                assert_eq!(hbfi.frm, 3);
+                assert_eq!(hbfi, HBFI::new(PublicIdentityInterface::Absent, producer.clone(), "echo", "echo", "echo", "ping").unwrap().offset(3));
+            },
+            command => panic!("parsed {:?}", command),
+        }
+        match format!("weights cop://{}/echo/echo/echo/ping#3", producer).parse::<Command>().unwrap() {
+            Command::Weights(hbfi) => assert_eq!(hbfi, HBFI::new(PublicIdentityInterface::Absent, producer, "echo", "echo", "echo", "ping").unwrap().offset(3)),
+            command => panic!("parsed {:?}", command),
+        }
+        assert!(matches!("log debug\n".parse::<Command>().unwrap(), Command::Log(LevelFilter::Debug)));
+        assert!(matches!("".parse::<Command>().unwrap(), Command::Help));
+        assert!("add-link lan rf 0.0.0.0:1 0.0.0.0:2".parse::<Command>().is_err());
+        assert!("remove-link".parse::<Command>().is_err());
+    }
+}
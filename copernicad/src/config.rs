@@ -0,0 +1,227 @@
+use {
+    copernica_broker::{CachePolicy, Role},
+    copernica_packets::{PublicIdentity, PublicIdentityInterface},
+    anyhow::{anyhow, Result},
+    cryptoxide::digest::Digest as _,
+    rand::RngCore,
+    serde::{de, Deserialize, Deserializer},
+    std::{
+        fmt,
+        fs::{self, OpenOptions},
+        io::Write,
+        net::{SocketAddr, SocketAddrV4},
+        os::unix::fs::OpenOptionsExt,
+        path::{Path, PathBuf},
+        str::FromStr,
+    },
+};
+pub const SEED_SIZE: usize = 32;
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub broker: BrokerConfig,
+    pub cache: Option<CacheConfig>,
+    #[serde(default)]
+    pub policy: PolicyConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    pub admin: Option<AdminConfig>,
+    #[serde(default, rename = "link")]
+    pub links: Vec<LinkConfig>,
+}
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BrokerConfig {
+    #[serde(default = "default_label")]
+    pub label: String,
+    // a file holding the hex encoded seed of the broker's key, made if missing
+    pub identity: PathBuf,
+    #[serde(default = "default_role", deserialize_with = "parse")]
+    pub role: Role,
+    #[serde(default = "default_router_threads")]
+    pub router_threads: usize,
+}
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CacheConfig {
+    // bytes of encoded Responses
+    pub size: usize,
+    #[serde(default = "default_cache_policy", deserialize_with = "parse")]
+    pub policy: CachePolicy,
+}
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyConfig {
+    // reloaded on SIGHUP
+    pub filter: Option<PathBuf>,
+    pub snapshot: Option<PathBuf>,
+    #[serde(default = "default_snapshot_interval")]
+    pub snapshot_interval: u64,
+    pub metrics: Option<SocketAddr>,
+    // grant peers credits so they can't send faster than the broker routes
+    #[serde(default)]
+    pub flow_control: bool,
+    // tell neighbours which Responses are cached
+    #[serde(default)]
+    pub cache_digests: bool,
+}
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LoggingConfig {
+    #[serde(default = "default_log_level", deserialize_with = "parse")]
+    pub level: log::LevelFilter,
+    pub file: Option<PathBuf>,
+}
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: default_log_level(),
+            file: None,
+        }
+    }
+}
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AdminConfig {
+    // the Unix socket copernicactl talks to
+    pub socket: PathBuf,
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum LinkType {
+    #[serde(rename = "udpipv4")]
+    UdpIpV4,
+}
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LinkConfig {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub link_type: LinkType,
+    pub bind: SocketAddrV4,
+    pub remote: SocketAddrV4,
+    // the remote link's public identity, a cleartext link without it
+    pub remote_identity: Option<String>,
+    #[serde(default)]
+    pub upstream: bool,
+}
+impl LinkConfig {
+    pub fn remote_identity(&self) -> Result<PublicIdentityInterface> {
+        match &self.remote_identity {
+            Some(pid) => {
+                let pid = PublicIdentity::from_str(pid)
+                    .map_err(|error| anyhow!("link {}: remote_identity: {}", self.name, error))?;
+                Ok(PublicIdentityInterface::new(pid))
+            },
+            None => Ok(PublicIdentityInterface::Absent),
+        }
+    }
+}
+impl Config {
+    pub fn load(path: &Path) -> Result<Self> {
+        let config = fs::read_to_string(path)
+            .map_err(|error| anyhow!("{}: {}", path.display(), error))?;
+        config.parse()
+    }
+}
+impl FromStr for Config {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let config: Config = toml::from_str(s)?;
+        for (n, link) in config.links.iter().enumerate() {
+            if config.links[..n].iter().any(|other| other.name == link.name) {
+                return Err(anyhow!("link {} is configured twice", link.name))
+            }
+        }
+        Ok(config)
+    }
+}
+// Reads the broker's seed from `path`, writing a new one readable only by
+// its owner if there's no file yet.
+pub fn identity(path: &Path) -> Result<[u8; SEED_SIZE]> {
+    if path.exists() {
+        let bytes = hex::decode(fs::read_to_string(path)?.trim())
+            .map_err(|error| anyhow!("{}: {}", path.display(), error))?;
+        let mut seed = [0u8; SEED_SIZE];
+        if bytes.len() != SEED_SIZE {
+            return Err(anyhow!("{}: expected a seed of {} bytes, found {}", path.display(), SEED_SIZE, bytes.len()))
+        }
+        seed.copy_from_slice(&bytes);
+        return Ok(seed)
+    }
+    let mut seed = [0u8; SEED_SIZE];
+    rand::thread_rng().fill_bytes(&mut seed);
+    let mut file = OpenOptions::new().write(true).create_new(true).mode(0o600).open(path)?;
+    writeln!(file, "{}", hex::encode(seed))?;
+    Ok(seed)
+}
+// Each link gets its own key from the broker's seed and the link's name, so
+// a link keeps its identity across restarts while no two links share one.
+pub fn link_seed(seed: &[u8; SEED_SIZE], name: &str) -> [u8; SEED_SIZE] {
+    let mut link_seed = [0u8; SEED_SIZE];
+    let mut b = cryptoxide::blake2b::Blake2b::new(SEED_SIZE);
+    b.input(seed);
+    b.input(name.as_bytes());
+    b.result(&mut link_seed);
+    link_seed
+}
+fn parse<'de, D, T>(deserializer: D) -> std::result::Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    let s = String::deserialize(deserializer)?;
+    s.parse().map_err(de::Error::custom)
+}
+fn default_label() -> String {
+    "copernicad".into()
+}
+fn default_role() -> Role {
+    Role::default()
+}
+fn default_router_threads() -> usize {
+    1
+}
+fn default_cache_policy() -> CachePolicy {
+    CachePolicy::Lru
+}
+fn default_snapshot_interval() -> u64 {
+    60
+}
+fn default_log_level() -> log::LevelFilter {
+    log::LevelFilter::Info
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use copernica_packets::PrivateIdentityInterface;
+
+    #[test]
+    fn test_example_config() {
+        let config: Config = include_str!("../copernicad.toml").parse().unwrap();
+        assert_eq!(config.broker.role, Role::Edge);
+        assert_eq!(config.cache.unwrap().policy, CachePolicy::TinyLfu);
+        assert_eq!(config.logging.level, log::LevelFilter::Info);
+        assert!(config.policy.flow_control);
+        assert!(config.policy.cache_digests);
+        assert_eq!(config.admin.unwrap().socket, PathBuf::from("/run/copernica/copernicad.sock"));
+        assert_eq!(config.links.len(), 2);
+        assert_eq!(config.links[0].link_type, LinkType::UdpIpV4);
+        assert!(config.links[0].upstream);
+        assert_eq!(config.links[1].remote_identity().unwrap(), PublicIdentityInterface::Absent);
+        let minimal: Config = "[broker]\nidentity = \"id.key\"".parse().unwrap();
+        assert_eq!(minimal.broker.role, Role::Mesh);
+        assert!(!minimal.policy.flow_control);
+        assert!(!minimal.policy.cache_digests);
+        assert!(minimal.links.is_empty());
+        assert!("[broker]\nidentity = \"id.key\"\nrole = \"router\"".parse::<Config>().is_err());
+    }
+    #[test]
+    fn test_links_keep_distinct_identities() {
+        let seed = [7u8; SEED_SIZE];
+        let link0 = PrivateIdentityInterface::from_seed(link_seed(&seed, "link0"));
+        let link1 = PrivateIdentityInterface::from_seed(link_seed(&seed, "link1"));
+        assert_eq!(link0.public_id(), PrivateIdentityInterface::from_seed(link_seed(&seed, "link0")).public_id());
+        assert_ne!(link0.public_id(), link1.public_id());
+    }
+}
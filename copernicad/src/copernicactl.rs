@@ -0,0 +1,36 @@
+use {
+    anyhow::{anyhow, Result},
+    std::{
+        io::{self, Read, Write},
+        net::Shutdown,
+        os::unix::net::UnixStream,
+        path::PathBuf,
+    },
+};
+const DEFAULT_SOCKET: &str = "/run/copernica/copernicad.sock";
+
+// copernicactl [--socket <path>] <command> [<argument>...]
+// Sends the command to copernicad's admin socket and prints the answer.
+fn main() -> Result<()> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let mut socket = PathBuf::from(DEFAULT_SOCKET);
+    if args.first().map(String::as_str) == Some("--socket") {
+        if args.len() < 2 {
+            return Err(anyhow!("--socket needs a path"))
+        }
+        socket = PathBuf::from(args.remove(1));
+        args.remove(0);
+    }
+    let mut stream = UnixStream::connect(&socket)
+        .map_err(|error| anyhow!("{}: {}", socket.display(), error))?;
+    writeln!(stream, "{}", args.join(" "))?;
+    stream.shutdown(Shutdown::Write)?;
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply)?;
+    if reply.starts_with("error: ") {
+        eprint!("{}", reply);
+        std::process::exit(1);
+    }
+    io::stdout().write_all(reply.as_bytes())?;
+    Ok(())
+}
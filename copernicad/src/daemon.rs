@@ -0,0 +1,70 @@
+use {
+    crate::config::{self, LinkConfig, LinkType, SEED_SIZE},
+    copernica_broker::{Broker},
+    copernica_links::{Link, UdpIpV4},
+    copernica_packets::{LinkId, PrivateIdentityInterface, ReplyTo},
+    copernica_common::{Operations},
+    anyhow::{anyhow, Result},
+    log::info,
+    std::collections::BTreeMap,
+};
+// The broker and its links by name, those from the config file and those
+// added over the admin socket since.
+pub struct Daemon {
+    pub broker: Broker,
+    ops: Operations,
+    seed: [u8; SEED_SIZE],
+    links: BTreeMap<String, (LinkConfig, LinkId)>,
+}
+impl Daemon {
+    pub fn new(broker: Broker, ops: Operations, seed: [u8; SEED_SIZE]) -> Self {
+        Self {
+            broker,
+            ops,
+            seed,
+            links: BTreeMap::new(),
+        }
+    }
+    // Peers the broker with a new link and starts it.
+    pub fn add_link(&mut self, link: LinkConfig) -> Result<()> {
+        if self.links.contains_key(&link.name) {
+            return Err(anyhow!("link {} already exists", link.name))
+        }
+        let link_sid = PrivateIdentityInterface::from_seed(config::link_seed(&self.seed, &link.name));
+        let link_id = match link.link_type {
+            LinkType::UdpIpV4 => {
+                let link_id = LinkId::link_with_type(link_sid, link.remote_identity()?, ReplyTo::UdpIpV4(link.bind));
+                let remote_id = link_id.remote(ReplyTo::UdpIpV4(link.remote))?;
+                if link.upstream {
+                    self.broker.upstream(remote_id.clone());
+                }
+                let (l2b_tx, b2l_rx, decode_failures) = self.broker.peer(remote_id.clone())?;
+                let mut udp: UdpIpV4 = Link::new(link_id.clone(), self.ops.label(&link.name), (l2b_tx, b2l_rx))?;
+                udp.count_decode_failures(decode_failures);
+                udp.run()?;
+                remote_id
+            },
+        };
+        info!("link {} {} on {} to {}", link.name, link_id.link_pid()?, link.bind, link.remote);
+        self.links.insert(link.name.clone(), (link, link_id));
+        Ok(())
+    }
+    // Unpeers the link, which stops it and frees its address.
+    pub fn remove_link(&mut self, name: &str) -> Result<()> {
+        let (_, link_id) = self.links.get(name).ok_or_else(|| anyhow!("no link {}", name))?;
+        self.broker.unpeer(link_id)?;
+        self.links.remove(name);
+        info!("link {} removed", name);
+        Ok(())
+    }
+    pub fn links(&self) -> impl Iterator<Item = &(LinkConfig, LinkId)> {
+        self.links.values()
+    }
+    pub fn link_name(&self, link_id: &LinkId) -> Option<&str> {
+        let lookup_id = link_id.lookup_id().ok()?;
+        self.links
+            .iter()
+            .find(|(_, (_, other))| other.lookup_id().ok() == Some(lookup_id))
+            .map(|(name, _)| name.as_str())
+    }
+}
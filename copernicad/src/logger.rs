@@ -0,0 +1,50 @@
+use {
+    std::{
+        io,
+        path::Path,
+    },
+    fern,
+    log,
+};
+
+// Everything is dispatched and log::set_max_level decides what's logged,
+// so the level can be changed while running.
+pub fn setup_logging(level: log::LevelFilter, logpath: Option<&Path>) -> Result<(), fern::InitError> {
+    let mut base_config = fern::Dispatch::new()
+        .level(log::LevelFilter::Trace)
+        .level_for("async_io", log::LevelFilter::Warn)
+        .level_for("polling", log::LevelFilter::Warn);
+
+    if let Some(logpath) = logpath {
+        let file_config = fern::Dispatch::new()
+            .format(|out, message, record| {
+                out.finish(format_args!(
+                    "{}[{}][{}] {}",
+                    chrono::Local::now().format("[%Y-%m-%d][%H:%M:%S]"),
+                    record.target(),
+                    record.level(),
+                    message
+                ))
+            })
+            .chain(fern::log_file(logpath)?);
+        base_config = base_config.chain(file_config);
+    }
+
+    let stderr_config = fern::Dispatch::new()
+        .format(|out, message, record| {
+            out.finish(format_args!(
+                "[{}][{}] {}",
+                record.target(),
+                record.level(),
+                message
+            ))
+        })
+        .chain(io::stderr());
+
+    base_config
+        .chain(stderr_config)
+        .apply()?;
+    log::set_max_level(level);
+
+    Ok(())
+}
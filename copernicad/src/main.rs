@@ -0,0 +1,86 @@
+mod admin;
+mod config;
+mod daemon;
+mod logger;
+use {
+    crate::{
+        config::{Config},
+        daemon::{Daemon},
+    },
+    copernica_broker::{Broker, CreditPolicy},
+    copernica_common::{Operations, constants::CACHE_DIGEST_INTERVAL},
+    anyhow::{Result},
+    log::{error, info, warn},
+    signal_hook::{
+        consts::{SIGHUP, SIGINT, SIGTERM},
+        iterator::Signals,
+    },
+    std::{
+        fs,
+        path::PathBuf,
+        sync::{Arc, Mutex},
+        time::Duration,
+    },
+};
+const DEFAULT_CONFIG: &str = "/etc/copernica/copernicad.toml";
+
+fn main() -> Result<()> {
+    let path = std::env::args().nth(1).map(PathBuf::from).unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG));
+    let config = Config::load(&path)?;
+    logger::setup_logging(config.logging.level, config.logging.file.as_deref())?;
+    let seed = config::identity(&config.broker.identity)?;
+    let ops = Operations::turned_off();
+    let mut broker = Broker::new(ops.label(&config.broker.label), config.broker.role);
+    broker.router_threads(config.broker.router_threads)?;
+    if let Some(cache) = &config.cache {
+        broker.cache(cache.policy, cache.size);
+    }
+    if let Some(filter) = &config.policy.filter {
+        broker.filter_file(filter.clone())?;
+    }
+    if config.policy.flow_control {
+        broker.flow_control(CreditPolicy::default());
+    }
+    if config.policy.cache_digests {
+        broker.cache_digests(Duration::from_millis(CACHE_DIGEST_INTERVAL));
+    }
+    let mut daemon = Daemon::new(broker, ops, seed);
+    for link in &config.links {
+        daemon.add_link(link.clone())?;
+    }
+    if let Some(snapshot) = &config.policy.snapshot {
+        if snapshot.exists() {
+            if let Err(error) = daemon.broker.restore(snapshot) {
+                warn!("starting cold, {}: {}", snapshot.display(), error);
+            }
+        }
+        daemon.broker.persist(snapshot.clone(), Duration::from_secs(config.policy.snapshot_interval));
+    }
+    daemon.broker.run()?;
+    if let Some(addr) = config.policy.metrics {
+        let addr = daemon.broker.serve_metrics(addr)?;
+        info!("serving metrics at http://{}/metrics", addr);
+    }
+    info!("{} running as a {} broker with {} links", config.broker.label, daemon.broker.role(), config.links.len());
+    let daemon = Arc::new(Mutex::new(daemon));
+    if let Some(admin) = &config.admin {
+        admin::serve(&admin.socket, daemon.clone())?;
+        info!("admin socket at {}", admin.socket.display());
+    }
+    let mut signals = Signals::new(&[SIGINT, SIGTERM, SIGHUP])?;
+    for signal in signals.forever() {
+        match signal {
+            SIGHUP => match daemon.lock().unwrap().broker.reload_filter() {
+                Ok(_) => info!("reloaded the filter policy"),
+                Err(error) => error!("keeping the current filter policy: {}", error),
+            },
+            _ => break,
+        }
+    }
+    info!("shutting down");
+    if let Some(admin) = &config.admin {
+        let _ = fs::remove_file(&admin.socket);
+    }
+    daemon.lock().unwrap().broker.shutdown()?;
+    Ok(())
+}